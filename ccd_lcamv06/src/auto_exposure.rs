@@ -0,0 +1,312 @@
+use crate::{
+    ccd::CCD,
+    error::{Error, Result},
+    response::{Frame, ACTIVE_PIXELS, SATURATION_LEVEL},
+    timing::ExposureTime,
+    IoAdapter,
+};
+use core::{fmt, fmt::Display};
+
+/// Goal and limits of `CCD::auto_exposure`
+#[derive(Debug, Clone, Copy)]
+pub struct AutoExposureOptions<'a> {
+    /// Peak signal as a fraction of full scale, which is the black level of a frame
+    pub target: f64,
+    /// Accepted deviation of peak signal from target, as a fraction of full scale
+    pub tolerance: f64,
+    /// Amount of exposure times tried before giving up
+    pub max_iterations: usize,
+    pub min: ExposureTime,
+    pub max: ExposureTime,
+    /// Pixels left out of peak signal, like known defects that are stuck near saturation
+    pub exclude: &'a [usize],
+}
+
+impl Default for AutoExposureOptions<'_> {
+    fn default() -> Self {
+        AutoExposureOptions {
+            target: 0.8,
+            tolerance: 0.05,
+            max_iterations: 10,
+            min: ExposureTime::MIN,
+            max: ExposureTime(1000),
+            exclude: &[],
+        }
+    }
+}
+
+impl AutoExposureOptions<'_> {
+    /// Checks that target is above zero and at most full scale, tolerance is not negative and
+    /// exposure limits are in order
+    pub fn validate(&self) -> Result<()> {
+        let valid = self.target > 0.0
+            && self.target <= 1.0
+            && self.tolerance >= 0.0
+            && self.min <= self.max;
+        if !valid {
+            return Err(Error::InvalidAutoExposureOptions);
+        }
+        Ok(())
+    }
+}
+
+/// Outcome of `CCD::auto_exposure`, chosen exposure time is left set on CCD
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AutoExposure {
+    pub exposure: ExposureTime,
+    /// Peak signal at chosen exposure time, as a fraction of full scale
+    pub peak: f64,
+    /// Amount of exposure times tried
+    pub iterations: usize,
+    /// Peak signal is within tolerance of target. Otherwise search ran out of iterations or
+    /// hit exposure limits, and the closest exposure time that does not saturate is chosen
+    pub converged: bool,
+}
+
+impl Display for AutoExposure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Exposure time {}, peak signal at {:.1}% of full scale after {} iterations",
+            self.exposure,
+            self.peak * 100.0,
+            self.iterations
+        )?;
+        if !self.converged {
+            write!(f, ", target was not reached")?;
+        }
+        Ok(())
+    }
+}
+
+/// Peak signal over active pixels of a frame as a fraction of full scale, and whether any of
+/// them saturated. Output is inverted, so peak is the lowest pixel and full scale is the black
+/// level
+pub(crate) fn peak_level(frame: &Frame, exclude: &[usize]) -> (f64, bool) {
+    let black = frame.black_level();
+    let lowest = ACTIVE_PIXELS
+        .filter(|idx| !exclude.contains(idx))
+        .map(|idx| frame[idx])
+        .min()
+        .unwrap_or(0);
+    let level = if black > 0.0 {
+        ((black - f64::from(lowest)) / black).clamp(0.0, 1.0)
+    } else {
+        0.0
+    };
+    (level, lowest <= SATURATION_LEVEL)
+}
+
+/// Exposure times in ms, that are known to be too short and too long. Starts out just past
+/// the limits, so search never leaves them
+struct Bounds {
+    under: u32,
+    over: u32,
+}
+
+impl Bounds {
+    fn new(options: &AutoExposureOptions) -> Self {
+        Bounds {
+            under: u32::from(options.min.as_millis()).saturating_sub(1),
+            over: u32::from(options.max.as_millis()) + 1,
+        }
+    }
+
+    /// Narrows bounds with a measurement, returns next exposure time to try. Signal is assumed
+    /// to grow linearly with exposure time, falling back to bisection when that guess is out
+    /// of bounds. None when there is nothing left to try
+    fn next(&mut self, current: u16, level: f64, saturated: bool, target: f64) -> Option<u16> {
+        let current = u32::from(current);
+        let guess = if saturated || level > target {
+            self.over = current;
+            // Saturated level underestimates signal, so halving is the only safe guess
+            if saturated {
+                current / 2
+            } else {
+                (f64::from(current) * target / level) as u32
+            }
+        } else {
+            self.under = current;
+            if level > 0.0 {
+                (f64::from(current) * target / level) as u32
+            } else {
+                self.over
+            }
+        };
+        if self.over - self.under <= 1 {
+            return None;
+        }
+        let next = if self.under < guess && guess < self.over {
+            guess
+        } else {
+            self.under + (self.over - self.under) / 2
+        };
+        u16::try_from(next).ok()
+    }
+}
+
+impl<IO> CCD<IO>
+where
+    IO: IoAdapter,
+{
+    /// Searches for exposure time that puts peak signal at target fraction of full scale,
+    /// starting from current exposure time
+    pub fn auto_exposure(&mut self, options: &AutoExposureOptions) -> Result<AutoExposure> {
+        options.validate()?;
+        let mut bounds = Bounds::new(options);
+        let mut exposure = self
+            .get_exp_time()?
+            .clamp(options.min, options.max);
+        // Longest exposure time that did not saturate, fallback if target is not reached
+        let mut best: Option<(ExposureTime, f64)> = None;
+        for iteration in 1..=options.max_iterations {
+            self.set_exp_time(exposure)?;
+            // First frame may still be integrating with previous exposure time
+            self.get_frame()?;
+            let (level, saturated) = peak_level(&self.get_frame()?, options.exclude);
+            log::debug!("Peak signal at {:.3} of full scale with {}", level, exposure);
+
+            if !saturated && (level - options.target).abs() <= options.tolerance {
+                return Ok(AutoExposure {
+                    exposure,
+                    peak: level,
+                    iterations: iteration,
+                    converged: true,
+                });
+            }
+            if !saturated && level <= options.target + options.tolerance {
+                best = best.filter(|(t, _)| *t > exposure).or(Some((exposure, level)));
+            }
+            let next = bounds
+                .next(exposure.as_millis(), level, saturated, options.target)
+                .map(ExposureTime);
+            match next {
+                Some(next) if iteration < options.max_iterations => exposure = next,
+                _ => {
+                    let (chosen, peak) = best.unwrap_or((exposure, level));
+                    if chosen != exposure {
+                        self.set_exp_time(chosen)?;
+                    }
+                    return Ok(AutoExposure {
+                        exposure: chosen,
+                        peak,
+                        iterations: iteration,
+                        converged: false,
+                    });
+                }
+            }
+        }
+        // Zero iterations were allowed, current exposure time is left as is
+        Ok(AutoExposure {
+            exposure,
+            peak: 0.0,
+            iterations: 0,
+            converged: false,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::response::FRAME_PIXEL_COUNT;
+
+    /// Linear sensor with black level of 3000, which reaches full scale at `full_ms`
+    fn simulate(exposure: u16, full_ms: f64) -> Frame {
+        let mut pixels = [3000u16; FRAME_PIXEL_COUNT];
+        let signal = (3000.0 * f64::from(exposure) / full_ms).min(3000.0);
+        pixels[1000] = (3000.0 - signal) as u16;
+        Frame::new(pixels)
+    }
+
+    fn search(start: u16, full_ms: f64) -> (u16, usize) {
+        let options = AutoExposureOptions::default();
+        let mut bounds = Bounds::new(&options);
+        let mut exposure = start;
+        for iteration in 1..=options.max_iterations {
+            let (level, saturated) = peak_level(&simulate(exposure, full_ms), &[]);
+            if !saturated && (level - options.target).abs() <= options.tolerance {
+                return (exposure, iteration);
+            }
+            match bounds.next(exposure, level, saturated, options.target) {
+                Some(next) => exposure = next,
+                None => break,
+            }
+        }
+        panic!("search did not converge, stopped at {exposure} ms");
+    }
+
+    #[test]
+    fn measure_peak_level() {
+        let (level, saturated) = peak_level(&simulate(50, 100.0), &[]);
+        assert!((level - 0.5).abs() < 1e-3);
+        assert!(!saturated);
+        assert!(peak_level(&simulate(200, 100.0), &[]).1);
+
+        // Dummy pixels past active ones and excluded defects don't count
+        let mut frame = simulate(50, 100.0);
+        frame[3690] = 0;
+        frame[2000] = 0;
+        let (level, saturated) = peak_level(&frame, &[2000]);
+        assert!((level - 0.5).abs() < 1e-3);
+        assert!(!saturated);
+    }
+
+    #[test]
+    fn converge_from_both_sides() {
+        // Linear guess lands on target right after first measurement
+        let (exposure, iterations) = search(10, 100.0);
+        assert!((75..=85).contains(&exposure));
+        assert_eq!(iterations, 2);
+
+        // Saturation gives no information about signal, so exposure is halved until it fits
+        let (exposure, _) = search(900, 50.0);
+        assert!((38..=42).contains(&exposure));
+    }
+
+    #[test]
+    fn stop_at_limits() {
+        let options = AutoExposureOptions::default();
+        let mut bounds = Bounds::new(&options);
+        // Not enough light even at longest exposure
+        assert_eq!(bounds.next(1000, 0.1, false, 0.8), None);
+        // Saturated even at shortest exposure
+        let mut bounds = Bounds::new(&options);
+        assert_eq!(bounds.next(1, 1.0, true, 0.8), None);
+    }
+
+    #[test]
+    fn reject_invalid_options() {
+        let options = AutoExposureOptions::default();
+        assert!(options.validate().is_ok());
+        let invalid = [
+            AutoExposureOptions {
+                min: ExposureTime(100),
+                max: ExposureTime(10),
+                ..options
+            },
+            AutoExposureOptions {
+                target: 0.0,
+                ..options
+            },
+            AutoExposureOptions {
+                target: 1.5,
+                ..options
+            },
+            AutoExposureOptions {
+                target: f64::NAN,
+                ..options
+            },
+            AutoExposureOptions {
+                tolerance: -0.1,
+                ..options
+            },
+        ];
+        for options in invalid {
+            assert!(matches!(
+                options.validate(),
+                Err(Error::InvalidAutoExposureOptions)
+            ));
+        }
+    }
+}
@@ -3,12 +3,14 @@ use crate::{
     error::{Error, Result},
     flags::{BaudRate, TriggerMode},
     response::{
-        parser::{align_response, parse_response},
+        parser::{align_response, parse_response, single_reading_crc_matches},
         Frame, Response, VersionDetails,
     },
+    stats::Stats,
+    timing::{AveragingCount, ExposureTime},
     IoAdapter,
 };
-use core::{mem::size_of, iter, iter::Extend};
+use core::{mem::size_of, iter, iter::Extend, time::Duration};
 use scopeguard::guard;
 
 // Sized as 2 responses in case of really unfortunate initial misalignment
@@ -25,6 +27,12 @@ where
     top: usize,
     // Keeps track if buffer was aligned after latest buffer read
     aligned: bool,
+    // Link health counters
+    stats: Stats,
+    // Exposure time as last set or read, attached to captured frames
+    exposure: Option<ExposureTime>,
+    // Averaging count as last set or read
+    averaging: Option<AveragingCount>,
 }
 
 impl<IO> CCD<IO>
@@ -37,13 +45,48 @@ where
             buf: [0; READ_BUF_SIZE],
             top: 0,
             aligned: false,
+            stats: Stats::default(),
+            exposure: None,
+            averaging: None,
         }
     }
 
+    /// Lets IO adapter know how long it takes to produce a frame with known settings, so that
+    /// long exposures don't run into read timeouts
+    fn update_frame_time(&mut self) -> Result<()> {
+        let exposure = self.exposure.map_or(0, |t| u64::from(t.as_millis()));
+        let averaging = self.averaging.map_or(1, |count| u64::from(count.get()));
+        self.io.set_frame_time(Duration::from_millis(exposure * averaging))
+    }
+
+    /// Reads exposure time and averaging count from CCD, so that captured frames carry exposure
+    /// and reads wait long enough for frames from the start. Settings persist on CCD across
+    /// sessions, so a freshly opened link knows nothing about them until they are read
+    pub fn sync_settings(&mut self) -> Result<()> {
+        self.get_exp_time()?;
+        self.get_avg_time()?;
+        Ok(())
+    }
+
+    /// Returns a snapshot of link health counters
+    pub fn stats(&self) -> Stats {
+        self.stats
+    }
+
+    /// Resets all link health counters to zero
+    pub fn reset_stats(&mut self) {
+        self.stats = Stats::default();
+    }
+
     fn fill_buffer(&mut self) -> Result<()> {
         self.aligned = false;
-        let read_bytes = self.io.read(&mut self.buf[self.top..])?;
+        let read_bytes = self.io.read(&mut self.buf[self.top..]).inspect_err(|err| {
+            if err.is_timeout() {
+                self.stats.timeouts += 1;
+            }
+        })?;
         self.top += read_bytes;
+        self.stats.bytes_read += read_bytes as u64;
         Ok(())
     }
 
@@ -54,14 +97,36 @@ where
             self.buf.rotate_left(consumed);
             self.top -= consumed;
             self.aligned = true;
+            self.stats.realignments += 1;
+            self.stats.bytes_discarded += consumed as u64;
         }
     }
 
     fn send_package(&mut self, cmd: Command) -> Result<()> {
-        self.io.write_all(&cmd.encode())?;
+        let package = cmd.encode();
+        self.io.write_all(&package)?;
+        self.stats.packages_sent += 1;
+        self.stats.bytes_written += package.len() as u64;
         Ok(())
     }
 
+    fn record_package(&mut self, resp: &Response, package_len: usize) {
+        let counts = &mut self.stats.packages_received;
+        match resp {
+            Response::SingleReading(_) => {
+                if !single_reading_crc_matches(&self.buf[..package_len]) {
+                    log::trace!("Frame CRC does not match its data");
+                    self.stats.crc_mismatches += 1;
+                }
+                self.stats.record_frame();
+            }
+            Response::ExposureTime(_) => counts.exposure_time += 1,
+            Response::AverageTime(_) => counts.average_time += 1,
+            Response::SerialBaudRate(_) => counts.serial_baud_rate += 1,
+            Response::VersionInfo(_) => counts.version_info += 1,
+        }
+    }
+
     fn receive_package(&mut self) -> Result<Response> {
         loop {
             log::trace!("Filling read buffer");
@@ -71,6 +136,7 @@ where
                 Ok((tail, resp)) => {
                     log::trace!("Successfuly parsed a package, freeing space in read buffer");
                     let consumed = self.top - tail.len();
+                    self.record_package(&resp, consumed);
                     self.buf.rotate_left(consumed);
                     self.top -= consumed;
                     return Ok(resp);
@@ -82,6 +148,7 @@ where
                 }
                 // TODO: Pass through parser errors when implemented correctly
                 Err(_) => {
+                    self.stats.parse_failures += 1;
                     if !self.aligned {
                         log::trace!("Failed to parse a package, trying to realign");
                         self.align_buffer();
@@ -93,37 +160,50 @@ where
         }
     }
 
-    pub fn set_avg_time(&mut self, t: u8) -> Result<()> {
-        log::debug!("Sending a SetAverageTime package with t = {}", t);
-        self.send_package(Command::SetAverageTime(t))
+    /// Sets amount of exposures that are averaged into a single frame
+    pub fn set_avg_time(&mut self, count: AveragingCount) -> Result<()> {
+        log::debug!("Sending a SetAverageTime package with count = {}", count);
+        self.send_package(Command::SetAverageTime(count.get()))?;
+        self.averaging = Some(count);
+        self.update_frame_time()
     }
 
-    pub fn get_avg_time(&mut self) -> Result<u8> {
+    /// Gets amount of exposures that are averaged into a single frame
+    pub fn get_avg_time(&mut self) -> Result<AveragingCount> {
         log::debug!("Sending a GetAverageTime package");
         self.send_package(Command::GetAverageTime)?;
         log::debug!("Waiting for a response");
         match self.receive_package()? {
             Response::AverageTime(t) => {
                 log::debug!("Recieved a AverageTime package with t = {}", t);
-                Ok(t)
+                let count = AveragingCount::new(t)?;
+                self.averaging = Some(count);
+                self.update_frame_time()?;
+                Ok(count)
             },
             r => Err(Error::UnexpectedResponse(r.into())),
         }
     }
 
-    // TODO: Figure out difference between Average, Integration and Exposure time
-    pub fn set_exp_time(&mut self, t: u16) -> Result<()> {
+    /// Sets exposure (integration) time of a single exposure
+    pub fn set_exp_time(&mut self, t: ExposureTime) -> Result<()> {
         log::debug!("Sending a SetIntegrationTime package with t = {}", t);
-        self.send_package(Command::SetIntegrationTime(t))
+        self.send_package(Command::SetIntegrationTime(t.as_millis()))?;
+        self.exposure = Some(t);
+        self.update_frame_time()
     }
 
-    pub fn get_exp_time(&mut self) -> Result<u16> {
+    /// Gets exposure (integration) time of a single exposure
+    pub fn get_exp_time(&mut self) -> Result<ExposureTime> {
         log::debug!("Sending a GetExposureTime package");
         self.send_package(Command::GetExposureTime)?;
         log::debug!("Waiting for a response");
         match self.receive_package()? {
             Response::ExposureTime(t) => {
                 log::debug!("Recieved a ExposureTime package with t = {}", t);
+                let t = ExposureTime::from_millis(t)?;
+                self.exposure = Some(t);
+                self.update_frame_time()?;
                 Ok(t)
             },
             r => Err(Error::UnexpectedResponse(r.into())),
@@ -177,7 +257,7 @@ where
         match self.receive_package()? {
             Response::SingleReading(f) => {
                 log::debug!("Recieved a SingleReading package");
-                Ok(f)
+                Ok(f.with_exposure(self.exposure))
             },
             r => Err(Error::UnexpectedResponse(r.into())),
         }
@@ -185,6 +265,15 @@ where
 
     /// Takes `count` frames from CCD and pushes them into buffer, or exits early on an error
     pub fn extend_with_frames<B: Extend<Frame>>(&mut self, buf: &mut B, count: usize) -> Result<()> {
+        self.stream_frames(Some(count), |frame| {
+            buf.extend(iter::once(frame));
+            true
+        })
+    }
+
+    /// Passes frames from CCD to a callback as they arrive, until it returns false or `count`
+    /// frames were taken. Without count it only stops on callback or an error
+    pub fn stream_frames<F: FnMut(Frame) -> bool>(&mut self, count: Option<usize>, mut f: F) -> Result<()> {
         log::debug!("Sending a ContinuousRead package");
         self.send_package(Command::ContinuousRead)?;
         let mut s = guard(self, |s| {
@@ -193,17 +282,21 @@ where
             s.send_package(Command::PauseRead)
                 .expect("Failed to stop continious CCD reading, unrecoverable state");
         });
-        log::debug!("Capturing {} frames", count);
-        for _ in 0..count {
+        log::debug!("Capturing {:?} frames", count);
+        let mut taken = 0;
+        while count.is_none_or(|count| taken < count) {
             log::debug!("Waiting for a response");
             let frame = match s.receive_package()? {
                 Response::SingleReading(f) => {
                     log::debug!("Recieved a SingleReading package");
-                    f
+                    f.with_exposure(s.exposure)
                 },
                 r => return Err(Error::UnexpectedResponse(r.into())),
             };
-            buf.extend(iter::once(frame))
+            taken += 1;
+            if !f(frame) {
+                break;
+            }
         }
         Ok(())
     }
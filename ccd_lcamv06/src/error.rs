@@ -8,6 +8,12 @@ pub enum Error {
     // TODO: Figure out a way to assemble list of baud rates at compile time
     #[error("Baud rate is not in range of accepted values: 115200, 384000, 921600")]
     InvalidBaudRate,
+    #[error("Exposure time should be a whole amount of milliseconds in range of 1 to 65535")]
+    InvalidExposureTime,
+    #[error("Averaging count should be in range of 1 to 255")]
+    InvalidAveragingCount,
+    #[error("Auto exposure needs target in range of 0 to 1, non-negative tolerance and shortest exposure time not above longest")]
+    InvalidAutoExposureOptions,
     #[error("Could not parse recieved data correctly")]
     InvalidData,
     #[error("Unexpected end of package")]
@@ -26,3 +32,17 @@ pub enum Error {
     #[error("Serial communication failed")]
     EmbeddedHalNbError,
 }
+
+impl Error {
+    /// Checks if error was caused by IO adapter running out of time waiting for data
+    pub fn is_timeout(&self) -> bool {
+        match self {
+            #[cfg(feature = "std")]
+            Error::StdIoError(err) => matches!(
+                err.kind(),
+                std::io::ErrorKind::TimedOut | std::io::ErrorKind::WouldBlock
+            ),
+            _ => false,
+        }
+    }
+}
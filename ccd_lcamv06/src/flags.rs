@@ -41,6 +41,15 @@ impl BaudRate {
     }
 }
 
+/// Baud rate from its value in bits per second, only rates supported by CCD are accepted
+impl TryFrom<u32> for BaudRate {
+    type Error = Error;
+
+    fn try_from(rate: u32) -> Result<Self, Error> {
+        num_traits::FromPrimitive::from_u32(rate).ok_or(Error::InvalidBaudRate)
+    }
+}
+
 impl Display for BaudRate {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.write_fmt(format_args!("{}", *self as u32))
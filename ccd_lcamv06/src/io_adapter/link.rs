@@ -0,0 +1,132 @@
+use super::{std_io::StdIoAdapter, tcp::{TcpAdapter, TcpOptions}, IoAdapter};
+use crate::{ccd::CCD, error::Result, flags::BaudRate};
+use core::{convert::Infallible, fmt, str::FromStr};
+use serialport::SerialPort;
+use std::{io, time::Duration};
+
+/// Read timeout of serial ports on top of frame time
+const SERIAL_TIMEOUT: Duration = Duration::from_millis(100);
+
+/// Where CCD is attached: a serial port name like "/dev/ttyUSB0" or "COM3", a network bridge as
+/// "tcp://host:port", or as "rfc2217://host:port" to also configure baud rate of the remote port
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LinkAddress {
+    Serial(String),
+    Tcp(String),
+    Rfc2217(String),
+}
+
+impl From<&str> for LinkAddress {
+    fn from(address: &str) -> Self {
+        if let Some(addr) = address.strip_prefix("tcp://") {
+            LinkAddress::Tcp(addr.to_string())
+        } else if let Some(addr) = address.strip_prefix("rfc2217://") {
+            LinkAddress::Rfc2217(addr.to_string())
+        } else {
+            LinkAddress::Serial(address.to_string())
+        }
+    }
+}
+
+impl FromStr for LinkAddress {
+    type Err = Infallible;
+
+    fn from_str(address: &str) -> core::result::Result<Self, Self::Err> {
+        Ok(LinkAddress::from(address))
+    }
+}
+
+impl fmt::Display for LinkAddress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LinkAddress::Serial(port) => write!(f, "{port}"),
+            LinkAddress::Tcp(addr) => write!(f, "tcp://{addr}"),
+            LinkAddress::Rfc2217(addr) => write!(f, "rfc2217://{addr}"),
+        }
+    }
+}
+
+/// Transport used to reach CCD, either a local serial port or a network bridge
+pub enum Link {
+    Serial(StdIoAdapter<Box<dyn SerialPort>>),
+    Tcp(TcpAdapter),
+}
+
+impl Link {
+    /// Opens link to CCD. Baud rate is set on serial ports and on remote ports of RFC2217
+    /// bridges, plain TCP bridges keep whatever rate they are configured with
+    pub fn open(address: &LinkAddress, baud: BaudRate) -> Result<Link> {
+        Ok(match address {
+            LinkAddress::Serial(port) => {
+                let port = serialport::new(port, baud as u32)
+                    .timeout(SERIAL_TIMEOUT)
+                    .open()
+                    .map_err(io::Error::from)?;
+                Link::Serial(StdIoAdapter::new(port))
+            }
+            LinkAddress::Tcp(addr) => Link::Tcp(TcpAdapter::connect(addr.as_str())?),
+            LinkAddress::Rfc2217(addr) => {
+                let options = TcpOptions {
+                    rfc2217: Some(baud),
+                    ..Default::default()
+                };
+                Link::Tcp(TcpAdapter::connect_with(addr.as_str(), options)?)
+            }
+        })
+    }
+
+    /// Opens link and CCD on it, with current CCD settings already read
+    pub fn connect(address: &LinkAddress, baud: BaudRate) -> Result<CCD<Link>> {
+        let mut ccd = Link::open(address, baud)?.open_ccd();
+        ccd.sync_settings()?;
+        Ok(ccd)
+    }
+}
+
+impl IoAdapter for Link {
+    fn write_all(&mut self, buf: &[u8]) -> Result<()> {
+        match self {
+            Link::Serial(io) => io.write_all(buf),
+            Link::Tcp(io) => io.write_all(buf),
+        }
+    }
+
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        match self {
+            Link::Serial(io) => io.read(buf),
+            Link::Tcp(io) => io.read(buf),
+        }
+    }
+
+    fn set_frame_time(&mut self, frame_time: Duration) -> Result<()> {
+        match self {
+            Link::Serial(io) => {
+                io.get_mut()
+                    .set_timeout(SERIAL_TIMEOUT + frame_time)
+                    .map_err(io::Error::from)?;
+                Ok(())
+            }
+            Link::Tcp(io) => io.set_frame_time(frame_time),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_link_address() {
+        assert_eq!(
+            LinkAddress::from("/dev/ttyUSB0"),
+            LinkAddress::Serial("/dev/ttyUSB0".into())
+        );
+        assert_eq!(
+            LinkAddress::from("tcp://192.168.1.5:4000"),
+            LinkAddress::Tcp("192.168.1.5:4000".into())
+        );
+        let address: LinkAddress = "rfc2217://bridge:2217".parse().unwrap();
+        assert_eq!(address, LinkAddress::Rfc2217("bridge:2217".into()));
+        assert_eq!(address.to_string(), "rfc2217://bridge:2217");
+    }
+}
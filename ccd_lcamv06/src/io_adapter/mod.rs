@@ -1,14 +1,25 @@
 #[cfg(feature = "std")]
 pub(crate) mod std_io;
+#[cfg(feature = "std")]
+pub(crate) mod tcp;
+#[cfg(feature = "serialport")]
+pub(crate) mod link;
 #[cfg(feature = "embedded-hal-nb")]
 pub(crate) mod embedded_hal;
 
 use crate::{error::Result, ccd::CCD};
+use core::time::Duration;
 
 pub trait IoAdapter {
     fn write_all(&mut self, buf: &[u8]) -> Result<()>;
     fn read(&mut self, buf: &mut [u8]) -> Result<usize>;
 
+    /// Time CCD takes to produce a frame with current settings. Adapters with a read timeout
+    /// should wait at least this much longer for data, does nothing by default
+    fn set_frame_time(&mut self, _frame_time: Duration) -> Result<()> {
+        Ok(())
+    }
+
     fn open_ccd(self) -> CCD<Self>
     where
         Self: Sized
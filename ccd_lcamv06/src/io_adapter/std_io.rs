@@ -22,4 +22,8 @@ impl<IO: Read + Write> StdIoAdapter<IO> {
     pub fn new(io: IO) -> Self {
         StdIoAdapter { io }
     }
+
+    pub fn get_mut(&mut self) -> &mut IO {
+        &mut self.io
+    }
 }
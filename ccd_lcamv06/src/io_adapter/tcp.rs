@@ -0,0 +1,298 @@
+use super::IoAdapter;
+use crate::{error::Result, flags::BaudRate};
+use std::{
+    io,
+    io::{Read, Write},
+    net::{SocketAddr, TcpStream, ToSocketAddrs},
+    time::Duration,
+};
+
+/// Connection settings for `TcpAdapter`
+#[derive(Debug, Clone)]
+pub struct TcpOptions {
+    /// Maximum time to wait for a single connection attempt
+    pub connect_timeout: Duration,
+    /// Maximum time to wait for data on top of frame time, `None` blocks until data arrives
+    pub read_timeout: Option<Duration>,
+    /// Amount of connection attempts made after connection was lost
+    pub reconnect_attempts: u32,
+    /// Negotiate RFC2217 (Telnet COM port control) and set baud rate of the remote serial port
+    pub rfc2217: Option<BaudRate>,
+}
+
+impl Default for TcpOptions {
+    fn default() -> Self {
+        TcpOptions {
+            connect_timeout: Duration::from_secs(3),
+            read_timeout: Some(Duration::from_secs(1)),
+            reconnect_attempts: 3,
+            rfc2217: None,
+        }
+    }
+}
+
+/// Adapter for CCD attached to a remote serial port bridge like ser2net. A failed read drops the
+/// connection and reports an error, connection is then restored before the next write
+pub struct TcpAdapter {
+    addrs: Vec<SocketAddr>,
+    options: TcpOptions,
+    stream: Option<TcpStream>,
+    telnet: Option<Telnet>,
+    frame_time: Duration,
+}
+
+impl TcpAdapter {
+    pub fn connect<A: ToSocketAddrs>(addr: A) -> Result<Self> {
+        Self::connect_with(addr, TcpOptions::default())
+    }
+
+    pub fn connect_with<A: ToSocketAddrs>(addr: A, options: TcpOptions) -> Result<Self> {
+        let addrs: Vec<_> = addr.to_socket_addrs()?.collect();
+        let mut adapter = TcpAdapter {
+            addrs,
+            options,
+            telnet: None,
+            stream: None,
+            frame_time: Duration::ZERO,
+        };
+        adapter.stream = Some(adapter.open_stream()?);
+        Ok(adapter)
+    }
+
+    fn open_stream(&mut self) -> io::Result<TcpStream> {
+        let mut last_err = io::Error::new(io::ErrorKind::InvalidInput, "No address to connect to");
+        for addr in &self.addrs {
+            log::debug!("Connecting to {}", addr);
+            match TcpStream::connect_timeout(addr, self.options.connect_timeout) {
+                Ok(mut stream) => {
+                    stream.set_read_timeout(self.read_timeout())?;
+                    stream.set_write_timeout(self.options.read_timeout)?;
+                    stream.set_nodelay(true)?;
+                    if let Some(baud) = self.options.rfc2217 {
+                        self.telnet = Some(Telnet::default());
+                        stream.write_all(&Telnet::negotiate_baud_rate(baud))?;
+                    }
+                    return Ok(stream);
+                }
+                Err(err) => last_err = err,
+            }
+        }
+        Err(last_err)
+    }
+
+    /// Frame takes its whole exposure to arrive, so it is added to configured timeout
+    fn read_timeout(&self) -> Option<Duration> {
+        self.options.read_timeout.map(|timeout| timeout + self.frame_time)
+    }
+
+    fn reconnect(&mut self) -> io::Result<&mut TcpStream> {
+        self.stream = None;
+        let mut attempt = 0;
+        loop {
+            attempt += 1;
+            log::debug!("Reconnecting, attempt {}", attempt);
+            match self.open_stream() {
+                Ok(stream) => return Ok(self.stream.insert(stream)),
+                Err(err) if attempt >= self.options.reconnect_attempts => return Err(err),
+                Err(_) => continue,
+            }
+        }
+    }
+
+    fn stream(&mut self) -> io::Result<&mut TcpStream> {
+        match self.stream {
+            Some(ref mut stream) => Ok(stream),
+            None => self.reconnect(),
+        }
+    }
+}
+
+impl IoAdapter for TcpAdapter {
+    fn write_all(&mut self, buf: &[u8]) -> Result<()> {
+        let escaped;
+        let buf = if self.telnet.is_some() {
+            escaped = Telnet::escape(buf);
+            escaped.as_slice()
+        } else {
+            buf
+        };
+        if let Err(err) = self.stream()?.write_all(buf) {
+            log::debug!("Write failed: {}, retrying on a new connection", err);
+            self.reconnect()?.write_all(buf)?;
+        }
+        Ok(())
+    }
+
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        let stream = self.stream()?;
+        let count = match stream.read(buf) {
+            Ok(0) if !buf.is_empty() => {
+                self.stream = None;
+                return Err(io::Error::from(io::ErrorKind::ConnectionAborted).into());
+            }
+            Ok(count) => count,
+            Err(err) if matches!(err.kind(), io::ErrorKind::TimedOut | io::ErrorKind::WouldBlock) => {
+                return Err(err.into());
+            }
+            Err(err) => {
+                self.stream = None;
+                return Err(err.into());
+            }
+        };
+        match self.telnet {
+            Some(ref mut telnet) => {
+                let count = telnet.filter(&mut buf[..count]);
+                let replies = telnet.take_replies();
+                if !replies.is_empty() {
+                    self.stream()?.write_all(&replies)?;
+                }
+                Ok(count)
+            }
+            None => Ok(count),
+        }
+    }
+
+    fn set_frame_time(&mut self, frame_time: Duration) -> Result<()> {
+        self.frame_time = frame_time;
+        if let Some(stream) = &self.stream {
+            stream.set_read_timeout(self.read_timeout())?;
+        }
+        Ok(())
+    }
+}
+
+// Telnet protocol constants, RFC854
+const IAC: u8 = 255;
+const DONT: u8 = 254;
+const DO: u8 = 253;
+const WONT: u8 = 252;
+const WILL: u8 = 251;
+const SB: u8 = 250;
+const SE: u8 = 240;
+const BINARY: u8 = 0;
+const SUPPRESS_GO_AHEAD: u8 = 3;
+// COM port control option, RFC2217
+const COM_PORT_OPTION: u8 = 44;
+const SET_BAUDRATE: u8 = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+enum TelnetState {
+    #[default]
+    Data,
+    Iac,
+    Negotiation(u8),
+    Subnegotiation,
+    SubnegotiationIac,
+}
+
+/// Strips Telnet commands from received data and escapes sent data
+#[derive(Debug, Default)]
+struct Telnet {
+    state: TelnetState,
+    replies: Vec<u8>,
+}
+
+impl Telnet {
+    fn negotiate_baud_rate(baud: BaudRate) -> Vec<u8> {
+        let mut package = vec![
+            IAC, WILL, BINARY, IAC, DO, BINARY, IAC, WILL, COM_PORT_OPTION,
+            IAC, SB, COM_PORT_OPTION, SET_BAUDRATE,
+        ];
+        package.extend(Self::escape(&(baud as u32).to_be_bytes()));
+        package.extend([IAC, SE]);
+        package
+    }
+
+    fn escape(buf: &[u8]) -> Vec<u8> {
+        let mut escaped = Vec::with_capacity(buf.len());
+        for &b in buf {
+            escaped.push(b);
+            if b == IAC {
+                escaped.push(IAC);
+            }
+        }
+        escaped
+    }
+
+    fn take_replies(&mut self) -> Vec<u8> {
+        core::mem::take(&mut self.replies)
+    }
+
+    /// Removes Telnet commands from buffer in place, returns amount of remaining data bytes
+    fn filter(&mut self, buf: &mut [u8]) -> usize {
+        use TelnetState::*;
+        let mut top = 0;
+        for i in 0..buf.len() {
+            let b = buf[i];
+            self.state = match (self.state, b) {
+                (Data, IAC) => Iac,
+                (Data, _) => {
+                    buf[top] = b;
+                    top += 1;
+                    Data
+                }
+                (Iac, IAC) => {
+                    buf[top] = IAC;
+                    top += 1;
+                    Data
+                }
+                (Iac, DO | DONT | WILL | WONT) => Negotiation(b),
+                (Iac, SB) => Subnegotiation,
+                (Iac, _) => Data,
+                (Negotiation(cmd), option) => {
+                    self.reply(cmd, option);
+                    Data
+                }
+                (Subnegotiation, IAC) => SubnegotiationIac,
+                (Subnegotiation, _) => Subnegotiation,
+                (SubnegotiationIac, SE) => Data,
+                (SubnegotiationIac, _) => Subnegotiation,
+            };
+        }
+        top
+    }
+
+    /// Refuses all options except the ones required for RFC2217
+    fn reply(&mut self, cmd: u8, option: u8) {
+        let supported = matches!(option, BINARY | SUPPRESS_GO_AHEAD | COM_PORT_OPTION);
+        match cmd {
+            DO if !supported => self.replies.extend([IAC, WONT, option]),
+            WILL if !supported => self.replies.extend([IAC, DONT, option]),
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn telnet_escape() {
+        assert_eq!(
+            Telnet::escape(&[0x81, 0x01, 0x00, 0x00, 0xFF]),
+            vec![0x81, 0x01, 0x00, 0x00, 0xFF, 0xFF]
+        );
+    }
+
+    #[test]
+    fn telnet_filter() {
+        let mut telnet = Telnet::default();
+        // Escaped IAC, negotiation and subnegotiation between data bytes
+        let mut buf = [
+            0x81, IAC, IAC, IAC, DO, 24, 0x02, IAC, SB, COM_PORT_OPTION, 101, 0, IAC, SE, 0x03,
+        ];
+        let count = telnet.filter(&mut buf);
+        assert_eq!(&buf[..count], &[0x81, IAC, 0x02, 0x03]);
+        assert_eq!(telnet.take_replies(), vec![IAC, WONT, 24]);
+
+        // Commands split between reads
+        let mut buf = [0x01, IAC];
+        let count = telnet.filter(&mut buf);
+        assert_eq!(&buf[..count], &[0x01]);
+        let mut buf = [WILL, COM_PORT_OPTION, 0x02];
+        let count = telnet.filter(&mut buf);
+        assert_eq!(&buf[..count], &[0x02]);
+        assert!(telnet.take_replies().is_empty());
+    }
+}
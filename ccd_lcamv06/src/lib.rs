@@ -2,6 +2,7 @@
 
 pub mod error;
 pub(crate) mod flags;
+pub(crate) mod timing;
 pub(crate) mod command;
 pub(crate) mod response;
 
@@ -9,11 +10,25 @@ pub mod io_adapter;
 pub use io_adapter::IoAdapter;
 #[cfg(feature = "std")]
 pub use io_adapter::std_io::StdIoAdapter;
+#[cfg(feature = "std")]
+pub use io_adapter::tcp::{TcpAdapter, TcpOptions};
+#[cfg(feature = "serialport")]
+pub use io_adapter::link::{Link, LinkAddress};
 #[cfg(feature = "embedded_hal")]
 pub use io_adapter::embedded_hal::EmbeddedHalAdapter;
 
 pub mod ccd;
 pub use ccd::CCD;
 
+pub mod auto_exposure;
+pub use auto_exposure::{AutoExposure, AutoExposureOptions};
+
+pub mod stats;
+pub use stats::Stats;
+
 pub use flags::{BaudRate, TriggerMode};
-pub use response::{Frame, FRAME_PIXEL_COUNT, VersionDetails};
+pub use timing::{AveragingCount, ExposureTime};
+pub use response::{
+    is_clipped, Frame, VersionDetails, ACTIVE_PIXELS, ADC_FULL_SCALE, CLIPPING_LEVEL, FRAME_PIXEL_COUNT,
+    SATURATION_LEVEL, SHIELDED_PIXELS,
+};
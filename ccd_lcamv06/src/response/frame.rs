@@ -0,0 +1,94 @@
+use super::FRAME_PIXEL_COUNT;
+use crate::timing::ExposureTime;
+use core::ops::{Deref, DerefMut, Range};
+
+/// Pixels of TCD1304 covered from light, their output is the black level of a frame
+pub const SHIELDED_PIXELS: Range<usize> = 16..29;
+/// Light sensitive pixels of TCD1304, the rest are shielded or dummy pixels
+pub const ACTIVE_PIXELS: Range<usize> = 32..3680;
+/// Largest raw value, ADC output is sent as 16 bit words
+pub const ADC_FULL_SCALE: u16 = u16::MAX;
+/// Raw values at or below this are saturated. Output is inverted, so bright light drives
+/// pixels towards zero
+pub const SATURATION_LEVEL: u16 = 16;
+/// Raw values at or above this are clipped at the top of ADC range
+pub const CLIPPING_LEVEL: u16 = ADC_FULL_SCALE - 16;
+
+/// CCD captured data. Dereferences into raw pixel values
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct Frame {
+    pixels: [u16; FRAME_PIXEL_COUNT],
+    exposure: Option<ExposureTime>,
+}
+
+impl Frame {
+    pub fn new(pixels: [u16; FRAME_PIXEL_COUNT]) -> Frame {
+        Frame {
+            pixels,
+            exposure: None,
+        }
+    }
+
+    pub fn with_exposure(mut self, exposure: Option<ExposureTime>) -> Frame {
+        self.exposure = exposure;
+        self
+    }
+
+    /// Exposure time frame was taken with, as last set or read through `CCD`. Unknown until
+    /// one of those happens, since CCD does not report it with every frame
+    pub fn exposure(&self) -> Option<ExposureTime> {
+        self.exposure
+    }
+
+    /// Whether any active pixel is at or beyond `SATURATION_LEVEL` or `CLIPPING_LEVEL`. Values
+    /// of those pixels don't reflect amount of light. Shielded and dummy pixels are left out,
+    /// since they never get light
+    pub fn is_clipped(&self) -> bool {
+        self.clipped_pixels().next().is_some()
+    }
+
+    /// Indices of active pixels at or beyond `SATURATION_LEVEL` or `CLIPPING_LEVEL`
+    pub fn clipped_pixels(&self) -> impl Iterator<Item = usize> + '_ {
+        ACTIVE_PIXELS.filter(|&idx| is_clipped(self.pixels[idx]))
+    }
+
+    pub fn pixels(&self) -> &[u16; FRAME_PIXEL_COUNT] {
+        &self.pixels
+    }
+
+    /// Mean raw value of light shielded pixels, which corresponds to no light. Output is
+    /// inverted, so light lowers values below this level
+    pub fn black_level(&self) -> f64 {
+        let shielded = &self.pixels[SHIELDED_PIXELS];
+        shielded.iter().map(|&p| f64::from(p)).sum::<f64>() / shielded.len() as f64
+    }
+
+    pub fn into_pixels(self) -> [u16; FRAME_PIXEL_COUNT] {
+        self.pixels
+    }
+}
+
+/// Whether raw value is too close to either end of ADC range to be trusted
+pub fn is_clipped(raw: u16) -> bool {
+    raw <= SATURATION_LEVEL || raw >= CLIPPING_LEVEL
+}
+
+impl From<[u16; FRAME_PIXEL_COUNT]> for Frame {
+    fn from(pixels: [u16; FRAME_PIXEL_COUNT]) -> Frame {
+        Frame::new(pixels)
+    }
+}
+
+impl Deref for Frame {
+    type Target = [u16; FRAME_PIXEL_COUNT];
+
+    fn deref(&self) -> &Self::Target {
+        &self.pixels
+    }
+}
+
+impl DerefMut for Frame {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.pixels
+    }
+}
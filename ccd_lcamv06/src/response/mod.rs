@@ -1,9 +1,13 @@
+mod frame;
 pub mod parser;
 mod version_details;
 mod version_parser;
 
 use crate::flags::BaudRate;
 use strum_macros::IntoStaticStr;
+pub use frame::{
+    is_clipped, Frame, ACTIVE_PIXELS, ADC_FULL_SCALE, CLIPPING_LEVEL, SATURATION_LEVEL, SHIELDED_PIXELS,
+};
 pub use version_details::VersionDetails;
 
 // While there is a large difference in response sizes, all of the small ones usually come one at a
@@ -26,6 +30,3 @@ const FRAME_PIXEL_PREFIX: usize = 0;
 const FRAME_PIXEL_POSTFIX: usize = 0;
 /// Amount of pixels in a single package
 const FRAME_TOTAL_COUNT: usize = FRAME_PIXEL_PREFIX + FRAME_PIXEL_COUNT + FRAME_PIXEL_POSTFIX;
-
-/// CCD captured data
-pub type Frame = [u16; FRAME_PIXEL_COUNT];
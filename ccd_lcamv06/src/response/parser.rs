@@ -10,7 +10,7 @@ use nom::{
 
 use crate::flags::BaudRate;
 use super::version_parser::*;
-use super::{Response, FRAME_TOTAL_COUNT};
+use super::{Frame, Response, FRAME_TOTAL_COUNT};
 
 /// byte version of nom::character::streaming::satisfy
 fn u8_satisfy<F, I, E: nom::error::ParseError<I>>(cond: F) -> impl Fn(I) -> IResult<I, u8, E>
@@ -65,25 +65,32 @@ fn single_frame_parser(input: &[u8]) -> IResult<&[u8], Response> {
         return Err(nom::Err::Incomplete(nom::Needed::Size(needed)));
     }
 
-    // Calculate CRC on individual bytes, each pixel is 2 bytes long
-    let _crc = input[..FRAME_TOTAL_COUNT * 2]
-        .iter()
-        .fold(0u16, |accum, val| accum.wrapping_add(*val as u16));
-
     // Parse data
     let mut data = [0u16; FRAME_TOTAL_COUNT];
     let (input, ()) = fill(be_u16, &mut data)(input)?;
-    let (input, _expected_crc) = be_u16(input)?;
-    // TODO: Figure out why some packages include wrong CRC
-    /*
-    if crc != expected_crc {
-        return Err(nom::Err::Error(nom::error::Error::new(
-            input,
-            nom::error::ErrorKind::Digit,
-        )));
+    let (input, _crc) = be_u16(input)?;
+    // TODO: Figure out why some packages include wrong CRC. Until then mismatches are only
+    // counted by CCD through `single_reading_crc_matches` instead of rejecting the package
+    Ok((input, Response::SingleReading(Frame::new(data))))
+}
+
+/// Length of SingleReading package head: prefix, command, scan size and a zero byte
+const SINGLE_READING_HEAD_LEN: usize = 5;
+
+/// Calculates CRC on individual bytes, each pixel is 2 bytes long
+fn frame_crc(data: &[u8]) -> u16 {
+    data.iter()
+        .fold(0u16, |accum, val| accum.wrapping_add(*val as u16))
+}
+
+/// Takes a complete SingleReading package and checks if included CRC matches the data
+pub(crate) fn single_reading_crc_matches(package: &[u8]) -> bool {
+    const DATA_END: usize = SINGLE_READING_HEAD_LEN + FRAME_TOTAL_COUNT * 2;
+    if package.len() < DATA_END + 2 {
+        return false;
     }
-    */
-    Ok((input, Response::SingleReading(data)))
+    let expected_crc = u16::from_be_bytes([package[DATA_END], package[DATA_END + 1]]);
+    frame_crc(&package[SINGLE_READING_HEAD_LEN..DATA_END]) == expected_crc
 }
 
 fn exposure_time_parser(input: &[u8]) -> IResult<&[u8], Response> {
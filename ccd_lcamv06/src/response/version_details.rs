@@ -32,6 +32,23 @@ impl VersionDetails {
                 .map_err(|_| Error::VersionDetailTooLong("Serial number"))?,
         })
     }
+
+    pub fn hardware_version(&self) -> &str {
+        &self.hardware_version
+    }
+
+    pub fn sensor_type(&self) -> &str {
+        &self.sensor_type
+    }
+
+    pub fn firmware_version(&self) -> &str {
+        &self.firmware_version
+    }
+
+    /// Serial number, looks like a timestamp of manufacturing
+    pub fn serial_number(&self) -> &str {
+        &self.serial_number
+    }
 }
 
 impl Display for VersionDetails {
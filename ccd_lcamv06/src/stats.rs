@@ -0,0 +1,127 @@
+use core::{
+    fmt,
+    fmt::{Debug, Display},
+    time::Duration,
+};
+#[cfg(feature = "std")]
+use std::time::Instant;
+
+/// Amount of received packages, grouped by type of response
+#[derive(PartialEq, Eq, Debug, Clone, Copy, Default)]
+pub struct PackageCounts {
+    pub single_reading: u32,
+    pub exposure_time: u32,
+    pub average_time: u32,
+    pub serial_baud_rate: u32,
+    pub version_info: u32,
+}
+
+impl PackageCounts {
+    pub fn total(&self) -> u32 {
+        self.single_reading
+            + self.exposure_time
+            + self.average_time
+            + self.serial_baud_rate
+            + self.version_info
+    }
+}
+
+/// Snapshot of link health counters collected by CCD since it was opened or since last reset
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Stats {
+    /// Amount of bytes received from IO adapter
+    pub bytes_read: u64,
+    /// Amount of bytes sent through IO adapter
+    pub bytes_written: u64,
+    /// Amount of command packages sent to CCD
+    pub packages_sent: u32,
+    /// Amount of successfully parsed packages
+    pub packages_received: PackageCounts,
+    /// Amount of times read buffer had to be realigned to a package head
+    pub realignments: u32,
+    /// Amount of bytes dropped while realigning read buffer
+    pub bytes_discarded: u64,
+    /// Amount of times received data could not be parsed as any known package
+    pub parse_failures: u32,
+    /// Amount of frames with checksum not matching the data
+    pub crc_mismatches: u32,
+    /// Amount of reads that timed out
+    pub timeouts: u32,
+    #[cfg(feature = "std")]
+    first_frame_at: Option<Instant>,
+    #[cfg(feature = "std")]
+    last_frame_at: Option<Instant>,
+}
+
+impl Stats {
+    pub(crate) fn record_frame(&mut self) {
+        self.packages_received.single_reading += 1;
+        #[cfg(feature = "std")]
+        {
+            let now = Instant::now();
+            self.first_frame_at.get_or_insert(now);
+            self.last_frame_at = Some(now);
+        }
+    }
+
+    /// Average amount of frames received per second over a given period of time. Useful when
+    /// there is no clock available to the driver, otherwise see `frame_rate`
+    pub fn frames_per_second(&self, elapsed: Duration) -> Option<f32> {
+        let secs = elapsed.as_secs_f32();
+        if secs > 0.0 {
+            Some(self.packages_received.single_reading as f32 / secs)
+        } else {
+            None
+        }
+    }
+
+    /// Average amount of frames received per second between first and latest frame
+    #[cfg(feature = "std")]
+    pub fn frame_rate(&self) -> Option<f32> {
+        let (first, last) = (self.first_frame_at?, self.last_frame_at?);
+        // First frame only marks the start of measured period
+        let intervals = self.packages_received.single_reading.checked_sub(1)?;
+        let secs = last.duration_since(first).as_secs_f32();
+        if secs > 0.0 {
+            Some(intervals as f32 / secs)
+        } else {
+            None
+        }
+    }
+}
+
+impl Display for Stats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_fmt(format_args!(
+            concat!(
+                "Bytes read: {}\n",
+                "Bytes written: {}\n",
+                "Packages sent: {}\n",
+                "Packages received: {} (frames: {}, exposure time: {}, average time: {}, baud rate: {}, version: {})\n",
+                "Realignments: {} ({} bytes discarded)\n",
+                "Parse failures: {}\n",
+                "CRC mismatches: {}\n",
+                "Timeouts: {}",
+            ),
+            self.bytes_read,
+            self.bytes_written,
+            self.packages_sent,
+            self.packages_received.total(),
+            self.packages_received.single_reading,
+            self.packages_received.exposure_time,
+            self.packages_received.average_time,
+            self.packages_received.serial_baud_rate,
+            self.packages_received.version_info,
+            self.realignments,
+            self.bytes_discarded,
+            self.parse_failures,
+            self.crc_mismatches,
+            self.timeouts,
+        ))?;
+        #[cfg(feature = "std")]
+        if let Some(rate) = self.frame_rate() {
+            f.write_fmt(format_args!("\nFrame rate: {rate:.2} fps"))?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,148 @@
+use crate::error::{Error, Result};
+use core::{
+    fmt,
+    fmt::{Debug, Display},
+    time::Duration,
+};
+
+/// Time during which CCD accumulates charge for a single frame, also called integration time.
+/// Firmware counts it in whole milliseconds as a 16 bit value, zero is rejected
+#[derive(PartialEq, Eq, PartialOrd, Ord, Debug, Clone, Copy, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(try_from = "u16", into = "u16")
+)]
+pub struct ExposureTime(pub(crate) u16);
+
+impl ExposureTime {
+    pub const MIN: ExposureTime = ExposureTime(1);
+    pub const MAX: ExposureTime = ExposureTime(u16::MAX);
+
+    pub fn from_millis(ms: u16) -> Result<Self> {
+        if ms < Self::MIN.0 {
+            return Err(Error::InvalidExposureTime);
+        }
+        Ok(ExposureTime(ms))
+    }
+
+    pub fn as_millis(self) -> u16 {
+        self.0
+    }
+
+    pub fn as_duration(self) -> Duration {
+        Duration::from_millis(self.0 as u64)
+    }
+}
+
+impl TryFrom<Duration> for ExposureTime {
+    type Error = Error;
+
+    /// Fails if duration is out of range or is not a whole amount of milliseconds
+    fn try_from(d: Duration) -> Result<Self> {
+        if !d.subsec_nanos().is_multiple_of(1_000_000) {
+            return Err(Error::InvalidExposureTime);
+        }
+        let ms = u16::try_from(d.as_millis()).map_err(|_| Error::InvalidExposureTime)?;
+        ExposureTime::from_millis(ms)
+    }
+}
+
+impl TryFrom<u16> for ExposureTime {
+    type Error = Error;
+
+    fn try_from(ms: u16) -> Result<Self> {
+        ExposureTime::from_millis(ms)
+    }
+}
+
+impl From<ExposureTime> for u16 {
+    fn from(t: ExposureTime) -> u16 {
+        t.as_millis()
+    }
+}
+
+impl From<ExposureTime> for Duration {
+    fn from(t: ExposureTime) -> Duration {
+        t.as_duration()
+    }
+}
+
+impl Display for ExposureTime {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_fmt(format_args!("{} ms", self.0))
+    }
+}
+
+/// Amount of consecutive exposures that firmware averages into a single frame
+#[derive(PartialEq, Eq, PartialOrd, Ord, Debug, Clone, Copy)]
+pub struct AveragingCount(u8);
+
+impl AveragingCount {
+    pub const MIN: AveragingCount = AveragingCount(1);
+    pub const MAX: AveragingCount = AveragingCount(u8::MAX);
+
+    pub fn new(count: u8) -> Result<Self> {
+        if count < Self::MIN.0 {
+            return Err(Error::InvalidAveragingCount);
+        }
+        Ok(AveragingCount(count))
+    }
+
+    pub fn get(self) -> u8 {
+        self.0
+    }
+
+    /// Time it takes to capture a single averaged frame with a given exposure
+    pub fn duration(self, exposure: ExposureTime) -> Duration {
+        exposure.as_duration() * self.0 as u32
+    }
+
+    /// Largest averaging count which fits into `total` time with a given exposure
+    pub fn from_duration(total: Duration, exposure: ExposureTime) -> Result<Self> {
+        let count = total.as_millis() / exposure.as_millis() as u128;
+        let count = u8::try_from(count).map_err(|_| Error::InvalidAveragingCount)?;
+        AveragingCount::new(count)
+    }
+}
+
+impl Display for AveragingCount {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_fmt(format_args!("{}", self.0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use claims::*;
+
+    #[test]
+    fn exposure_time_from_duration() {
+        assert_ok_eq!(
+            ExposureTime::try_from(Duration::from_millis(20)),
+            ExposureTime(20)
+        );
+        assert_ok_eq!(
+            ExposureTime::try_from(Duration::from_secs(65)),
+            ExposureTime(65000)
+        );
+        // Too short, too long and not whole milliseconds
+        assert_err!(ExposureTime::try_from(Duration::ZERO));
+        assert_err!(ExposureTime::try_from(Duration::from_secs(66)));
+        assert_err!(ExposureTime::try_from(Duration::from_micros(1500)));
+    }
+
+    #[test]
+    fn averaging_count_duration() {
+        let exposure = ExposureTime::from_millis(20).unwrap();
+        let count = AveragingCount::new(5).unwrap();
+        assert_eq!(count.duration(exposure), Duration::from_millis(100));
+        assert_ok_eq!(
+            AveragingCount::from_duration(Duration::from_millis(110), exposure),
+            count
+        );
+        assert_err!(AveragingCount::from_duration(Duration::from_millis(10), exposure));
+        assert_err!(AveragingCount::new(0));
+    }
+}
@@ -1,7 +1,7 @@
 use utilities::{
     SINGLE_PACKAGE, MockIO
 };
-use ccd_lcamv06::{IoAdapter, StdIoAdapter};
+use ccd_lcamv06::{ExposureTime, Frame, IoAdapter, StdIoAdapter};
 use std::io::Write;
 
 #[test]
@@ -34,3 +34,107 @@ fn decode_single_package() {
         .sqrt();
     assert!(deviation < 100 as f32);
 }
+
+#[test]
+fn count_link_stats() {
+    let mut mock_io = MockIO::new();
+    mock_io.expect_write().returning(|msg| Ok(msg.len()));
+    mock_io.expect_read().returning(move |mut buf| {
+        buf.write(&SINGLE_PACKAGE)
+    });
+    let mut ccd = StdIoAdapter::new(mock_io).open_ccd();
+    ccd.get_frame().unwrap();
+
+    let stats = ccd.stats();
+    assert_eq!(stats.packages_sent, 1);
+    assert_eq!(stats.bytes_written, 5);
+    assert_eq!(stats.packages_received.single_reading, 1);
+    assert_eq!(stats.packages_received.total(), 1);
+    assert_eq!(stats.bytes_read, SINGLE_PACKAGE.len() as u64);
+    assert_eq!(stats.realignments, 0);
+    assert_eq!(stats.parse_failures, 0);
+}
+
+#[test]
+fn count_realignments_and_crc_mismatches() {
+    // Garbage before package head and a corrupted pixel
+    let mut package = vec![0xDE, 0xAD, 0xBE, 0xEF];
+    package.extend_from_slice(&SINGLE_PACKAGE);
+    package[4 + 10] ^= 0xFF;
+
+    let mut mock_io = MockIO::new();
+    mock_io.expect_write().returning(|msg| Ok(msg.len()));
+    mock_io.expect_read().returning(move |mut buf| {
+        buf.write(&package)
+    });
+    let mut ccd = StdIoAdapter::new(mock_io).open_ccd();
+    ccd.get_frame().unwrap();
+
+    let stats = ccd.stats();
+    assert_eq!(stats.parse_failures, 1);
+    assert_eq!(stats.realignments, 1);
+    assert_eq!(stats.bytes_discarded, 4);
+    assert_eq!(stats.crc_mismatches, 1);
+    assert_eq!(stats.packages_received.single_reading, 1);
+
+    ccd.reset_stats();
+    assert_eq!(ccd.stats().bytes_read, 0);
+}
+
+#[test]
+fn attach_exposure_to_frames() {
+    let mut mock_io = MockIO::new();
+    mock_io.expect_write().returning(|msg| Ok(msg.len()));
+    mock_io.expect_read().returning(move |mut buf| {
+        buf.write(&SINGLE_PACKAGE)
+    });
+    let mut ccd = StdIoAdapter::new(mock_io).open_ccd();
+    assert_eq!(ccd.get_frame().unwrap().exposure(), None);
+
+    let exposure = ExposureTime::from_millis(20).unwrap();
+    ccd.set_exp_time(exposure).unwrap();
+    assert_eq!(ccd.get_frame().unwrap().exposure(), Some(exposure));
+}
+
+#[test]
+fn flag_clipped_frames() {
+    let mut mock_io = MockIO::new();
+    mock_io.expect_write().returning(|msg| Ok(msg.len()));
+    mock_io.expect_read().returning(move |mut buf| {
+        buf.write(&SINGLE_PACKAGE)
+    });
+    let mut ccd = StdIoAdapter::new(mock_io).open_ccd();
+    let mut pixels = ccd.get_frame().unwrap().into_pixels();
+    assert!(!Frame::new(pixels).is_clipped());
+
+    // Dummy pixels never get light, so they don't count
+    pixels[3] = 0;
+    assert!(!Frame::new(pixels).is_clipped());
+
+    pixels[100] = 0;
+    assert!(Frame::new(pixels).is_clipped());
+
+    // Pixels changed in place are checked as well
+    let mut frame = Frame::new(pixels);
+    frame[100] = 1000;
+    assert!(!frame.is_clipped());
+}
+
+#[test]
+fn stop_streaming_from_callback() {
+    let mut mock_io = MockIO::new();
+    mock_io.expect_write().returning(|msg| Ok(msg.len()));
+    mock_io.expect_read().returning(move |mut buf| {
+        buf.write(&SINGLE_PACKAGE)
+    });
+    let mut ccd = StdIoAdapter::new(mock_io).open_ccd();
+    let mut taken = 0;
+    ccd.stream_frames(None, |_| {
+        taken += 1;
+        taken < 4
+    })
+    .unwrap();
+    assert_eq!(taken, 4);
+    // Continuous read is paused once callback stops it
+    assert_eq!(ccd.stats().packages_sent, 2);
+}
@@ -0,0 +1,109 @@
+use utilities::Emulator;
+use ccd_lcamv06::{
+    error::Result, ExposureTime, IoAdapter, TcpAdapter, TcpOptions, FRAME_PIXEL_COUNT,
+};
+use std::{cell::Cell, rc::Rc, thread, time::Duration};
+
+/// Keeps track of frame time that CCD reports to adapter
+struct FrameTimeProbe {
+    io: TcpAdapter,
+    frame_time: Rc<Cell<Duration>>,
+}
+
+impl IoAdapter for FrameTimeProbe {
+    fn write_all(&mut self, buf: &[u8]) -> Result<()> {
+        self.io.write_all(buf)
+    }
+
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        self.io.read(buf)
+    }
+
+    fn set_frame_time(&mut self, frame_time: Duration) -> Result<()> {
+        self.frame_time.set(frame_time);
+        self.io.set_frame_time(frame_time)
+    }
+}
+
+#[test]
+fn read_over_tcp() {
+    let emulator = Emulator::start();
+    let mut ccd = TcpAdapter::connect(emulator.addr()).unwrap().open_ccd();
+
+    let frame = ccd.get_frame().unwrap();
+    assert_eq!(frame.len(), FRAME_PIXEL_COUNT);
+
+    let exposure = ExposureTime::from_millis(20).unwrap();
+    ccd.set_exp_time(exposure).unwrap();
+    assert_eq!(ccd.get_exp_time().unwrap(), exposure);
+
+    let mut frames = Vec::new();
+    ccd.extend_with_frames(&mut frames, 3).unwrap();
+    assert_eq!(frames.len(), 3);
+}
+
+#[test]
+fn wait_for_exposures_longer_than_read_timeout() {
+    let emulator = Emulator::start();
+    let options = TcpOptions {
+        read_timeout: Some(Duration::from_millis(100)),
+        ..Default::default()
+    };
+    let mut ccd = TcpAdapter::connect_with(emulator.addr(), options).unwrap().open_ccd();
+    ccd.set_exp_time(ExposureTime::from_millis(300).unwrap()).unwrap();
+    assert_eq!(ccd.get_frame().unwrap().len(), FRAME_PIXEL_COUNT);
+}
+
+#[test]
+fn report_frame_time_of_fresh_ccd() {
+    let emulator = Emulator::start();
+    let frame_time = Rc::new(Cell::new(Duration::ZERO));
+    let probe = FrameTimeProbe {
+        io: TcpAdapter::connect(emulator.addr()).unwrap(),
+        frame_time: frame_time.clone(),
+    };
+    let mut ccd = probe.open_ccd();
+    ccd.sync_settings().unwrap();
+    assert!(frame_time.get() > Duration::ZERO);
+}
+
+#[test]
+fn wait_for_exposure_set_in_earlier_session() {
+    let emulator = Emulator::start();
+    let mut ccd = TcpAdapter::connect(emulator.addr()).unwrap().open_ccd();
+    ccd.set_exp_time(ExposureTime::from_millis(300).unwrap()).unwrap();
+    drop(ccd);
+
+    let options = TcpOptions {
+        read_timeout: Some(Duration::from_millis(100)),
+        ..Default::default()
+    };
+    let mut ccd = TcpAdapter::connect_with(emulator.addr(), options).unwrap().open_ccd();
+    ccd.sync_settings().unwrap();
+    let frame = ccd.get_frame().unwrap();
+    assert_eq!(frame.exposure(), Some(ExposureTime::from_millis(300).unwrap()));
+}
+
+#[test]
+fn reconnect_after_connection_loss() {
+    let emulator = Emulator::start();
+    let mut ccd = TcpAdapter::connect(emulator.addr()).unwrap().open_ccd();
+    ccd.get_exp_time().unwrap();
+
+    emulator.disconnect();
+    thread::sleep(Duration::from_millis(100));
+    // Request sent over a dropped connection might get lost, but the next one has to succeed
+    let _ = ccd.get_exp_time();
+    ccd.get_exp_time().unwrap();
+}
+
+#[cfg(feature = "serialport")]
+#[test]
+fn open_link_from_address() {
+    use ccd_lcamv06::{BaudRate, Link, LinkAddress};
+
+    let emulator = Emulator::start();
+    let address = LinkAddress::from(format!("tcp://{}", emulator.addr()).as_str());
+    let mut ccd = Link::open(&address, BaudRate::default()).unwrap().open_ccd();
+    assert_eq!(ccd.get_frame().unwrap().len(), FRAME_PIXEL_COUNT);
+}
@@ -0,0 +1,111 @@
+use crate::SINGLE_PACKAGE;
+use std::{
+    io::{ErrorKind, Read, Write},
+    net::{SocketAddr, TcpListener, TcpStream},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread,
+    time::Duration,
+};
+
+/// Minimal CCD emulator listening on a local TCP port, replays recorded frame on every reading
+/// and keeps track of settings. Connections are served one at a time
+pub struct Emulator {
+    addr: SocketAddr,
+    disconnect: Arc<AtomicBool>,
+}
+
+struct Settings {
+    exposure_time: u16,
+    average_time: u8,
+    baud_rate_code: u8,
+}
+
+impl Emulator {
+    pub fn start() -> Emulator {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("Failed to bind emulator port");
+        let addr = listener.local_addr().unwrap();
+        let disconnect = Arc::new(AtomicBool::new(false));
+        let disconnect_flag = disconnect.clone();
+        thread::spawn(move || {
+            let mut settings = Settings {
+                exposure_time: 10,
+                average_time: 1,
+                baud_rate_code: 0x01,
+            };
+            for stream in listener.incoming() {
+                let Ok(stream) = stream else { continue };
+                // Client is gone or emulator was asked to drop it, wait for the next one
+                let _ = serve(stream, &mut settings, &disconnect_flag);
+                disconnect_flag.store(false, Ordering::SeqCst);
+            }
+        });
+        Emulator { addr, disconnect }
+    }
+
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// Drops current client connection, as if network link went down
+    pub fn disconnect(&self) {
+        self.disconnect.store(true, Ordering::SeqCst);
+    }
+}
+
+fn serve(
+    mut stream: TcpStream,
+    settings: &mut Settings,
+    disconnect: &AtomicBool,
+) -> std::io::Result<()> {
+    stream.set_read_timeout(Some(Duration::from_millis(10)))?;
+    let mut streaming = false;
+    let mut cmd = Vec::with_capacity(5);
+    let mut buf = [0u8; 64];
+    loop {
+        if disconnect.load(Ordering::SeqCst) {
+            return Ok(());
+        }
+        match stream.read(&mut buf) {
+            Ok(0) => return Ok(()),
+            Ok(count) => cmd.extend_from_slice(&buf[..count]),
+            Err(err) if matches!(err.kind(), ErrorKind::TimedOut | ErrorKind::WouldBlock) => {
+                if streaming {
+                    stream.write_all(&SINGLE_PACKAGE)?;
+                }
+                continue;
+            }
+            Err(err) => return Err(err),
+        }
+        while cmd.len() >= 5 {
+            let package: Vec<_> = cmd.drain(..5).collect();
+            if package[0] != 0x81 || package[4] != 0xFF {
+                // Drop a byte at a time until package is aligned again
+                cmd.splice(..0, package[1..].iter().copied());
+                continue;
+            }
+            match package[1] {
+                0x01 => {
+                    // Frame is only sent after exposure
+                    thread::sleep(Duration::from_millis(settings.exposure_time.into()));
+                    stream.write_all(&SINGLE_PACKAGE)?
+                }
+                0x02 => streaming = true,
+                0x03 => settings.exposure_time = u16::from_be_bytes([package[2], package[3]]),
+                0x06 => streaming = false,
+                0x09 => stream.write_all(b"HdInfo:LCAM_V8.4.2,S11639,V4.2,202111161548")?,
+                0x0a => {
+                    let [hi, lo] = settings.exposure_time.to_be_bytes();
+                    stream.write_all(&[0x81, 0x02, hi, lo, 0xFF])?
+                }
+                0x0c => settings.average_time = package[2],
+                0x0e => stream.write_all(&[0x81, 0x0E, settings.average_time, 0x00, 0xFF])?,
+                0x13 => settings.baud_rate_code = package[2],
+                0x16 => stream.write_all(&[0x81, 0x16, settings.baud_rate_code, 0x00, 0xFF])?,
+                _ => {}
+            }
+        }
+    }
+}
@@ -11,6 +11,9 @@ use nom::{
 };
 use std::io::{Read, Write};
 
+pub mod emulator;
+pub use emulator::Emulator;
+
 /// Decodes a pair of chars formatted as hex into a byte. For example "FF" -> 255
 fn hex_byte(input: &str) -> IResult<&str, u8> {
     map_res(
@@ -0,0 +1,15 @@
+fn main() {
+    let crate_dir = std::env::var("CARGO_MANIFEST_DIR").unwrap();
+    println!("cargo:rerun-if-changed=src");
+    println!("cargo:rerun-if-changed=cbindgen.toml");
+
+    cbindgen::Builder::new()
+        .with_crate(&crate_dir)
+        .with_config(
+            cbindgen::Config::from_file(format!("{crate_dir}/cbindgen.toml"))
+                .expect("cbindgen.toml should be valid"),
+        )
+        .generate()
+        .expect("Failed to generate C header")
+        .write_to_file(format!("{crate_dir}/include/ccd_lcamv06.h"));
+}
@@ -0,0 +1,116 @@
+#ifndef CCD_LCAMV06_H
+#define CCD_LCAMV06_H
+
+/* Generated by cbindgen from ccd_lcamv06_ffi, do not edit manually */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+// Amount of pixels in a single frame, size of frame buffers is measured in pixels
+#define CCD_FRAME_PIXEL_COUNT 3694
+
+// Result of every call, `CCD_STATUS_OK` on success
+typedef enum CcdStatus {
+  CCD_STATUS_OK = 0,
+  // Null pointer or invalid string was passed
+  CCD_STATUS_INVALID_ARGUMENT = 1,
+  // Caller provided buffer cannot fit the result
+  CCD_STATUS_BUFFER_TOO_SMALL = 2,
+  // Serial port or network address could not be opened
+  CCD_STATUS_OPEN_FAILED = 3,
+  CCD_STATUS_INVALID_BAUD_RATE = 4,
+  CCD_STATUS_INVALID_EXPOSURE_TIME = 5,
+  CCD_STATUS_INVALID_AVERAGING_COUNT = 6,
+  CCD_STATUS_INVALID_DATA = 7,
+  CCD_STATUS_UNEXPECTED_EOP = 8,
+  CCD_STATUS_VERSION_DETAIL_TOO_LONG = 9,
+  CCD_STATUS_UNEXPECTED_RESPONSE = 10,
+  // CCD did not respond in time
+  CCD_STATUS_TIMEOUT = 11,
+  // Communication with CCD failed
+  CCD_STATUS_IO_ERROR = 12,
+  // Library hit an internal error, handle should not be used anymore
+  CCD_STATUS_PANIC = 13,
+} CcdStatus;
+
+// Opaque handle of an opened CCD
+typedef struct CcdHandle CcdHandle;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+// Opens CCD connected to a serial port, like "/dev/ttyUSB0" or "COM3". Network bridges are
+// accepted as "tcp://host:port", or as "rfc2217://host:port" to also configure baud rate of the
+// remote port. `baud` is 115200, 384000 or 921600, or 0 for the default of 115200, plain TCP
+// bridges keep their own setting. On success handle is written into `out` and has to be
+// released with `ccd_close`
+//
+// # Safety
+// `port` has to be a NUL terminated string, `out` has to point to a writable pointer
+enum CcdStatus ccd_open(const char *port, uint32_t baud, struct CcdHandle **out);
+
+// Closes CCD and releases handle, null is ignored
+//
+// # Safety
+// `handle` has to be returned by `ccd_open` and not used after this call
+void ccd_close(struct CcdHandle *handle);
+
+// Gets exposure time in milliseconds
+//
+// # Safety
+// `handle` has to be returned by `ccd_open`, `out` has to be writable
+enum CcdStatus ccd_get_exposure_ms(struct CcdHandle *handle, uint16_t *out);
+
+// Sets exposure time in milliseconds, from 1 to 65535
+//
+// # Safety
+// `handle` has to be returned by `ccd_open`
+enum CcdStatus ccd_set_exposure_ms(struct CcdHandle *handle, uint16_t ms);
+
+// Gets amount of exposures averaged into a single frame
+//
+// # Safety
+// `handle` has to be returned by `ccd_open`, `out` has to be writable
+enum CcdStatus ccd_get_averaging(struct CcdHandle *handle, uint8_t *out);
+
+// Sets amount of exposures averaged into a single frame, from 1 to 255
+//
+// # Safety
+// `handle` has to be returned by `ccd_open`
+enum CcdStatus ccd_set_averaging(struct CcdHandle *handle, uint8_t count);
+
+// Takes a single frame into `buf`, which has to fit `CCD_FRAME_PIXEL_COUNT` pixels
+//
+// # Safety
+// `handle` has to be returned by `ccd_open`, `buf` has to be writable for `len` pixels
+enum CcdStatus ccd_get_frame(struct CcdHandle *handle, uint16_t *buf, size_t len);
+
+// Takes `count` frames in continuous mode into `buf`, which has to fit
+// `count * CCD_FRAME_PIXEL_COUNT` pixels
+//
+// # Safety
+// `handle` has to be returned by `ccd_open`, `buf` has to be writable for `len` pixels
+enum CcdStatus ccd_get_frames(struct CcdHandle *handle, uint16_t *buf, size_t len, size_t count);
+
+// Writes CCD version details as a NUL terminated, multiline string into `buf`
+//
+// # Safety
+// `handle` has to be returned by `ccd_open`, `buf` has to be writable for `len` bytes
+enum CcdStatus ccd_get_version(struct CcdHandle *handle, char *buf, size_t len);
+
+// Returns a static, NUL terminated version of this library
+const char *ccd_library_version(void);
+
+// Returns a static, NUL terminated description of a status code. Takes a plain integer, since
+// C callers may pass any value, codes that are not part of this library get a generic message
+const char *ccd_status_message(int status);
+
+#ifdef __cplusplus
+} // extern "C"
+#endif // __cplusplus
+
+#endif /* CCD_LCAMV06_H */
@@ -0,0 +1,235 @@
+//! C interface for ccd_lcamv06. Header is generated into `include/ccd_lcamv06.h` during build
+
+mod status;
+
+use ccd_lcamv06::{
+    AveragingCount, BaudRate, ExposureTime, Frame, IoAdapter, Link, LinkAddress, CCD,
+    FRAME_PIXEL_COUNT,
+};
+use std::{
+    ffi::{c_char, CStr},
+    io::Write,
+    panic::{catch_unwind, AssertUnwindSafe},
+    slice::ChunksExactMut,
+};
+
+pub use status::{ccd_status_message, CcdStatus};
+
+/// Amount of pixels in a single frame, size of frame buffers is measured in pixels
+pub const CCD_FRAME_PIXEL_COUNT: usize = 3694;
+const _: () = assert!(CCD_FRAME_PIXEL_COUNT == FRAME_PIXEL_COUNT);
+
+/// Opaque handle of an opened CCD
+pub struct CcdHandle {
+    ccd: CCD<Link>,
+}
+
+/// Runs a call body, converting errors and panics into status codes
+fn ffi_call<F: FnOnce() -> Result<(), CcdStatus>>(f: F) -> CcdStatus {
+    match catch_unwind(AssertUnwindSafe(f)) {
+        Ok(Ok(())) => CcdStatus::Ok,
+        Ok(Err(status)) => status,
+        Err(_) => CcdStatus::Panic,
+    }
+}
+
+/// Safety: pointer has to be either null or point to a valid value
+unsafe fn deref_mut<'a, T>(p: *mut T) -> Result<&'a mut T, CcdStatus> {
+    p.as_mut().ok_or(CcdStatus::InvalidArgument)
+}
+
+/// Opens CCD connected to a serial port, like "/dev/ttyUSB0" or "COM3". Network bridges are
+/// accepted as "tcp://host:port", or as "rfc2217://host:port" to also configure baud rate of the
+/// remote port. `baud` is 115200, 384000 or 921600, or 0 for the default of 115200, plain TCP
+/// bridges keep their own setting. On success handle is written into `out` and has to be
+/// released with `ccd_close`
+///
+/// # Safety
+/// `port` has to be a NUL terminated string, `out` has to point to a writable pointer
+#[no_mangle]
+pub unsafe extern "C" fn ccd_open(
+    port: *const c_char,
+    baud: u32,
+    out: *mut *mut CcdHandle,
+) -> CcdStatus {
+    ffi_call(|| {
+        if port.is_null() {
+            return Err(CcdStatus::InvalidArgument);
+        }
+        let out = deref_mut(out)?;
+        let port = CStr::from_ptr(port)
+            .to_str()
+            .map_err(|_| CcdStatus::InvalidArgument)?;
+        let baud = match baud {
+            0 => BaudRate::default(),
+            rate => BaudRate::try_from(rate)?,
+        };
+        let mut ccd = Link::open(&LinkAddress::from(port), baud)
+            .map_err(|_| CcdStatus::OpenFailed)?
+            .open_ccd();
+        ccd.sync_settings()?;
+        *out = Box::into_raw(Box::new(CcdHandle { ccd }));
+        Ok(())
+    })
+}
+
+/// Closes CCD and releases handle, null is ignored
+///
+/// # Safety
+/// `handle` has to be returned by `ccd_open` and not used after this call
+#[no_mangle]
+pub unsafe extern "C" fn ccd_close(handle: *mut CcdHandle) {
+    if !handle.is_null() {
+        drop(Box::from_raw(handle));
+    }
+}
+
+/// Gets exposure time in milliseconds
+///
+/// # Safety
+/// `handle` has to be returned by `ccd_open`, `out` has to be writable
+#[no_mangle]
+pub unsafe extern "C" fn ccd_get_exposure_ms(handle: *mut CcdHandle, out: *mut u16) -> CcdStatus {
+    ffi_call(|| {
+        let handle = deref_mut(handle)?;
+        let out = deref_mut(out)?;
+        *out = handle.ccd.get_exp_time()?.as_millis();
+        Ok(())
+    })
+}
+
+/// Sets exposure time in milliseconds, from 1 to 65535
+///
+/// # Safety
+/// `handle` has to be returned by `ccd_open`
+#[no_mangle]
+pub unsafe extern "C" fn ccd_set_exposure_ms(handle: *mut CcdHandle, ms: u16) -> CcdStatus {
+    ffi_call(|| {
+        let handle = deref_mut(handle)?;
+        handle.ccd.set_exp_time(ExposureTime::from_millis(ms)?)?;
+        Ok(())
+    })
+}
+
+/// Gets amount of exposures averaged into a single frame
+///
+/// # Safety
+/// `handle` has to be returned by `ccd_open`, `out` has to be writable
+#[no_mangle]
+pub unsafe extern "C" fn ccd_get_averaging(handle: *mut CcdHandle, out: *mut u8) -> CcdStatus {
+    ffi_call(|| {
+        let handle = deref_mut(handle)?;
+        let out = deref_mut(out)?;
+        *out = handle.ccd.get_avg_time()?.get();
+        Ok(())
+    })
+}
+
+/// Sets amount of exposures averaged into a single frame, from 1 to 255
+///
+/// # Safety
+/// `handle` has to be returned by `ccd_open`
+#[no_mangle]
+pub unsafe extern "C" fn ccd_set_averaging(handle: *mut CcdHandle, count: u8) -> CcdStatus {
+    ffi_call(|| {
+        let handle = deref_mut(handle)?;
+        handle.ccd.set_avg_time(AveragingCount::new(count)?)?;
+        Ok(())
+    })
+}
+
+/// Takes a single frame into `buf`, which has to fit `CCD_FRAME_PIXEL_COUNT` pixels
+///
+/// # Safety
+/// `handle` has to be returned by `ccd_open`, `buf` has to be writable for `len` pixels
+#[no_mangle]
+pub unsafe extern "C" fn ccd_get_frame(
+    handle: *mut CcdHandle,
+    buf: *mut u16,
+    len: usize,
+) -> CcdStatus {
+    ccd_get_frames(handle, buf, len, 1)
+}
+
+/// Writes frames one after another into a caller provided buffer
+struct FrameSink<'a> {
+    chunks: ChunksExactMut<'a, u16>,
+}
+
+impl Extend<Frame> for FrameSink<'_> {
+    fn extend<T: IntoIterator<Item = Frame>>(&mut self, iter: T) {
+        for frame in iter {
+            if let Some(chunk) = self.chunks.next() {
+                chunk.copy_from_slice(frame.pixels());
+            }
+        }
+    }
+}
+
+/// Takes `count` frames in continuous mode into `buf`, which has to fit
+/// `count * CCD_FRAME_PIXEL_COUNT` pixels
+///
+/// # Safety
+/// `handle` has to be returned by `ccd_open`, `buf` has to be writable for `len` pixels
+#[no_mangle]
+pub unsafe extern "C" fn ccd_get_frames(
+    handle: *mut CcdHandle,
+    buf: *mut u16,
+    len: usize,
+    count: usize,
+) -> CcdStatus {
+    ffi_call(|| {
+        let handle = deref_mut(handle)?;
+        if buf.is_null() {
+            return Err(CcdStatus::InvalidArgument);
+        }
+        match count.checked_mul(FRAME_PIXEL_COUNT) {
+            Some(required) if required <= len => {}
+            _ => return Err(CcdStatus::BufferTooSmall),
+        }
+        let buf = std::slice::from_raw_parts_mut(buf, count * FRAME_PIXEL_COUNT);
+        let mut sink = FrameSink {
+            chunks: buf.chunks_exact_mut(FRAME_PIXEL_COUNT),
+        };
+        if count == 1 {
+            let frame = handle.ccd.get_frame()?;
+            sink.extend(std::iter::once(frame));
+        } else {
+            handle.ccd.extend_with_frames(&mut sink, count)?;
+        }
+        Ok(())
+    })
+}
+
+/// Writes CCD version details as a NUL terminated, multiline string into `buf`
+///
+/// # Safety
+/// `handle` has to be returned by `ccd_open`, `buf` has to be writable for `len` bytes
+#[no_mangle]
+pub unsafe extern "C" fn ccd_get_version(
+    handle: *mut CcdHandle,
+    buf: *mut c_char,
+    len: usize,
+) -> CcdStatus {
+    ffi_call(|| {
+        let handle = deref_mut(handle)?;
+        if buf.is_null() {
+            return Err(CcdStatus::InvalidArgument);
+        }
+        let version = handle.ccd.get_version()?.to_string();
+        if version.len() >= len {
+            return Err(CcdStatus::BufferTooSmall);
+        }
+        let mut out = std::slice::from_raw_parts_mut(buf.cast::<u8>(), len);
+        out.write_all(version.as_bytes())
+            .and_then(|_| out.write_all(&[0]))
+            .map_err(|_| CcdStatus::BufferTooSmall)?;
+        Ok(())
+    })
+}
+
+/// Returns a static, NUL terminated version of this library
+#[no_mangle]
+pub extern "C" fn ccd_library_version() -> *const c_char {
+    concat!(env!("CARGO_PKG_VERSION"), "\0").as_ptr().cast()
+}
@@ -0,0 +1,103 @@
+use ccd_lcamv06::error::Error;
+use std::ffi::{c_char, c_int};
+
+/// Result of every call, `CCD_STATUS_OK` on success
+#[repr(C)]
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum CcdStatus {
+    Ok = 0,
+    /// Null pointer or invalid string was passed
+    InvalidArgument = 1,
+    /// Caller provided buffer cannot fit the result
+    BufferTooSmall = 2,
+    /// Serial port or network address could not be opened
+    OpenFailed = 3,
+    InvalidBaudRate = 4,
+    InvalidExposureTime = 5,
+    InvalidAveragingCount = 6,
+    InvalidData = 7,
+    UnexpectedEop = 8,
+    VersionDetailTooLong = 9,
+    UnexpectedResponse = 10,
+    /// CCD did not respond in time
+    Timeout = 11,
+    /// Communication with CCD failed
+    IoError = 12,
+    /// Library hit an internal error, handle should not be used anymore
+    Panic = 13,
+}
+
+impl From<Error> for CcdStatus {
+    fn from(err: Error) -> Self {
+        if err.is_timeout() {
+            return CcdStatus::Timeout;
+        }
+        match err {
+            Error::InvalidBaudRate => CcdStatus::InvalidBaudRate,
+            Error::InvalidExposureTime => CcdStatus::InvalidExposureTime,
+            Error::InvalidAveragingCount => CcdStatus::InvalidAveragingCount,
+            Error::InvalidAutoExposureOptions => CcdStatus::InvalidArgument,
+            Error::InvalidData => CcdStatus::InvalidData,
+            Error::UnexpectedEop => CcdStatus::UnexpectedEop,
+            Error::VersionDetailTooLong(_) => CcdStatus::VersionDetailTooLong,
+            Error::UnexpectedResponse(_) => CcdStatus::UnexpectedResponse,
+            Error::StdIoError(_) => CcdStatus::IoError,
+            Error::EmbeddedHalNbError => CcdStatus::IoError,
+        }
+    }
+}
+
+impl CcdStatus {
+    /// Status with given code, None for codes that are not part of this library
+    fn from_code(code: c_int) -> Option<CcdStatus> {
+        use CcdStatus::*;
+        [
+            Ok,
+            InvalidArgument,
+            BufferTooSmall,
+            OpenFailed,
+            InvalidBaudRate,
+            InvalidExposureTime,
+            InvalidAveragingCount,
+            InvalidData,
+            UnexpectedEop,
+            VersionDetailTooLong,
+            UnexpectedResponse,
+            Timeout,
+            IoError,
+            Panic,
+        ]
+        .into_iter()
+        .find(|status| *status as c_int == code)
+    }
+
+    fn message(self) -> &'static [u8] {
+        use CcdStatus::*;
+        match self {
+            Ok => b"Success\0",
+            InvalidArgument => b"Invalid argument\0",
+            BufferTooSmall => b"Buffer is too small\0",
+            OpenFailed => b"Could not open serial port or network address\0",
+            InvalidBaudRate => b"Baud rate is not in range of accepted values\0",
+            InvalidExposureTime => b"Exposure time is out of range\0",
+            InvalidAveragingCount => b"Averaging count is out of range\0",
+            InvalidData => b"Could not parse recieved data correctly\0",
+            UnexpectedEop => b"Unexpected end of package\0",
+            VersionDetailTooLong => b"Version detail is longer than expected\0",
+            UnexpectedResponse => b"Recieved an unexpected type of response\0",
+            Timeout => b"CCD did not respond in time\0",
+            IoError => b"Communication with CCD failed\0",
+            Panic => b"Internal library error\0",
+        }
+    }
+}
+
+/// Returns a static, NUL terminated description of a status code. Takes a plain integer, since
+/// C callers may pass any value, codes that are not part of this library get a generic message
+#[no_mangle]
+pub extern "C" fn ccd_status_message(status: c_int) -> *const c_char {
+    CcdStatus::from_code(status)
+        .map_or(&b"Unknown status\0"[..], CcdStatus::message)
+        .as_ptr()
+        .cast()
+}
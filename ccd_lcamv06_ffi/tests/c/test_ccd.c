@@ -0,0 +1,71 @@
+/* Exercises C API against a CCD, address is passed as the first argument */
+#include <stdint.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+
+#include "ccd_lcamv06.h"
+
+#define CHECK(call)                                                                    \
+    do {                                                                               \
+        CcdStatus status = (call);                                                     \
+        if (status != CCD_STATUS_OK) {                                                 \
+            fprintf(stderr, "%s:%d: %s failed: %s\n", __FILE__, __LINE__, #call,       \
+                    ccd_status_message(status));                                       \
+            return 1;                                                                  \
+        }                                                                              \
+    } while (0)
+
+#define EXPECT(cond)                                                                   \
+    do {                                                                               \
+        if (!(cond)) {                                                                 \
+            fprintf(stderr, "%s:%d: expected %s\n", __FILE__, __LINE__, #cond);        \
+            return 1;                                                                  \
+        }                                                                              \
+    } while (0)
+
+int main(int argc, char **argv) {
+    if (argc < 2) {
+        fprintf(stderr, "Usage: %s <port>\n", argv[0]);
+        return 2;
+    }
+    printf("Library version: %s\n", ccd_library_version());
+    EXPECT(strcmp(ccd_status_message(CCD_STATUS_TIMEOUT), "CCD did not respond in time") == 0);
+    EXPECT(strcmp(ccd_status_message(-1), "Unknown status") == 0);
+
+    CcdHandle *ccd = NULL;
+    EXPECT(ccd_open(NULL, 0, &ccd) == CCD_STATUS_INVALID_ARGUMENT);
+    EXPECT(ccd_open(argv[1], 9600, &ccd) == CCD_STATUS_INVALID_BAUD_RATE);
+    CHECK(ccd_open(argv[1], 115200, &ccd));
+
+    char version[256];
+    CHECK(ccd_get_version(ccd, version, sizeof(version)));
+    EXPECT(strstr(version, "Serial number") != NULL);
+    EXPECT(ccd_get_version(ccd, version, 4) == CCD_STATUS_BUFFER_TOO_SMALL);
+
+    uint16_t exposure = 0;
+    CHECK(ccd_set_exposure_ms(ccd, 20));
+    CHECK(ccd_get_exposure_ms(ccd, &exposure));
+    EXPECT(exposure == 20);
+    EXPECT(ccd_set_exposure_ms(ccd, 0) == CCD_STATUS_INVALID_EXPOSURE_TIME);
+
+    uint8_t averaging = 0;
+    CHECK(ccd_set_averaging(ccd, 4));
+    CHECK(ccd_get_averaging(ccd, &averaging));
+    EXPECT(averaging == 4);
+
+    uint16_t frame[CCD_FRAME_PIXEL_COUNT];
+    CHECK(ccd_get_frame(ccd, frame, CCD_FRAME_PIXEL_COUNT));
+    EXPECT(frame[CCD_FRAME_PIXEL_COUNT / 2] != 0);
+    EXPECT(ccd_get_frame(ccd, frame, CCD_FRAME_PIXEL_COUNT - 1) == CCD_STATUS_BUFFER_TOO_SMALL);
+
+    size_t count = 3;
+    uint16_t *frames = calloc(count * CCD_FRAME_PIXEL_COUNT, sizeof(uint16_t));
+    CHECK(ccd_get_frames(ccd, frames, count * CCD_FRAME_PIXEL_COUNT, count));
+    EXPECT(frames[(count - 1) * CCD_FRAME_PIXEL_COUNT + CCD_FRAME_PIXEL_COUNT / 2] != 0);
+    free(frames);
+
+    ccd_close(ccd);
+    printf("All checks passed\n");
+    return 0;
+}
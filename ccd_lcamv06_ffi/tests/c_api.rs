@@ -0,0 +1,46 @@
+use utilities::Emulator;
+use std::{env, path::PathBuf, process::Command};
+
+/// Directory with compiled cdylib, cargo places it next to integration test executables
+fn library_dir() -> PathBuf {
+    let exe = env::current_exe().unwrap();
+    exe.parent().unwrap().to_path_buf()
+}
+
+#[test]
+fn c_program_against_emulator() {
+    let manifest_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    let lib_dir = library_dir();
+    let program = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("test_ccd");
+
+    let cc = env::var("CC").unwrap_or_else(|_| "cc".to_string());
+    let status = Command::new(cc)
+        .arg(manifest_dir.join("tests/c/test_ccd.c"))
+        .arg("-Wall")
+        .arg("-Werror")
+        .arg("-I")
+        .arg(manifest_dir.join("include"))
+        .arg("-L")
+        .arg(&lib_dir)
+        .arg(format!("-Wl,-rpath,{}", lib_dir.display()))
+        .arg("-lccd_lcamv06_ffi")
+        .arg("-o")
+        .arg(&program)
+        .status()
+        .expect("C compiler should be available");
+    assert!(status.success(), "Failed to compile C test program");
+
+    let emulator = Emulator::start();
+    // Cargo puts its own output directories first in library path, which may hold a stale build
+    let output = Command::new(&program)
+        .env("LD_LIBRARY_PATH", &lib_dir)
+        .arg(format!("tcp://{}", emulator.addr()))
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "C test program failed:\n{}{}",
+        String::from_utf8_lossy(&output.stdout),
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
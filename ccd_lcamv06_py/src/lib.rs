@@ -0,0 +1,165 @@
+//! Python bindings for ccd_lcamv06, built with maturin as `ccd_lcamv06` module
+
+use ccd_lcamv06::{
+    error::Error, AveragingCount, BaudRate, ExposureTime, Link, LinkAddress,
+    VersionDetails as CCDVersionDetails, CCD, FRAME_PIXEL_COUNT,
+};
+use numpy::{PyArray1, PyArray2, PyArrayMethods};
+use pyo3::{
+    create_exception,
+    exceptions::{PyException, PyTimeoutError},
+    prelude::*,
+    types::PyDict,
+};
+use std::sync::{Mutex, MutexGuard};
+
+create_exception!(ccd_lcamv06, CCDError, PyException, "Communication with CCD failed");
+
+fn to_py_err(err: Error) -> PyErr {
+    if err.is_timeout() {
+        PyTimeoutError::new_err(err.to_string())
+    } else {
+        CCDError::new_err(err.to_string())
+    }
+}
+
+/// CCD version details
+#[pyclass(frozen, get_all, module = "ccd_lcamv06")]
+struct VersionDetails {
+    hardware_version: String,
+    sensor_type: String,
+    firmware_version: String,
+    serial_number: String,
+}
+
+impl From<CCDVersionDetails> for VersionDetails {
+    fn from(d: CCDVersionDetails) -> Self {
+        VersionDetails {
+            hardware_version: d.hardware_version().to_string(),
+            sensor_type: d.sensor_type().to_string(),
+            firmware_version: d.firmware_version().to_string(),
+            serial_number: d.serial_number().to_string(),
+        }
+    }
+}
+
+#[pymethods]
+impl VersionDetails {
+    fn __repr__(&self) -> String {
+        format!(
+            "VersionDetails(hardware_version={:?}, sensor_type={:?}, firmware_version={:?}, serial_number={:?})",
+            self.hardware_version, self.sensor_type, self.firmware_version, self.serial_number
+        )
+    }
+}
+
+/// Connection to a CCD. Accepts serial port name, like "/dev/ttyUSB0" or "COM3", or a network
+/// bridge as "tcp://host:port" or "rfc2217://host:port". Baud rate is set on serial ports and
+/// RFC2217 bridges, one of 115200, 384000 or 921600
+#[pyclass(name = "CCD", module = "ccd_lcamv06")]
+struct PyCCD {
+    // Python objects can be shared between threads, while CCD can only be used by one at a time
+    ccd: Mutex<CCD<Link>>,
+}
+
+impl PyCCD {
+    fn ccd(&self) -> MutexGuard<'_, CCD<Link>> {
+        // Panics are propagated to Python, CCD state is still usable after them
+        self.ccd.lock().unwrap_or_else(|err| err.into_inner())
+    }
+}
+
+#[pymethods]
+impl PyCCD {
+    #[new]
+    #[pyo3(signature = (port, baud = 115200))]
+    fn open(py: Python<'_>, port: &str, baud: u32) -> PyResult<Self> {
+        let baud = BaudRate::try_from(baud).map_err(to_py_err)?;
+        let port = port.to_string();
+        let ccd = py
+            .detach(move || Link::connect(&LinkAddress::from(port.as_str()), baud))
+            .map_err(to_py_err)?;
+        Ok(PyCCD {
+            ccd: Mutex::new(ccd),
+        })
+    }
+
+    /// Exposure time of a single exposure in milliseconds, from 1 to 65535
+    #[getter]
+    fn get_exposure_ms(&self, py: Python<'_>) -> PyResult<u16> {
+        let t = py.detach(|| self.ccd().get_exp_time()).map_err(to_py_err)?;
+        Ok(t.as_millis())
+    }
+
+    #[setter]
+    fn set_exposure_ms(&self, py: Python<'_>, ms: u16) -> PyResult<()> {
+        let t = ExposureTime::from_millis(ms).map_err(to_py_err)?;
+        py.detach(|| self.ccd().set_exp_time(t)).map_err(to_py_err)
+    }
+
+    /// Amount of exposures averaged into a single frame, from 1 to 255
+    #[getter]
+    fn get_averaging(&self, py: Python<'_>) -> PyResult<u8> {
+        let count = py.detach(|| self.ccd().get_avg_time()).map_err(to_py_err)?;
+        Ok(count.get())
+    }
+
+    #[setter]
+    fn set_averaging(&self, py: Python<'_>, count: u8) -> PyResult<()> {
+        let count = AveragingCount::new(count).map_err(to_py_err)?;
+        py.detach(|| self.ccd().set_avg_time(count)).map_err(to_py_err)
+    }
+
+    fn get_version(&self, py: Python<'_>) -> PyResult<VersionDetails> {
+        let version = py.detach(|| self.ccd().get_version()).map_err(to_py_err)?;
+        Ok(version.into())
+    }
+
+    /// Takes a single frame as an array of raw ADC counts
+    fn get_frame<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyArray1<u16>>> {
+        let frame = py.detach(|| self.ccd().get_frame()).map_err(to_py_err)?;
+        Ok(PyArray1::from_slice(py, frame.pixels()))
+    }
+
+    /// Takes `count` frames in continuous mode as a 2D array, one frame per row
+    fn get_frames<'py>(
+        &self,
+        py: Python<'py>,
+        count: usize,
+    ) -> PyResult<Bound<'py, PyArray2<u16>>> {
+        let frames = py
+            .detach(|| {
+                let mut frames = Vec::with_capacity(count);
+                self.ccd().extend_with_frames(&mut frames, count).map(|_| frames)
+            })
+            .map_err(to_py_err)?;
+        PyArray1::from_vec(py, frames.iter().flat_map(|f| f.pixels()).copied().collect()).reshape([count, FRAME_PIXEL_COUNT])
+    }
+
+    /// Link health counters as a dictionary
+    fn stats<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        let stats = self.ccd().stats();
+        let dict = PyDict::new(py);
+        dict.set_item("bytes_read", stats.bytes_read)?;
+        dict.set_item("bytes_written", stats.bytes_written)?;
+        dict.set_item("packages_sent", stats.packages_sent)?;
+        dict.set_item("packages_received", stats.packages_received.total())?;
+        dict.set_item("frames_received", stats.packages_received.single_reading)?;
+        dict.set_item("realignments", stats.realignments)?;
+        dict.set_item("bytes_discarded", stats.bytes_discarded)?;
+        dict.set_item("parse_failures", stats.parse_failures)?;
+        dict.set_item("crc_mismatches", stats.crc_mismatches)?;
+        dict.set_item("timeouts", stats.timeouts)?;
+        dict.set_item("frame_rate", stats.frame_rate())?;
+        Ok(dict)
+    }
+}
+
+#[pymodule(name = "ccd_lcamv06")]
+fn ccd_lcamv06_module(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add("FRAME_PIXEL_COUNT", FRAME_PIXEL_COUNT)?;
+    m.add("CCDError", m.py().get_type::<CCDError>())?;
+    m.add_class::<PyCCD>()?;
+    m.add_class::<VersionDetails>()?;
+    Ok(())
+}
@@ -0,0 +1,76 @@
+"""Emulated CCD served over a local TCP port, replays recorded frame on every reading"""
+import pathlib
+import socket
+import threading
+
+import pytest
+
+RESOURCES = pathlib.Path(__file__).parents[2] / "ccd_lcamv06" / "utilities" / "resources" / "test"
+SINGLE_PACKAGE = bytes.fromhex(
+    (RESOURCES / "single_package_example.txt").read_text().replace("\n", " ")
+)
+VERSION_INFO = b"HdInfo:LCAM_V8.4.2,S11639,V4.2,202111161548"
+
+
+class Emulator:
+    def __init__(self):
+        self.exposure_time = 10
+        self.average_time = 1
+        self.listener = socket.create_server(("127.0.0.1", 0))
+        self.addr = "tcp://127.0.0.1:{}".format(self.listener.getsockname()[1])
+        threading.Thread(target=self._accept, daemon=True).start()
+
+    def _accept(self):
+        while True:
+            try:
+                conn, _ = self.listener.accept()
+            except OSError:
+                return
+            with conn:
+                self._serve(conn)
+
+    def _serve(self, conn):
+        conn.settimeout(0.01)
+        streaming = False
+        cmd = b""
+        while True:
+            try:
+                data = conn.recv(64)
+                if not data:
+                    return
+                cmd += data
+            except socket.timeout:
+                if streaming:
+                    conn.sendall(SINGLE_PACKAGE)
+                continue
+            except OSError:
+                return
+            while len(cmd) >= 5:
+                package, cmd = cmd[:5], cmd[5:]
+                code = package[1]
+                if code == 0x01:
+                    conn.sendall(SINGLE_PACKAGE)
+                elif code == 0x02:
+                    streaming = True
+                elif code == 0x03:
+                    self.exposure_time = int.from_bytes(package[2:4], "big")
+                elif code == 0x06:
+                    streaming = False
+                elif code == 0x09:
+                    conn.sendall(VERSION_INFO)
+                elif code == 0x0A:
+                    conn.sendall(bytes([0x81, 0x02]) + self.exposure_time.to_bytes(2, "big") + b"\xff")
+                elif code == 0x0C:
+                    self.average_time = package[2]
+                elif code == 0x0E:
+                    conn.sendall(bytes([0x81, 0x0E, self.average_time, 0x00, 0xFF]))
+
+    def close(self):
+        self.listener.close()
+
+
+@pytest.fixture
+def emulator():
+    emulator = Emulator()
+    yield emulator
+    emulator.close()
@@ -0,0 +1,75 @@
+import threading
+
+import numpy as np
+import pytest
+
+import ccd_lcamv06
+
+
+@pytest.fixture
+def ccd(emulator):
+    return ccd_lcamv06.CCD(emulator.addr)
+
+
+def test_version(ccd):
+    version = ccd.get_version()
+    assert version.hardware_version == "LCAM_V8.4.2"
+    assert version.sensor_type == "S11639"
+    assert version.firmware_version == "V4.2"
+    assert version.serial_number == "202111161548"
+
+
+def test_baud_rate(emulator):
+    ccd_lcamv06.CCD(emulator.addr, baud=921600).get_version()
+    with pytest.raises(ccd_lcamv06.CCDError):
+        ccd_lcamv06.CCD(emulator.addr, baud=9600)
+
+
+def test_settings(ccd, emulator):
+    ccd.exposure_ms = 20
+    assert ccd.exposure_ms == 20
+    assert emulator.exposure_time == 20
+    ccd.averaging = 4
+    assert ccd.averaging == 4
+    with pytest.raises(ccd_lcamv06.CCDError):
+        ccd.exposure_ms = 0
+
+
+def test_single_frame(ccd):
+    frame = ccd.get_frame()
+    assert frame.dtype == np.uint16
+    assert frame.shape == (ccd_lcamv06.FRAME_PIXEL_COUNT,)
+    # Recorded frame is a flat line with some noise
+    assert frame[10:-10].std() < 100
+
+
+def test_multiple_frames(ccd):
+    frames = ccd.get_frames(5)
+    assert frames.shape == (5, ccd_lcamv06.FRAME_PIXEL_COUNT)
+    assert (frames == frames[0]).all()
+    assert ccd.stats()["frames_received"] == 5
+
+
+def test_acquisition_releases_gil(ccd):
+    # Counter keeps running in another thread while frames are captured
+    ticks = 0
+    done = threading.Event()
+
+    def tick():
+        nonlocal ticks
+        while not done.is_set():
+            ticks += 1
+
+    thread = threading.Thread(target=tick)
+    thread.start()
+    before = ticks
+    ccd.get_frames(20)
+    during = ticks - before
+    done.set()
+    thread.join()
+    assert during > 0
+
+
+def test_open_failure():
+    with pytest.raises(ccd_lcamv06.CCDError):
+        ccd_lcamv06.CCD("/dev/nonexistent-ccd")
@@ -1,15 +1,39 @@
-use ccd_lcamv06::{BaudRate, error::Error};
-use clap::{Args, Parser, Subcommand};
+use ccd_lcamv06::{AveragingCount, BaudRate, ExposureTime, error::Error};
+use clap::{ArgEnum, Args, Parser, Subcommand};
 use num_traits::FromPrimitive;
-use crate::{output::Output, serial::SerialConf};
+use simple_eyre::{eyre::eyre, Result};
+use spectrometer_processing::{
+    BandMetric, CalibrationPoint, Grid, Laser, Store, WavelengthCalibration,
+};
+use std::{ops::RangeInclusive, path::PathBuf, time::Duration};
+use crate::{
+    output::{unique_path_parser, Output, ResampleMethod, XAxis},
+    processing::ProcessingConf,
+    serial::SerialConf,
+};
 
 #[derive(Parser)]
 #[clap(author, version, about, long_about = None)]
 pub struct Cli {
+    /// Directory with data stored per CCD, like wavelength calibrations
+    #[clap(long, global = true, value_parser, env = "SPECTROMETER_DATA_DIR", value_hint = clap::ValueHint::DirPath)]
+    pub data_dir: Option<PathBuf>,
+
     #[clap(subcommand)]
     pub command: Commands,
 }
 
+impl Cli {
+    pub fn store(&self) -> Result<Store> {
+        let root = match &self.data_dir {
+            Some(dir) => dir.clone(),
+            None => Store::default_root()
+                .ok_or_else(|| eyre!("Could not find data directory, set it with --data-dir"))?,
+        };
+        Ok(Store::new(root))
+    }
+}
+
 #[derive(Subcommand)]
 pub enum Commands {
     /// Lists connected serial devices
@@ -20,10 +44,40 @@ pub enum Commands {
     Read(ReadCommand),
     /// Configure baud rate for UART, which is separate from USB port
     BaudRate(BaudRateCommand),
-    /// "Average time" related commands, not sure what that really means
+    /// Amount of exposures averaged by CCD into a single frame
     AverageTime(AvgTimeCommand),
-    /// "Exposure time" related commands, not sure how that's different from "average time"
+    /// Exposure (integration) time of a single exposure
     ExposureTime(ExpTimeCommand),
+    /// Pixel to wavelength calibration, stored per CCD
+    Calibration(CalibrationCommand),
+    /// Find wavelength calibration from a spectrum of a reference lamp and store it for CCD
+    Calibrate(CalibrateConf),
+    /// Dark frames, stored per CCD and exposure time
+    Dark(DarkCommand),
+    /// Reference spectra of a blank for transmittance and absorbance, stored per CCD and exposure time
+    Reference(ReferenceCommand),
+    /// Excitation laser wavelength for Raman shift axis, stored per CCD
+    Laser(LaserCommand),
+    /// Map of hot and dead pixels, which are interpolated over when reading
+    Defects(DefectsCommand),
+    /// Find peaks in a single frame and print their positions, widths and areas
+    Peaks(PeaksConf),
+    /// Named spectra of known materials, used to identify samples
+    Library(LibraryCommand),
+    /// Compare a single frame with library spectra and print best matches
+    Identify(IdentifyConf),
+    /// Fit line shapes to peaks of a spectrum saved as CSV and print their parameters
+    Fit(FitConf),
+    /// Put spectra saved as CSV, possibly by different CCDs, onto a common uniform grid
+    Resample(ResampleConf),
+    /// Read frames continuously and print band metrics of every frame as a time series
+    Monitor(MonitorConf),
+    /// Calibration curves relating absorbance to concentration, shared between CCDs
+    Curve(CurveCommand),
+    /// Measure absorbance of a sample and estimate concentration with a stored curve
+    Quantify(QuantifyConf),
+    /// Apply PCA or PLS model to spectra saved as CSV and print scores, predictions and outlier flags
+    Predict(PredictConf),
 }
 
 #[derive(Args)]
@@ -42,9 +96,21 @@ pub enum ReadCommands {
 
 #[derive(Args)]
 pub struct SingleReadingConf {
+    /// Average this many frames into one
+    #[clap(long, value_parser = clap::value_parser!(u64).range(1..))]
+    pub average: Option<u64>,
+
+    /// Output uncertainty of averaged frame, standard error of the mean of every pixel. Adds a
+    /// column to CSV and a band around chart line
+    #[clap(long, requires = "average")]
+    pub uncertainty: bool,
+
     #[clap(flatten)]
     pub output: Output,
 
+    #[clap(flatten)]
+    pub processing: ProcessingConf,
+
     #[clap(flatten)]
     pub serial: SerialConf,
 }
@@ -58,6 +124,9 @@ pub struct MultiReadingConf {
     #[clap(flatten)]
     pub output: Output,
 
+    #[clap(flatten)]
+    pub processing: ProcessingConf,
+
     #[clap(flatten)]
     pub serial: SerialConf,
 }
@@ -85,7 +154,7 @@ pub struct SetBaudRateConf {
     pub serial: SerialConf,
 }
 
-fn parse_baud_rate(s: &str) -> Result<BaudRate, Error> {
+pub(crate) fn parse_baud_rate(s: &str) -> Result<BaudRate, Error> {
     s.parse()
         .or(Err(()))
         .and_then(|n| FromPrimitive::from_u32(n).ok_or(()))
@@ -100,17 +169,17 @@ pub struct AvgTimeCommand {
 
 #[derive(Subcommand)]
 pub enum AvgTimeCommands {
-    /// Get current "average time"
+    /// Get current averaging count
     Get(SerialConf),
-    /// Set "average time"
+    /// Set averaging count
     Set(SetAvgTimeConf),
 }
 
 #[derive(Args)]
 pub struct SetAvgTimeConf {
-    /// New "average time"
-    #[clap(value_parser)]
-    pub average_time: u8,
+    /// New amount of averaged exposures, from 1 to 255
+    #[clap(value_parser = parse_averaging_count)]
+    pub average_time: AveragingCount,
     #[clap(flatten)]
     pub serial: SerialConf,
 }
@@ -123,17 +192,592 @@ pub struct ExpTimeCommand {
 
 #[derive(Subcommand)]
 pub enum ExpTimeCommands {
-    /// Get current "exposure time"
+    /// Get current exposure time
     Get(SerialConf),
-    /// Set "exposure time"
+    /// Set exposure time
     Set(SetExpTimeConf),
+    /// Search for exposure time that puts peak signal at target level, and leave it set
+    Auto(AutoExpTimeConf),
 }
 
 #[derive(Args)]
 pub struct SetExpTimeConf {
-    /// New "exposure time"
+    /// New exposure time, whole milliseconds from 1ms to 65535ms. Accepts units: us, ms, s
+    #[clap(short, long, value_parser = parse_exposure_time)]
+    pub exposure: ExposureTime,
+    #[clap(flatten)]
+    pub serial: SerialConf,
+}
+
+#[derive(Args)]
+pub struct AutoExpTimeConf {
+    /// Peak signal to aim for, as a fraction of full scale
+    #[clap(short, long, default_value_t = 0.8)]
+    pub target: f64,
+    /// Accepted deviation of peak signal from target, as a fraction of full scale
+    #[clap(long, default_value_t = 0.05)]
+    pub tolerance: f64,
+    /// Amount of exposure times to try before giving up
+    #[clap(short = 'n', long, default_value_t = 10)]
+    pub iterations: usize,
+    /// Shortest exposure time to try. Accepts units: us, ms, s
+    #[clap(long, value_parser = parse_exposure_time, default_value = "1ms")]
+    pub min: ExposureTime,
+    /// Longest exposure time to try. Accepts units: us, ms, s
+    #[clap(long, value_parser = parse_exposure_time, default_value = "1s")]
+    pub max: ExposureTime,
+    #[clap(flatten)]
+    pub serial: SerialConf,
+}
+
+#[derive(Args)]
+pub struct CalibrationCommand {
+    #[clap(subcommand)]
+    pub command: CalibrationCommands,
+}
+
+#[derive(Subcommand)]
+pub enum CalibrationCommands {
+    /// Fit calibration to known wavelengths and store it for CCD
+    Fit(CalibrationFitConf),
+    /// Show calibration stored for CCD
+    Show(SerialConf),
+}
+
+#[derive(Args)]
+pub struct CalibrationFitConf {
+    /// Known wavelength at a pixel as PIXEL:NM, like 1024.5:546.07. Repeat for every point
+    #[clap(short, long = "point", value_parser = parse_calibration_point, required = true)]
+    pub points: Vec<CalibrationPoint>,
+    /// Order of fitted polynomial
+    #[clap(long, value_parser = parse_calibration_order, default_value = "2")]
+    pub order: usize,
+    #[clap(flatten)]
+    pub serial: SerialConf,
+}
+
+#[derive(Args)]
+pub struct DarkCommand {
+    #[clap(subcommand)]
+    pub command: DarkCommands,
+}
+
+#[derive(Subcommand)]
+pub enum DarkCommands {
+    /// Capture and average frames with CCD covered, and store result for current exposure time
+    Capture(CaptureConf),
+}
+
+#[derive(Args)]
+pub struct ReferenceCommand {
+    #[clap(subcommand)]
+    pub command: ReferenceCommands,
+}
+
+#[derive(Subcommand)]
+pub enum ReferenceCommands {
+    /// Capture and average frames of a blank, and store result for current exposure time
+    Capture(CaptureConf),
+}
+
+#[derive(Args)]
+pub struct LaserCommand {
+    #[clap(subcommand)]
+    pub command: LaserCommands,
+}
+
+#[derive(Subcommand)]
+pub enum LaserCommands {
+    /// Store laser wavelength for CCD
+    Set(SetLaserConf),
+    /// Print stored laser wavelength
+    Show(SerialConf),
+}
+
+#[derive(Args)]
+pub struct SetLaserConf {
+    /// Laser wavelength in nm, e.g. 532 or 785nm
+    #[clap(short, long, value_parser)]
+    pub wavelength: Laser,
+    #[clap(flatten)]
+    pub serial: SerialConf,
+}
+
+#[derive(Args)]
+pub struct DefectsCommand {
+    #[clap(subcommand)]
+    pub command: DefectsCommands,
+}
+
+#[derive(Subcommand)]
+pub enum DefectsCommands {
+    /// Capture frames with CCD covered and evenly lit, find outlier pixels and store them
+    Capture(DefectsCaptureConf),
+    /// Print stored hot and dead pixels
+    Show(SerialConf),
+}
+
+#[derive(Args)]
+pub struct DefectsCaptureConf {
+    /// Deviation from neighbouring pixels for a pixel to be defective, in standard deviations
+    #[clap(long, default_value_t = 8.0)]
+    pub threshold: f64,
+    /// Amount of neighbouring pixels a pixel is compared with
+    #[clap(long, default_value_t = 9)]
+    pub window: usize,
+    /// Only print found pixels without storing them
+    #[clap(long)]
+    pub dry_run: bool,
+    #[clap(flatten)]
+    pub capture: CaptureConf,
+}
+
+#[derive(Args)]
+pub struct CaptureConf {
+    /// Amount of averaged frames
+    #[clap(long, value_parser, default_value = "20")]
+    pub count: usize,
+    /// Set exposure time before capturing. Accepts units: us, ms, s
+    #[clap(short, long, value_parser = parse_exposure_time)]
+    pub exposure: Option<ExposureTime>,
+    #[clap(flatten)]
+    pub serial: SerialConf,
+}
+
+#[derive(Args)]
+pub struct CalibrateConf {
+    /// Reference lamp that CCD is looking at
+    #[clap(long, value_enum, default_value_t)]
+    pub lamp: Lamp,
+    /// File with a wavelength in nm per line, used instead of a built-in lamp line list
+    #[clap(long, value_parser, value_hint = clap::ValueHint::FilePath)]
+    pub lines: Option<PathBuf>,
+    /// Order of fitted polynomial
+    #[clap(long, value_parser = parse_calibration_order, default_value = "2")]
+    pub order: usize,
+    /// Plausible dispersion in nm per pixel as MIN:MAX
+    #[clap(long, value_parser = parse_dispersion, default_value = "0.02:1.0")]
+    pub dispersion: RangeInclusive<f64>,
+    /// Maximum distance between a peak and a predicted line position, in pixels
+    #[clap(long, value_parser, default_value = "2")]
+    pub tolerance: f64,
+    /// Minimum prominence of a peak as a fraction of spectrum range
+    #[clap(long, value_parser, default_value = "0.02")]
+    pub min_prominence: f64,
+    /// Only show found calibration, without storing it
+    #[clap(long)]
+    pub dry_run: bool,
+    #[clap(flatten)]
+    pub serial: SerialConf,
+}
+
+#[derive(Args)]
+pub struct PeaksConf {
+    /// Minimum prominence of a peak as a fraction of spectrum range
+    #[clap(long, value_parser, default_value = "0.05")]
+    pub min_prominence: f64,
+    /// Minimum full width at half maximum, in pixels
+    #[clap(long, value_parser, default_value = "0")]
+    pub min_width: f64,
+    /// Minimum absolute height of a peak
+    #[clap(long, value_parser)]
+    pub threshold: Option<f64>,
+    /// Way to find peak position between pixels
+    #[clap(long, value_enum, default_value_t)]
+    pub interpolation: PeakInterpolation,
+    /// Units of positions and widths
+    #[clap(long, value_enum, default_value_t)]
+    pub units: XAxis,
+    #[clap(flatten)]
+    pub processing: ProcessingConf,
+    #[clap(flatten)]
+    pub serial: SerialConf,
+}
+
+#[derive(ArgEnum, Clone, Copy, Default)]
+pub enum PeakInterpolation {
+    #[default]
+    Parabolic,
+    Gaussian,
+    Centroid,
+}
+
+#[derive(Args)]
+pub struct LibraryCommand {
+    #[clap(subcommand)]
+    pub command: LibraryCommands,
+}
+
+#[derive(Subcommand)]
+pub enum LibraryCommands {
+    /// Take a single frame and store it in library under given name
+    Add(LibraryAddConf),
+    /// Print names of library spectra
+    List(LibraryPath),
+}
+
+#[derive(Args)]
+pub struct LibraryPath {
+    /// Library directory or JSON file, defaults to `library` in data directory
+    #[clap(long, value_parser, value_hint = clap::ValueHint::AnyPath)]
+    pub library: Option<PathBuf>,
+}
+
+impl LibraryPath {
+    pub fn resolve(&self, store: &Store) -> PathBuf {
+        self.library.clone().unwrap_or_else(|| store.library_dir())
+    }
+}
+
+#[derive(Args)]
+pub struct LibraryAddConf {
+    /// Name of material, replaces library spectrum with the same name
+    #[clap(short, long)]
+    pub name: String,
+    #[clap(flatten)]
+    pub library: LibraryPath,
+    #[clap(flatten)]
+    pub processing: ProcessingConf,
+    #[clap(flatten)]
+    pub serial: SerialConf,
+}
+
+#[derive(Args)]
+pub struct IdentifyConf {
+    /// Way to score similarity of sample and library spectra
+    #[clap(long, value_enum, default_value_t)]
+    pub metric: MatchMetric,
+    /// Scaling applied to sample and library spectra before scoring
+    #[clap(long, value_enum, default_value_t)]
+    pub normalization: MatchNormalization,
+    /// Amount of best matches to print
+    #[clap(long, value_parser, default_value = "5")]
+    pub top: usize,
+    /// Interpolation of library spectra taken with other CCDs onto wavelengths of this one
+    #[clap(long, value_enum, default_value_t)]
+    pub interpolation: ResampleMethod,
+    #[clap(flatten)]
+    pub library: LibraryPath,
+    // Baseline given here is removed from library spectra as well
+    #[clap(flatten)]
+    pub processing: ProcessingConf,
+    #[clap(flatten)]
+    pub serial: SerialConf,
+}
+
+#[derive(ArgEnum, Clone, Copy, Default)]
+pub enum MatchMetric {
+    /// Pearson correlation, higher is better
+    #[default]
+    Correlation,
+    /// Euclidean distance, lower is better
+    Euclidean,
+    /// Angle between spectra in radians, lower is better
+    SpectralAngle,
+}
+
+#[derive(ArgEnum, Clone, Copy, Default)]
+pub enum MatchNormalization {
+    None,
+    /// Highest value becomes 1
+    Max,
+    /// Sum of absolute values becomes 1
+    Area,
+    /// Euclidean norm becomes 1
+    #[default]
+    Vector,
+    /// Zero mean and unit standard deviation
+    Snv,
+}
+
+#[derive(Args)]
+pub struct FitConf {
+    /// CSV file written by `read`
+    #[clap(value_parser, value_hint = clap::ValueHint::FilePath)]
+    pub input: PathBuf,
+    /// Frame to fit, counting from 1
+    #[clap(long, value_parser = clap::value_parser!(u64).range(1..), default_value = "1")]
+    pub frame: u64,
+    /// Line shape of every peak
+    #[clap(long, value_enum, default_value_t)]
+    pub shape: PeakShape,
+    /// Starting position of a peak in units of file axis. Repeat for overlapping peaks, which
+    /// are then fitted together. Peaks are detected when not given
+    #[clap(short, long = "peak", value_parser)]
+    pub peaks: Vec<f64>,
+    /// Only fit data within MIN:MAX, in units of file axis
+    #[clap(long, value_parser = parse_range)]
+    pub range: Option<RangeInclusive<f64>>,
+    /// Minimum prominence of detected peaks as a fraction of spectrum range
+    #[clap(long, value_parser, default_value = "0.05")]
+    pub min_prominence: f64,
+}
+
+#[derive(Args)]
+pub struct ResampleConf {
+    /// CSV files written by `read` with wavelength or Raman shift axis
+    #[clap(value_parser, required = true, value_hint = clap::ValueHint::FilePath)]
+    pub inputs: Vec<PathBuf>,
+    /// Uniform grid of axis values as START:STOP:STEP
+    #[clap(short, long, value_parser)]
+    pub grid: Grid,
+    /// Interpolation between pixels
+    #[clap(long, value_enum, default_value_t)]
+    pub interpolation: ResampleMethod,
+    /// Path to CSV file with a column for every frame of every input
+    #[clap(short, long, value_parser = unique_path_parser, value_hint = clap::ValueHint::FilePath)]
+    pub output: PathBuf,
+}
+
+#[derive(Args)]
+pub struct MonitorConf {
+    /// Number computed from every frame, one of sum:START:END, mean:START:END, peak:START:END,
+    /// centroid:START:END or ratio:START:END/START:END. Repeat for more columns
+    #[clap(short, long = "metric", value_parser, required = true)]
+    pub metrics: Vec<BandMetric>,
+    /// Units of band limits
+    #[clap(long, value_enum, default_value_t)]
+    pub units: XAxis,
+    /// Stop after this many frames, otherwise runs until interrupted
+    #[clap(long, value_parser)]
+    pub count: Option<usize>,
+    /// Format of lines printed for every frame
+    #[clap(long, value_enum, default_value_t)]
+    pub format: StreamFormat,
+    #[clap(flatten)]
+    pub processing: ProcessingConf,
+    #[clap(flatten)]
+    pub serial: SerialConf,
+}
+
+#[derive(Args)]
+pub struct CurveCommand {
+    #[clap(subcommand)]
+    pub command: CurveCommands,
+}
+
+#[derive(Subcommand)]
+pub enum CurveCommands {
+    /// Fit curve to absorbance spectra of standards and store it under given name
+    Fit(CurveFitConf),
+    /// Show stored curves
+    Show(CurveShowConf),
+    /// Estimate concentration of samples from absorbance spectra saved as CSV
+    Apply(CurveApplyConf),
+}
+
+#[derive(Args)]
+pub struct CurveFitConf {
+    /// Name of analyte, replaces stored curve with the same name
+    #[clap(short, long)]
+    pub name: String,
+    /// Unit of concentrations, like mg/L
+    #[clap(short, long, default_value = "")]
+    pub unit: String,
+    /// Response computed from absorbance in units of file axis. Absorbance at a band is
+    /// mean:START:END or peak:START:END, its integral is sum:START:END
+    #[clap(short, long, value_parser)]
+    pub metric: BandMetric,
+    /// Standard as CONCENTRATION=FILE, with absorbance saved by `read --measure absorbance`.
+    /// Every frame of a file is a separate point. Repeat for every standard
+    #[clap(short, long = "standard", value_parser = parse_standard, required = true)]
+    pub standards: Vec<(f64, PathBuf)>,
+    /// Order of fitted polynomial, 2 allows for deviations from Beer-Lambert law at high
+    /// concentrations
+    #[clap(long, value_parser = clap::value_parser!(u64).range(1..=2), default_value = "1")]
+    pub order: u64,
+}
+
+#[derive(Args)]
+pub struct CurveShowConf {
+    /// Name of analyte, all curves are shown when not given
     #[clap(value_parser)]
-    pub exposure_time: u16,
+    pub name: Option<String>,
+}
+
+#[derive(Args)]
+pub struct CurveApplyConf {
+    /// Name of analyte
+    #[clap(short, long)]
+    pub curve: String,
+    /// CSV files with absorbance in the same axis units as standards. Frames of a file are
+    /// averaged, and every file is a replicate of the sample
+    #[clap(value_parser, required = true, value_hint = clap::ValueHint::FilePath)]
+    pub inputs: Vec<PathBuf>,
+}
+
+#[derive(Args)]
+pub struct QuantifyConf {
+    /// Name of analyte
+    #[clap(short, long)]
+    pub curve: String,
+    /// Average this many frames into one
+    #[clap(long, value_parser = clap::value_parser!(u64).range(1..), default_value = "1")]
+    pub average: u64,
+    /// Units of band limits in curve
+    #[clap(long, value_enum, default_value = "wavelength")]
+    pub units: XAxis,
+    // Measured quantity has to be absorbance
+    #[clap(flatten)]
+    pub processing: ProcessingConf,
     #[clap(flatten)]
     pub serial: SerialConf,
 }
+
+#[derive(Args)]
+pub struct PredictConf {
+    /// Model JSON file, or name of a model in `models` of data directory
+    #[clap(short, long)]
+    pub model: String,
+    /// CSV files written by `read` with the same processing as model calibration spectra
+    #[clap(value_parser, required = true, value_hint = clap::ValueHint::FilePath)]
+    pub inputs: Vec<PathBuf>,
+    /// Format of lines printed for every frame
+    #[clap(long, value_enum, default_value_t)]
+    pub format: StreamFormat,
+}
+
+#[derive(ArgEnum, Clone, Copy, Default)]
+pub enum StreamFormat {
+    /// Header line followed by a line per frame
+    #[default]
+    Csv,
+    /// JSON object per frame, with metrics as fields
+    JsonLines,
+}
+
+#[derive(ArgEnum, Clone, Copy, Default)]
+pub enum PeakShape {
+    #[default]
+    Gaussian,
+    Lorentzian,
+    /// Mix of Gaussian and Lorentzian, with fitted fraction of Lorentzian
+    PseudoVoigt,
+}
+
+#[derive(ArgEnum, Clone, Copy, Default)]
+pub enum Lamp {
+    #[default]
+    HgAr,
+    Ne,
+}
+
+fn parse_dispersion(s: &str) -> Result<RangeInclusive<f64>> {
+    let (min, max) = s
+        .split_once(':')
+        .ok_or_else(|| eyre!("Expected MIN:MAX, got {s:?}"))?;
+    let (min, max): (f64, f64) = (min.trim().parse()?, max.trim().parse()?);
+    if min <= 0.0 || min > max {
+        return Err(eyre!("Dispersion range should be positive, with MIN not above MAX"));
+    }
+    Ok(min..=max)
+}
+
+fn parse_range(s: &str) -> Result<RangeInclusive<f64>> {
+    let (min, max) = s
+        .split_once(':')
+        .ok_or_else(|| eyre!("Expected MIN:MAX, got {s:?}"))?;
+    let (min, max): (f64, f64) = (min.trim().parse()?, max.trim().parse()?);
+    if min >= max {
+        return Err(eyre!("MIN should be below MAX"));
+    }
+    Ok(min..=max)
+}
+
+fn parse_standard(s: &str) -> Result<(f64, PathBuf)> {
+    let (concentration, path) = s
+        .split_once('=')
+        .ok_or_else(|| eyre!("Expected CONCENTRATION=FILE, got {s:?}"))?;
+    let concentration: f64 = concentration.trim().parse()?;
+    if !concentration.is_finite() || concentration < 0.0 {
+        return Err(eyre!("Concentration should not be negative"));
+    }
+    Ok((concentration, PathBuf::from(path)))
+}
+
+fn parse_calibration_point(s: &str) -> Result<CalibrationPoint> {
+    let (pixel, wavelength) = s
+        .split_once(':')
+        .ok_or_else(|| eyre!("Expected PIXEL:NM, got {s:?}"))?;
+    Ok(CalibrationPoint {
+        pixel: pixel.trim().parse()?,
+        wavelength: wavelength.trim().parse()?,
+    })
+}
+
+fn parse_calibration_order(s: &str) -> Result<usize> {
+    let order = s.parse()?;
+    if (WavelengthCalibration::MIN_ORDER..=WavelengthCalibration::MAX_ORDER).contains(&order) {
+        Ok(order)
+    } else {
+        Err(eyre!(
+            "Order should be in range of {} to {}",
+            WavelengthCalibration::MIN_ORDER,
+            WavelengthCalibration::MAX_ORDER
+        ))
+    }
+}
+
+fn parse_averaging_count(s: &str) -> Result<AveragingCount, Error> {
+    s.parse()
+        .map_err(|_| Error::InvalidAveragingCount)
+        .and_then(AveragingCount::new)
+}
+
+/// Parses a duration like "20ms", "1.5s" or "500us". Plain numbers are treated as milliseconds
+fn parse_duration(s: &str) -> Result<Duration> {
+    let s = s.trim();
+    let unit_start = s
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(s.len());
+    let (value, unit) = s.split_at(unit_start);
+    let value: f64 = value
+        .parse()
+        .map_err(|_| eyre!("{s:?} does not start with a number"))?;
+    let secs = match unit.trim() {
+        "us" | "µs" => value / 1_000_000.0,
+        "" | "ms" => value / 1_000.0,
+        "s" => value,
+        unit => return Err(eyre!("Unknown time unit {unit:?}, expected one of: us, ms, s")),
+    };
+    Duration::try_from_secs_f64(secs).map_err(|err| eyre!("{s:?} is not a valid duration: {err}"))
+}
+
+fn parse_exposure_time(s: &str) -> Result<ExposureTime> {
+    // Rounding to whole microseconds, so that floating point errors do not end up in exposure
+    let d = parse_duration(s)?;
+    let d = Duration::from_micros((d.as_secs_f64() * 1_000_000.0).round() as u64);
+    Ok(ExposureTime::try_from(d)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_durations() {
+        assert_eq!(parse_duration("20ms").unwrap(), Duration::from_millis(20));
+        assert_eq!(parse_duration("20").unwrap(), Duration::from_millis(20));
+        assert_eq!(parse_duration("1.5s").unwrap(), Duration::from_millis(1500));
+        assert_eq!(parse_duration("500us").unwrap(), Duration::from_micros(500));
+        assert!(parse_duration("20 min").is_err());
+        assert!(parse_duration("ms").is_err());
+    }
+
+    #[test]
+    fn parse_exposure_times() {
+        assert_eq!(parse_exposure_time("20ms").unwrap().as_millis(), 20);
+        assert_eq!(parse_exposure_time("0.1s").unwrap().as_millis(), 100);
+        assert!(parse_exposure_time("500us").is_err());
+        assert!(parse_exposure_time("70s").is_err());
+    }
+
+    #[test]
+    fn parse_calibration_points() {
+        let point = parse_calibration_point("1024.5:546.07").unwrap();
+        assert_eq!(point.pixel, 1024.5);
+        assert_eq!(point.wavelength, 546.07);
+        assert!(parse_calibration_point("1024.5").is_err());
+        assert!(parse_calibration_point("a:546").is_err());
+    }
+}
@@ -1,27 +1,44 @@
 mod cli;
 mod output;
+mod processing;
 mod serial;
 
+use ccd_lcamv06::{AutoExposureOptions, Frame, FRAME_PIXEL_COUNT};
 use clap::Parser;
-use simple_eyre::Result;
+use simple_eyre::{eyre::eyre, Result};
+use spectrometer_processing::{
+    concentration::{ConcentrationCurve, Estimate, Standard},
+    defects::DefectOptions,
+    fit::{self, FitOptions, PeakGuess, Shape},
+    intensity::intensity,
+    lamp::{self, LampOptions, LineList},
+    library::{LibraryEntry, MatchOptions, Metric, Normalization},
+    peaks::{self, Interpolation, PeakOptions},
+    ChemometricModel, DarkFrame, DefectMap, Library, ReferenceSpectrum, Saturation, Statistics, Store, WavelengthCalibration,
+};
 use num_traits::ToPrimitive;
+use serde::Serialize;
 use std::io::Write;
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
 use termcolor::{Color, ColorChoice, ColorSpec, StandardStream, WriteColor};
 
 use cli::*;
+use output::{csv_field, value_to_csv, JsonFields, SavedSpectra};
+use processing::{Pipeline, Quantity};
 use serial::SerialConf;
 
 fn main() -> Result<()> {
     simple_eyre::install()?;
     let cli = Cli::parse();
     env_logger::init();
+    let store = cli.store()?;
 
     match &cli.command {
         Commands::List => list_serial(),
         Commands::CCDVersion(conf) => get_version(conf),
         Commands::Read(subcomm) => match &subcomm.command {
-            ReadCommands::Single(conf) => get_single_reading(conf),
-            ReadCommands::Multi(conf) => get_multiple_readings(conf),
+            ReadCommands::Single(conf) => get_single_reading(conf, &store),
+            ReadCommands::Multi(conf) => get_multiple_readings(conf, &store),
         },
         Commands::BaudRate(subcomm) => match &subcomm.command {
             BaudRateCommands::Get(conf) => get_baud_rate(conf),
@@ -34,7 +51,43 @@ fn main() -> Result<()> {
         Commands::ExposureTime(subcomm) => match &subcomm.command {
             ExpTimeCommands::Get(conf) => get_exp_time(conf),
             ExpTimeCommands::Set(conf) => set_exp_time(conf),
+            ExpTimeCommands::Auto(conf) => auto_exp_time(conf, &store),
         },
+        Commands::Calibration(subcomm) => match &subcomm.command {
+            CalibrationCommands::Fit(conf) => fit_calibration(conf, &store),
+            CalibrationCommands::Show(conf) => show_calibration(conf, &store),
+        },
+        Commands::Calibrate(conf) => calibrate_from_lamp(conf, &store),
+        Commands::Dark(subcomm) => match &subcomm.command {
+            DarkCommands::Capture(conf) => capture_dark(conf, &store),
+        },
+        Commands::Reference(subcomm) => match &subcomm.command {
+            ReferenceCommands::Capture(conf) => capture_reference(conf, &store),
+        },
+        Commands::Laser(subcomm) => match &subcomm.command {
+            LaserCommands::Set(conf) => set_laser(conf, &store),
+            LaserCommands::Show(conf) => show_laser(conf, &store),
+        },
+        Commands::Defects(subcomm) => match &subcomm.command {
+            DefectsCommands::Capture(conf) => capture_defects(conf, &store),
+            DefectsCommands::Show(conf) => show_defects(conf, &store),
+        },
+        Commands::Peaks(conf) => find_peaks(conf, &store),
+        Commands::Library(subcomm) => match &subcomm.command {
+            LibraryCommands::Add(conf) => add_to_library(conf, &store),
+            LibraryCommands::List(conf) => list_library(conf, &store),
+        },
+        Commands::Identify(conf) => identify(conf, &store),
+        Commands::Fit(conf) => fit_peaks(conf),
+        Commands::Resample(conf) => resample(conf),
+        Commands::Monitor(conf) => monitor(conf, &store),
+        Commands::Curve(subcomm) => match &subcomm.command {
+            CurveCommands::Fit(conf) => fit_curve(conf, &store),
+            CurveCommands::Show(conf) => show_curves(conf, &store),
+            CurveCommands::Apply(conf) => apply_curve(conf, &store),
+        },
+        Commands::Quantify(conf) => quantify(conf, &store),
+        Commands::Predict(conf) => predict(conf, &store),
     }
 }
 
@@ -47,6 +100,69 @@ fn get_stdout() -> StandardStream {
     })
 }
 
+/// Same as `get_stdout`, but for diagnostics that should not end up in piped output
+fn get_stderr() -> StandardStream {
+    StandardStream::stderr(if atty::is(atty::Stream::Stderr) {
+        ColorChoice::Auto
+    } else {
+        ColorChoice::Never
+    })
+}
+
+/// Prints a highlighted warning to stderr
+fn warn(message: impl std::fmt::Display) -> Result<()> {
+    let mut stderr = get_stderr();
+    stderr.set_color(ColorSpec::new().set_fg(Some(Color::Yellow)))?;
+    write!(&mut stderr, "Warning:")?;
+    stderr.reset()?;
+    writeln!(&mut stderr, " {message}")?;
+    Ok(())
+}
+
+/// Prints saturated and clipped pixels of every frame to stderr, their values are meaningless
+fn warn_saturation(frames: &[Frame]) -> Result<()> {
+    for (idx, frame) in frames.iter().enumerate() {
+        if !frame.is_clipped() {
+            continue;
+        }
+        let saturation = Saturation::analyze(frame);
+        if frames.len() > 1 {
+            warn(format!("frame {} has {saturation}", idx + 1))?;
+        } else {
+            warn(format!("frame has {saturation}"))?;
+        }
+    }
+    Ok(())
+}
+
+/// Replaces spikes in frames if requested, and reports amount of replaced pixels to stderr
+fn remove_spikes(pipeline: &Pipeline, frames: &mut [Frame]) -> Result<()> {
+    let replaced = pipeline.despike(frames)?;
+    if replaced > 0 {
+        let mut stderr = get_stderr();
+        stderr.set_color(ColorSpec::new().set_fg(Some(Color::Cyan)))?;
+        write!(&mut stderr, "Despiking:")?;
+        stderr.reset()?;
+        writeln!(&mut stderr, " replaced {replaced} pixel values")?;
+    }
+    Ok(())
+}
+
+/// Prints defective pixels that readings are interpolated over to stderr
+fn report_defects(pipeline: &Pipeline) -> Result<()> {
+    let Some(defects) = pipeline.defects() else {
+        return Ok(());
+    };
+    let pixels = defects.pixels();
+    let list: Vec<_> = pixels.iter().map(|p| p.to_string()).collect();
+    let mut stderr = get_stderr();
+    stderr.set_color(ColorSpec::new().set_fg(Some(Color::Cyan)))?;
+    write!(&mut stderr, "Corrected {} defective pixels:", pixels.len())?;
+    stderr.reset()?;
+    writeln!(&mut stderr, " {}", list.join(", "))?;
+    Ok(())
+}
+
 fn list_serial() -> Result<()> {
     let mut stdout = get_stdout();
     let paths = serialport::available_ports()?;
@@ -63,20 +179,59 @@ fn list_serial() -> Result<()> {
     Ok(())
 }
 
-fn get_multiple_readings(conf: &MultiReadingConf) -> Result<()> {
+fn get_multiple_readings(conf: &MultiReadingConf, store: &Store) -> Result<()> {
     let mut ccd = conf.serial.open_ccd()?;
+    let axis = conf.output.axis(&mut ccd, store)?;
+    let pipeline = conf.processing.pipeline(&mut ccd, store)?;
+    report_defects(&pipeline)?;
     let mut frames: Vec<_> = Vec::with_capacity(conf.count);
 
     ccd.extend_with_frames(&mut frames, conf.count)?;
-    conf.output.write_frames(&frames)?;
+    log::debug!("Link statistics:\n{}", ccd.stats());
+    warn_saturation(&frames)?;
+    remove_spikes(&pipeline, &mut frames)?;
+    let spectra = frames
+        .iter()
+        .map(|frame| pipeline.apply(frame))
+        .collect::<Result<Vec<_>>>()?;
+    conf.output
+        .write_frames(&spectra, axis.as_ref(), pipeline.quantity())?;
 
     Ok(())
 }
 
-fn get_single_reading(conf: &SingleReadingConf) -> Result<()> {
+fn get_single_reading(conf: &SingleReadingConf, store: &Store) -> Result<()> {
     let mut ccd = conf.serial.open_ccd()?;
-    let frame = ccd.get_frame()?;
-    conf.output.write_frame(&frame)?;
+    let axis = conf.output.axis(&mut ccd, store)?;
+    let pipeline = conf.processing.pipeline(&mut ccd, store)?;
+    report_defects(&pipeline)?;
+    let Some(count) = conf.average else {
+        let mut frame = ccd.get_frame()?;
+        log::debug!("Link statistics:\n{}", ccd.stats());
+        warn_saturation(std::slice::from_ref(&frame))?;
+        remove_spikes(&pipeline, std::slice::from_mut(&mut frame))?;
+        conf.output
+            .write_frame(&pipeline.apply(&frame)?, None, axis.as_ref(), pipeline.quantity())?;
+        return Ok(());
+    };
+
+    let count = count as usize;
+    let mut frames: Vec<_> = Vec::with_capacity(count);
+    ccd.extend_with_frames(&mut frames, count)?;
+    log::debug!("Link statistics:\n{}", ccd.stats());
+    warn_saturation(&frames)?;
+    remove_spikes(&pipeline, &mut frames)?;
+    let mut stats = Statistics::new(FRAME_PIXEL_COUNT);
+    for frame in &frames {
+        stats.push(&pipeline.apply(frame)?);
+    }
+    let uncertainty = conf.uncertainty.then(|| stats.std_error());
+    conf.output.write_frame(
+        stats.mean(),
+        uncertainty.as_deref(),
+        axis.as_ref(),
+        pipeline.quantity(),
+    )?;
     Ok(())
 }
 
@@ -102,7 +257,7 @@ fn set_baud_rate(conf: &SetBaudRateConf) -> Result<()> {
 
 fn get_avg_time(conf: &SerialConf) -> Result<()> {
     let mut ccd = conf.open_ccd()?;
-    println!("Current \"average time\": {}", ccd.get_avg_time()?);
+    println!("Current averaging count: {}", ccd.get_avg_time()?);
     Ok(())
 }
 
@@ -114,12 +269,689 @@ fn set_avg_time(conf: &SetAvgTimeConf) -> Result<()> {
 
 fn get_exp_time(conf: &SerialConf) -> Result<()> {
     let mut ccd = conf.open_ccd()?;
-    println!("Current \"exposure time\": {}", ccd.get_exp_time()?);
+    println!("Current exposure time: {}", ccd.get_exp_time()?);
     Ok(())
 }
 
 fn set_exp_time(conf: &SetExpTimeConf) -> Result<()> {
     let mut ccd = conf.serial.open_ccd()?;
-    ccd.set_exp_time(conf.exposure_time)?;
+    ccd.set_exp_time(conf.exposure)?;
+    Ok(())
+}
+
+fn auto_exp_time(conf: &AutoExpTimeConf, store: &Store) -> Result<()> {
+    let mut ccd = conf.serial.open_ccd()?;
+    let version = ccd.get_version()?;
+    // Defects stuck near saturation would keep exposure time short
+    let defects = store
+        .defect_map(version.serial_number())?
+        .map(|defects| defects.pixels())
+        .unwrap_or_default();
+    let options = AutoExposureOptions {
+        target: conf.target,
+        tolerance: conf.tolerance,
+        max_iterations: conf.iterations,
+        min: conf.min,
+        max: conf.max,
+        exclude: &defects,
+    };
+    let result = ccd.auto_exposure(&options)?;
+    println!("{result}");
+    Ok(())
+}
+
+fn fit_calibration(conf: &CalibrationFitConf, store: &Store) -> Result<()> {
+    let mut ccd = conf.serial.open_ccd()?;
+    let version = ccd.get_version()?;
+    let calibration = WavelengthCalibration::fit(conf.points.clone(), conf.order)?;
+    println!("{calibration}");
+    store.save_wavelength_calibration(version.serial_number(), &calibration)?;
+    println!("Saved calibration for CCD {}", version.serial_number());
+    Ok(())
+}
+
+fn show_calibration(conf: &SerialConf, store: &Store) -> Result<()> {
+    let mut ccd = conf.open_ccd()?;
+    let version = ccd.get_version()?;
+    let calibration = store
+        .wavelength_calibration(version.serial_number())?
+        .ok_or_else(|| eyre!("No wavelength calibration stored for CCD {}", version.serial_number()))?;
+    println!("{calibration}");
+    Ok(())
+}
+
+fn calibrate_from_lamp(conf: &CalibrateConf, store: &Store) -> Result<()> {
+    let lines = match &conf.lines {
+        Some(path) => LineList::parse(&std::fs::read_to_string(path)?)?,
+        None => match conf.lamp {
+            Lamp::HgAr => LineList::hg_ar(),
+            Lamp::Ne => LineList::ne(),
+        },
+    };
+    let options = LampOptions {
+        order: conf.order,
+        dispersion: conf.dispersion.clone(),
+        tolerance: conf.tolerance,
+        min_prominence: conf.min_prominence,
+        ..Default::default()
+    };
+
+    let mut ccd = conf.serial.open_ccd()?;
+    let version = ccd.get_version()?;
+    let frame = ccd.get_frame()?;
+    warn_saturation(std::slice::from_ref(&frame))?;
+    let intensities = intensity(&frame);
+    let result = lamp::calibrate(&intensities, &lines, &options)?;
+    println!("{result}");
+
+    if !conf.dry_run {
+        store.save_wavelength_calibration(version.serial_number(), &result.calibration)?;
+        println!("Saved calibration for CCD {}", version.serial_number());
+    }
+    Ok(())
+}
+
+/// Takes frames for averaging, returns them with serial number of CCD
+fn capture_frames(conf: &CaptureConf) -> Result<(String, Vec<Frame>)> {
+    let mut ccd = conf.serial.open_ccd()?;
+    let version = ccd.get_version()?;
+    match conf.exposure {
+        Some(exposure) => ccd.set_exp_time(exposure)?,
+        // Makes CCD attach exposure time to captured frames
+        None => drop(ccd.get_exp_time()?),
+    }
+
+    // First frame may still be integrating with previous settings, so it is dropped
+    let mut frames = Vec::with_capacity(conf.count + 1);
+    ccd.extend_with_frames(&mut frames, conf.count + 1)?;
+    frames.remove(0);
+    Ok((version.serial_number().to_string(), frames))
+}
+
+/// Prints pixels that were saturated or clipped while averaging to stderr, they are left out of
+/// measurements
+fn warn_clipped(clipped: &[usize]) -> Result<()> {
+    if clipped.is_empty() {
+        return Ok(());
+    }
+    let list: Vec<_> = clipped.iter().map(|p| p.to_string()).collect();
+    warn(format!(
+        "{} pixels were saturated or clipped and are left out: {}",
+        clipped.len(),
+        list.join(", ")
+    ))
+}
+
+fn capture_dark(conf: &CaptureConf, store: &Store) -> Result<()> {
+    let (serial, frames) = capture_frames(conf)?;
+    let dark = DarkFrame::average(&frames)?;
+    warn_clipped(dark.clipped())?;
+    store.save_dark_frame(&serial, &dark)?;
+    println!(
+        "Saved dark frame averaged from {} frames at {} for CCD {serial}",
+        dark.frame_count(),
+        dark.exposure(),
+    );
+    Ok(())
+}
+
+fn capture_reference(conf: &CaptureConf, store: &Store) -> Result<()> {
+    let (serial, frames) = capture_frames(conf)?;
+    let reference = ReferenceSpectrum::average(&frames)?;
+    warn_clipped(reference.clipped())?;
+    store.save_reference(&serial, &reference)?;
+    println!(
+        "Saved reference spectrum averaged from {} frames at {} for CCD {serial}",
+        reference.frame_count(),
+        reference.exposure(),
+    );
+    Ok(())
+}
+
+fn set_laser(conf: &SetLaserConf, store: &Store) -> Result<()> {
+    let mut ccd = conf.serial.open_ccd()?;
+    let version = ccd.get_version()?;
+    store.save_laser(version.serial_number(), &conf.wavelength)?;
+    println!(
+        "Saved laser wavelength {} for CCD {}",
+        conf.wavelength,
+        version.serial_number()
+    );
+    Ok(())
+}
+
+fn show_laser(conf: &SerialConf, store: &Store) -> Result<()> {
+    let mut ccd = conf.open_ccd()?;
+    let version = ccd.get_version()?;
+    let laser = store
+        .laser(version.serial_number())?
+        .ok_or_else(|| eyre!("No laser wavelength stored for CCD {}", version.serial_number()))?;
+    println!("Laser wavelength: {laser}");
+    Ok(())
+}
+
+fn capture_defects(conf: &DefectsCaptureConf, store: &Store) -> Result<()> {
+    let wait_for_enter = |prompt: &str| -> Result<()> {
+        println!("{prompt}, then press Enter");
+        std::io::stdin().read_line(&mut String::new())?;
+        Ok(())
+    };
+    wait_for_enter("Cover CCD")?;
+    let (serial, frames) = capture_frames(&conf.capture)?;
+    let dark = DarkFrame::average(&frames)?;
+    wait_for_enter("Light CCD evenly, without saturating it")?;
+    let (_, frames) = capture_frames(&conf.capture)?;
+    let flat = ReferenceSpectrum::average(&frames)?;
+
+    let options = DefectOptions {
+        threshold: conf.threshold,
+        window: conf.window,
+    };
+    let defects = DefectMap::find(&dark, &flat, &options);
+    println!("{defects}");
+    if !conf.dry_run {
+        store.save_defect_map(&serial, &defects)?;
+        println!("Saved defect map for CCD {serial}");
+    }
+    Ok(())
+}
+
+fn show_defects(conf: &SerialConf, store: &Store) -> Result<()> {
+    let mut ccd = conf.open_ccd()?;
+    let version = ccd.get_version()?;
+    let defects = store
+        .defect_map(version.serial_number())?
+        .ok_or_else(|| eyre!("No defect map stored for CCD {}", version.serial_number()))?;
+    println!("{defects}");
+    Ok(())
+}
+
+fn find_peaks(conf: &PeaksConf, store: &Store) -> Result<()> {
+    let mut ccd = conf.serial.open_ccd()?;
+    let axis = conf.units.resolve(&mut ccd, store)?;
+    let pipeline = conf.processing.pipeline(&mut ccd, store)?;
+    report_defects(&pipeline)?;
+    let mut frame = ccd.get_frame()?;
+    warn_saturation(std::slice::from_ref(&frame))?;
+    remove_spikes(&pipeline, std::slice::from_mut(&mut frame))?;
+    let spectrum = pipeline.apply(&frame)?;
+
+    let (min, max) = spectrum
+        .iter()
+        .filter(|y| !y.is_nan())
+        .fold((f64::INFINITY, f64::NEG_INFINITY), |(lo, hi), &y| {
+            (lo.min(y), hi.max(y))
+        });
+    let options = PeakOptions {
+        min_prominence: (max - min) * conf.min_prominence,
+        min_width: conf.min_width,
+        threshold: conf.threshold,
+        interpolation: match conf.interpolation {
+            PeakInterpolation::Parabolic => Interpolation::Parabolic,
+            PeakInterpolation::Gaussian => Interpolation::Gaussian,
+            PeakInterpolation::Centroid => Interpolation::Centroid,
+        },
+    };
+    let peaks = peaks::detect_peaks(&spectrum, &options);
+
+    let unit = axis.unit();
+    println!(
+        "{:>12} {:>12} {:>12} {:>10} {:>12}",
+        format!("Position, {unit}"),
+        "Height",
+        "Prominence",
+        format!("FWHM, {unit}"),
+        "Area"
+    );
+    for peak in &peaks {
+        let (left, right) = (axis.interpolate(peak.left), axis.interpolate(peak.right));
+        // Area is summed over pixels, scaled by local pixel width to get axis units
+        let pixel_width = (axis.interpolate(peak.position + 0.5)
+            - axis.interpolate(peak.position - 0.5))
+        .abs();
+        println!(
+            "{:>12.3} {:>12.1} {:>12.1} {:>10.3} {:>12.1}",
+            axis.interpolate(peak.position),
+            peak.height,
+            peak.prominence,
+            (right - left).abs(),
+            peak.area * pixel_width
+        );
+    }
+    println!("Found {} peaks", peaks.len());
+    Ok(())
+}
+
+fn add_to_library(conf: &LibraryAddConf, store: &Store) -> Result<()> {
+    let mut ccd = conf.serial.open_ccd()?;
+    let version = ccd.get_version()?;
+    let pipeline = conf.processing.pipeline(&mut ccd, store)?;
+    report_defects(&pipeline)?;
+    let mut frame = ccd.get_frame()?;
+    warn_saturation(std::slice::from_ref(&frame))?;
+    remove_spikes(&pipeline, std::slice::from_mut(&mut frame))?;
+    let entry = LibraryEntry {
+        name: conf.name.clone(),
+        wavelengths: store
+            .wavelength_calibration(version.serial_number())?
+            .map(|calibration| calibration.wavelengths(frame.len())),
+        values: pipeline.apply(&frame)?,
+    };
+    let path = Library::save_entry(&conf.library.resolve(store), &entry)?;
+    println!("Saved {:?} to {}", entry.name, path.display());
+    Ok(())
+}
+
+fn list_library(conf: &LibraryPath, store: &Store) -> Result<()> {
+    let library = Library::load(&conf.resolve(store))?;
+    for entry in library.entries() {
+        println!("{}", entry.name);
+    }
+    println!("{} spectra in library", library.entries().len());
+    Ok(())
+}
+
+fn identify(conf: &IdentifyConf, store: &Store) -> Result<()> {
+    let library = Library::load(&conf.library.resolve(store))?;
+    if library.entries().is_empty() {
+        return Err(eyre!("Library is empty, add spectra with `library add`"));
+    }
+    let mut ccd = conf.serial.open_ccd()?;
+    let mut pipeline = conf.processing.pipeline(&mut ccd, store)?;
+    report_defects(&pipeline)?;
+    let options = MatchOptions {
+        metric: match conf.metric {
+            MatchMetric::Correlation => Metric::Correlation,
+            MatchMetric::Euclidean => Metric::Euclidean,
+            MatchMetric::SpectralAngle => Metric::SpectralAngle,
+        },
+        normalization: match conf.normalization {
+            MatchNormalization::None => Normalization::None,
+            MatchNormalization::Max => Normalization::Max,
+            MatchNormalization::Area => Normalization::Area,
+            MatchNormalization::Vector => Normalization::Vector,
+            MatchNormalization::Snv => Normalization::Snv,
+        },
+        baseline: pipeline.take_baseline(),
+        resampling: conf.interpolation.into(),
+    };
+    // Library spectra with wavelengths are resampled onto these, if CCD is calibrated
+    let version = ccd.get_version()?;
+    let mut frame = ccd.get_frame()?;
+    let wavelengths = store
+        .wavelength_calibration(version.serial_number())?
+        .map(|calibration| calibration.wavelengths(frame.len()));
+    warn_saturation(std::slice::from_ref(&frame))?;
+    remove_spikes(&pipeline, std::slice::from_mut(&mut frame))?;
+    let hits = library.identify(&pipeline.apply(&frame)?, wavelengths.as_deref(), &options)?;
+
+    println!("{:<32} {:>10}", "Name", "Score");
+    for hit in hits.iter().take(conf.top) {
+        println!("{hit}");
+    }
+    Ok(())
+}
+
+fn fit_peaks(conf: &FitConf) -> Result<()> {
+    let saved = SavedSpectra::read(&conf.input)?;
+    let (name, y) = saved
+        .columns
+        .get(conf.frame as usize - 1)
+        .ok_or_else(|| eyre!("File has only {} frames", saved.columns.len()))?;
+    let x: Vec<f64> = (0..y.len()).map(|pixel| saved.axis.value(pixel)).collect();
+    // Axis may run in either direction, so range is turned into a span of pixels
+    let pixels = match &conf.range {
+        None => 0..y.len(),
+        Some(range) => {
+            let inside = |&pixel: &usize| range.contains(&x[pixel]);
+            let start = (0..y.len()).find(inside);
+            let end = (0..y.len()).rev().find(inside);
+            match start.zip(end) {
+                Some((start, end)) => start..end + 1,
+                None => return Err(eyre!("No data within range {range:?}")),
+            }
+        }
+    };
+    let (x, y) = (&x[pixels.clone()], &y[pixels]);
+
+    let (min, max) = y
+        .iter()
+        .filter(|y| !y.is_nan())
+        .fold((f64::INFINITY, f64::NEG_INFINITY), |(lo, hi), &y| {
+            (lo.min(y), hi.max(y))
+        });
+    let detected = peaks::find_peaks(y, (max - min) * conf.min_prominence);
+    let options = FitOptions {
+        shape: match conf.shape {
+            PeakShape::Gaussian => Shape::Gaussian,
+            PeakShape::Lorentzian => Shape::Lorentzian,
+            PeakShape::PseudoVoigt => Shape::PseudoVoigt,
+        },
+        ..Default::default()
+    };
+    let fits = if conf.peaks.is_empty() {
+        fit::fit_detected(x, y, &detected, &options)
+    } else {
+        // Height is taken at closest pixel, width from closest detected peak
+        let closest = |center: f64| {
+            (0..x.len())
+                .min_by(|&a, &b| (x[a] - center).abs().total_cmp(&(x[b] - center).abs()))
+                .unwrap_or(0)
+        };
+        let guesses: Vec<PeakGuess> = conf
+            .peaks
+            .iter()
+            .map(|&center| {
+                let pixel = closest(center);
+                let fwhm_pixels = detected
+                    .iter()
+                    .min_by(|a, b| {
+                        (a.position - pixel as f64)
+                            .abs()
+                            .total_cmp(&(b.position - pixel as f64).abs())
+                    })
+                    .map_or(5.0, |peak| peak.fwhm().max(1.0));
+                let span = |offset: f64| {
+                    x[(pixel as f64 + offset).clamp(0.0, (x.len() - 1) as f64) as usize]
+                };
+                PeakGuess {
+                    center,
+                    height: y[pixel],
+                    fwhm: (span(fwhm_pixels / 2.0) - span(-fwhm_pixels / 2.0)).abs(),
+                }
+            })
+            .collect();
+        vec![fit::fit_peaks(x, y, &guesses, &options)?]
+    };
+
+    if fits.is_empty() {
+        return Err(eyre!("No peaks to fit, lower --min-prominence or give positions with --peak"));
+    }
+    println!("{name}, positions and widths in {}", saved.axis.unit());
+    for fit in &fits {
+        println!();
+        println!("{fit}");
+        if !fit.converged {
+            warn("fit did not converge, try other starting positions or a narrower range")?;
+        }
+    }
+    Ok(())
+}
+
+fn resample(conf: &ResampleConf) -> Result<()> {
+    let mut combined: Option<SavedSpectra> = None;
+    for input in &conf.inputs {
+        let mut saved = SavedSpectra::read(input)?.resample(&conf.grid, conf.interpolation.into())?;
+        // Frame headers repeat between files, so they are prefixed with file name
+        let stem = input.file_stem().unwrap_or_default().to_string_lossy();
+        for (name, _) in &mut saved.columns {
+            *name = format!("{stem} {name}");
+        }
+        match &mut combined {
+            None => combined = Some(saved),
+            Some(combined) => combined
+                .extend(saved)
+                .map_err(|_| eyre!("{} has a different axis than previous files", input.display()))?,
+        }
+    }
+    if let Some(combined) = combined {
+        combined.write(&conf.output, false)?;
+        println!(
+            "Saved {} frames resampled onto {} points to {}",
+            combined.columns.len(),
+            conf.grid.len(),
+            conf.output.display()
+        );
+    }
+    Ok(())
+}
+
+fn monitor(conf: &MonitorConf, store: &Store) -> Result<()> {
+    if conf.processing.despike.is_some_and(|despike| despike.needs_batch()) {
+        return Err(eyre!("Frames are processed one at a time, only laplacian spike removal works on them"));
+    }
+    let mut ccd = conf.serial.open_ccd()?;
+    let axis = conf.units.resolve(&mut ccd, store)?;
+    let x: Vec<f64> = (0..FRAME_PIXEL_COUNT).map(|pixel| axis.value(pixel)).collect();
+    let pipeline = conf.processing.pipeline(&mut ccd, store)?;
+    report_defects(&pipeline)?;
+
+    #[derive(Serialize)]
+    struct Line<'a> {
+        time: &'a str,
+        frame: usize,
+        #[serde(flatten)]
+        metrics: JsonFields,
+    }
+
+    let mut stdout = std::io::stdout().lock();
+    if let StreamFormat::Csv = conf.format {
+        let names: Vec<_> = conf.metrics.iter().map(|metric| csv_field(&metric.to_string())).collect();
+        writeln!(stdout, "Time,Frame,{}", names.join(","))?;
+    }
+    let mut idx = 0;
+    let mut line = |mut frame: Frame| -> Result<String> {
+        idx += 1;
+        let time = OffsetDateTime::now_local()?.format(&Rfc3339)?;
+        warn_saturation(std::slice::from_ref(&frame))?;
+        remove_spikes(&pipeline, std::slice::from_mut(&mut frame))?;
+        let spectrum = pipeline.apply(&frame)?;
+        let values = conf.metrics.iter().map(|metric| metric.evaluate(&x, &spectrum));
+        // Bands without measured pixels have no value
+        Ok(match conf.format {
+            StreamFormat::Csv => {
+                let values: Vec<_> = values.map(value_to_csv).collect();
+                format!("{time},{idx},{}", values.join(","))
+            }
+            StreamFormat::JsonLines => serde_json::to_string(&Line {
+                time: &time,
+                frame: idx,
+                metrics: JsonFields(conf.metrics.iter().map(|metric| metric.to_string()).zip(values).collect()),
+            })?,
+        })
+    };
+    // Printing stops on the first error, like a closed pipe
+    let mut result = Ok(());
+    ccd.stream_frames(conf.count, |frame| {
+        result = line(frame).and_then(|line| {
+            writeln!(stdout, "{line}")?;
+            stdout.flush()?;
+            Ok(())
+        });
+        result.is_ok()
+    })?;
+    result
+}
+
+fn fit_curve(conf: &CurveFitConf, store: &Store) -> Result<()> {
+    let mut standards = Vec::new();
+    for (concentration, path) in &conf.standards {
+        let saved = SavedSpectra::read(path)?;
+        let x = saved.x();
+        for (name, y) in saved.frames() {
+            let response = conf.metric.evaluate(&x, y);
+            if !response.is_finite() {
+                return Err(eyre!("{} {name} has no absorbance within {}", path.display(), conf.metric));
+            }
+            standards.push(Standard { concentration: *concentration, response });
+        }
+    }
+    let curve = ConcentrationCurve::fit(
+        conf.name.clone(),
+        conf.unit.clone(),
+        conf.metric,
+        standards,
+        conf.order as usize,
+    )?;
+    println!("{curve}");
+    store.save_concentration_curve(&curve)?;
+    println!("Saved curve {:?}", curve.name);
+    Ok(())
+}
+
+fn show_curves(conf: &CurveShowConf, store: &Store) -> Result<()> {
+    let curves = match &conf.name {
+        Some(name) => vec![load_curve(name, store)?],
+        None => store.concentration_curves()?,
+    };
+    if curves.is_empty() {
+        println!("No curves stored, fit one with `curve fit`");
+    }
+    for curve in curves {
+        println!("{curve}");
+    }
+    Ok(())
+}
+
+fn load_curve(name: &str, store: &Store) -> Result<ConcentrationCurve> {
+    store
+        .concentration_curve(name)?
+        .ok_or_else(|| eyre!("No curve stored for {name:?}"))
+}
+
+/// Prints concentration with uncertainty, and warns about estimates that can't be trusted
+fn print_estimate(label: &str, curve: &ConcentrationCurve, estimate: &Estimate) -> Result<()> {
+    println!(
+        "{label}: {:.4} ± {:.4} {} (response {:.5})",
+        estimate.concentration, estimate.uncertainty, curve.unit, estimate.response
+    );
+    let (lowest, highest) = curve.range();
+    for (flag, text) in [
+        (estimate.below_lod, format!("below limit of detection of {:.4} {}", curve.lod, curve.unit)),
+        (estimate.extrapolated, format!("outside of standards from {lowest} to {highest} {}", curve.unit)),
+    ] {
+        if flag {
+            warn(format!("{label} is {text}"))?;
+        }
+    }
+    Ok(())
+}
+
+fn apply_curve(conf: &CurveApplyConf, store: &Store) -> Result<()> {
+    let curve = load_curve(&conf.curve, store)?;
+    let mut responses = Vec::with_capacity(conf.inputs.len());
+    for path in &conf.inputs {
+        let saved = SavedSpectra::read(path)?;
+        let x = saved.x();
+        let label = path.file_stem().unwrap_or_default().to_string_lossy();
+        let (mut values, mut skipped) = (Vec::new(), 0);
+        for (_, y) in saved.frames() {
+            match curve.metric.evaluate(&x, y) {
+                value if value.is_finite() => values.push(value),
+                _ => skipped += 1,
+            }
+        }
+        if values.is_empty() {
+            return Err(eyre!("{} has no frame where {} can be measured", path.display(), curve.name));
+        }
+        if skipped > 0 {
+            warn(format!("{skipped} frames of {label} have no valid response and are left out"))?;
+        }
+        let response = values.iter().sum::<f64>() / values.len() as f64;
+        print_estimate(&label, &curve, &curve.concentration(response, 1)?)?;
+        responses.push(response);
+    }
+    if responses.len() > 1 {
+        let mean = responses.iter().sum::<f64>() / responses.len() as f64;
+        let label = format!("Mean of {} replicates", responses.len());
+        print_estimate(&label, &curve, &curve.concentration(mean, responses.len())?)?;
+    }
+    Ok(())
+}
+
+fn quantify(conf: &QuantifyConf, store: &Store) -> Result<()> {
+    let curve = load_curve(&conf.curve, store)?;
+    let mut ccd = conf.serial.open_ccd()?;
+    let axis = conf.units.resolve(&mut ccd, store)?;
+    let x: Vec<f64> = (0..FRAME_PIXEL_COUNT).map(|pixel| axis.value(pixel)).collect();
+    let pipeline = conf.processing.pipeline(&mut ccd, store)?;
+    if !matches!(pipeline.quantity(), Quantity::Absorbance) {
+        return Err(eyre!("Concentration is estimated from absorbance, add --measure absorbance"));
+    }
+    report_defects(&pipeline)?;
+    let mut frames: Vec<_> = Vec::with_capacity(conf.average as usize);
+    ccd.extend_with_frames(&mut frames, conf.average as usize)?;
+    warn_saturation(&frames)?;
+    remove_spikes(&pipeline, &mut frames)?;
+    let mut stats = Statistics::new(FRAME_PIXEL_COUNT);
+    for frame in &frames {
+        stats.push(&pipeline.apply(frame)?);
+    }
+    print_estimate(&curve.name, &curve, &curve.estimate(&x, stats.mean())?)
+}
+
+fn predict(conf: &PredictConf, store: &Store) -> Result<()> {
+    let path = std::path::Path::new(&conf.model);
+    let model = if path.is_file() {
+        ChemometricModel::load(path)?
+    } else {
+        store
+            .models()?
+            .into_iter()
+            .find(|model| model.name == conf.model)
+            .ok_or_else(|| eyre!("No model file or stored model named {:?}", conf.model))?
+    };
+
+    #[derive(Serialize)]
+    struct Line<'a> {
+        file: &'a str,
+        frame: &'a str,
+        #[serde(flatten)]
+        values: JsonFields,
+        t_squared: Option<f64>,
+        q: Option<f64>,
+        t_squared_outlier: bool,
+        q_outlier: bool,
+    }
+
+    let mut stdout = std::io::stdout().lock();
+    let scores = (1..=model.components()).map(|component| format!("T{component}"));
+    let predictions = model.regression.iter().map(|regression| regression.name.clone());
+    let names: Vec<String> = scores.chain(predictions).collect();
+    if let StreamFormat::Csv = conf.format {
+        // Names of predicted properties may contain commas
+        let names: Vec<_> = names.iter().map(|name| csv_field(name)).collect();
+        writeln!(stdout, "File,Frame,{},T²,Q,T² outlier,Q outlier", names.join(","))?;
+    }
+    let mut outliers = 0;
+    for input in &conf.inputs {
+        let saved = SavedSpectra::read(input)?;
+        let x = saved.x();
+        let file = input.file_name().unwrap_or_default().to_string_lossy();
+        for (frame, y) in saved.frames() {
+            let output = model.apply(&x, y)?;
+            let (t_outlier, q_outlier) = (output.t_squared_outlier(&model), output.q_outlier(&model));
+            if t_outlier || q_outlier {
+                outliers += 1;
+            }
+            let values = output.scores.iter().chain(&output.predictions).copied();
+            let line = match conf.format {
+                StreamFormat::Csv => {
+                    let values: Vec<_> = values.map(value_to_csv).collect();
+                    format!(
+                        "{},{},{},{},{},{t_outlier},{q_outlier}",
+                        csv_field(&file),
+                        csv_field(frame),
+                        values.join(","),
+                        value_to_csv(output.t_squared),
+                        value_to_csv(output.q_residual)
+                    )
+                }
+                StreamFormat::JsonLines => serde_json::to_string(&Line {
+                    file: &file,
+                    frame,
+                    values: JsonFields(names.iter().cloned().zip(values).collect()),
+                    t_squared: output.t_squared.is_finite().then_some(output.t_squared),
+                    q: output.q_residual.is_finite().then_some(output.q_residual),
+                    t_squared_outlier: t_outlier,
+                    q_outlier,
+                })?,
+            };
+            writeln!(stdout, "{line}")?;
+        }
+    }
+    if outliers > 0 {
+        warn(format!("{outliers} frames are outside of model limits, their predictions are not reliable"))?;
+    }
     Ok(())
 }
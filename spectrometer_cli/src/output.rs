@@ -1,11 +1,19 @@
-use ccd_lcamv06::Frame;
+use crate::{processing::Quantity, serial::SerialCCD};
+use ccd_lcamv06::{ADC_FULL_SCALE, FRAME_PIXEL_COUNT};
+use spectrometer_processing::{
+    raman::Side,
+    resample::{resample, Grid, Resampling},
+    Store,
+};
 use time::{OffsetDateTime, macros::format_description, format_description::FormatItem};
 use clap::{ArgEnum, Args};
 use plotters::prelude::*;
+use serde::{Serialize, Serializer};
 use simple_eyre::{eyre::eyre, Result};
 use std::{
     fs::File,
     io::Write,
+    ops::Range,
     path::{Path, PathBuf},
 };
 
@@ -18,9 +26,23 @@ pub struct Output {
     /// File format for reading output
     #[clap(long, value_enum, default_value_t)]
     pub format: OutputFormat,
+
+    /// Horizontal axis of a chart. For CSV output it switches layout to a column per frame,
+    /// preceded by columns with pixel number and axis values
+    #[clap(long, value_enum)]
+    pub x_axis: Option<XAxis>,
+
+    /// Resample frames onto a uniform grid of axis values given as START:STOP:STEP, so that
+    /// readings of different CCDs line up. Values past measured range are left empty
+    #[clap(long, value_parser, requires = "x-axis")]
+    pub resample: Option<Grid>,
+
+    /// Interpolation between pixels used for resampling
+    #[clap(long, value_enum, default_value_t, requires = "resample")]
+    pub interpolation: ResampleMethod,
 }
 
-fn unique_path_parser(p: &str) -> Result<PathBuf> {
+pub fn unique_path_parser(p: &str) -> Result<PathBuf> {
     let p = Path::new(p);
     if p.try_exists()? {
         Err(eyre!("Path {p:?} already exists"))
@@ -36,26 +58,380 @@ pub enum OutputFormat {
     Csv,
 }
 
-fn frame_to_csv(frame: &Frame) -> String {
+#[derive(ArgEnum, Clone, Copy, Default)]
+pub enum ResampleMethod {
+    #[default]
+    Linear,
+    /// Natural cubic spline, smoother but may overshoot around sharp peaks
+    Cubic,
+}
+
+impl From<ResampleMethod> for Resampling {
+    fn from(method: ResampleMethod) -> Self {
+        match method {
+            ResampleMethod::Linear => Resampling::Linear,
+            ResampleMethod::Cubic => Resampling::CubicSpline,
+        }
+    }
+}
+
+#[derive(ArgEnum, Clone, Copy, Default)]
+pub enum XAxis {
+    #[default]
+    Pixel,
+    /// Requires wavelength calibration stored for CCD
+    Wavelength,
+    /// Stokes Raman shift, requires wavelength calibration and laser wavelength stored for CCD
+    RamanShift,
+    /// Same as Raman shift, but positive on the anti-Stokes side of laser line
+    AntiStokesShift,
+}
+
+/// Horizontal axis values resolved for a specific CCD
+pub enum Axis {
+    Pixel,
+    Wavelength(Vec<f64>),
+    /// Raman shift in cm⁻¹ of every pixel
+    RamanShift(Vec<f64>),
+}
+
+impl XAxis {
+    /// Resolves axis values, wavelengths come from calibration stored for CCD and Raman shifts
+    /// also need its laser wavelength
+    pub fn resolve(self, ccd: &mut SerialCCD, store: &Store) -> Result<Axis> {
+        let side = match self {
+            XAxis::Pixel => return Ok(Axis::Pixel),
+            XAxis::Wavelength => None,
+            XAxis::RamanShift => Some(Side::Stokes),
+            XAxis::AntiStokesShift => Some(Side::AntiStokes),
+        };
+        let version = ccd.get_version()?;
+        let serial = version.serial_number();
+        let calibration = store.wavelength_calibration(serial)?.ok_or_else(|| {
+            eyre!("No wavelength calibration stored for CCD {serial}, create one with `calibration fit`")
+        })?;
+        let wavelengths = calibration.wavelengths(FRAME_PIXEL_COUNT);
+        let Some(side) = side else {
+            return Ok(Axis::Wavelength(wavelengths));
+        };
+        let laser = store.laser(serial)?.ok_or_else(|| {
+            eyre!("No laser wavelength stored for CCD {serial}, set one with `laser set`")
+        })?;
+        Ok(Axis::RamanShift(laser.raman_shifts(&wavelengths, side)))
+    }
+}
+
+impl Axis {
+    fn label(&self) -> &'static str {
+        match self {
+            Axis::Pixel => "Pixel #",
+            Axis::Wavelength(_) => "Wavelength, nm",
+            Axis::RamanShift(_) => "Raman shift, cm⁻¹",
+        }
+    }
+
+    pub fn unit(&self) -> &'static str {
+        match self {
+            Axis::Pixel => "px",
+            Axis::Wavelength(_) => "nm",
+            Axis::RamanShift(_) => "cm⁻¹",
+        }
+    }
+
+    /// Axis value of every pixel, None for pixel numbers
+    fn values(&self) -> Option<&[f64]> {
+        match self {
+            Axis::Pixel => None,
+            Axis::Wavelength(values) | Axis::RamanShift(values) => Some(values),
+        }
+    }
+
+    pub fn value(&self, pixel: usize) -> f64 {
+        match self.values() {
+            None => pixel as f64,
+            Some(values) => values[pixel],
+        }
+    }
+
+    /// Value at a sub-pixel position, linearly interpolated between pixels. An axis of a single
+    /// pixel has the same value everywhere, an empty one has none
+    pub fn interpolate(&self, position: f64) -> f64 {
+        match self.values() {
+            None => position,
+            Some([]) => f64::NAN,
+            Some([value]) => *value,
+            Some(values) => {
+                let last = values.len() - 1;
+                let idx = (position.floor().max(0.0) as usize).min(last - 1);
+                let fraction = position - idx as f64;
+                values[idx] + (values[idx + 1] - values[idx]) * fraction
+            }
+        }
+    }
+
+    /// Same kind of axis with other values, None for pixel numbers
+    fn with_values(&self, values: Vec<f64>) -> Option<Axis> {
+        match self {
+            Axis::Pixel => None,
+            Axis::Wavelength(_) => Some(Axis::Wavelength(values)),
+            Axis::RamanShift(_) => Some(Axis::RamanShift(values)),
+        }
+    }
+
+    fn is_same_kind(&self, other: &Axis) -> bool {
+        core::mem::discriminant(self) == core::mem::discriminant(other)
+    }
+
+    /// Interpolates columns with a value per pixel onto grid, which becomes their axis
+    fn resample(
+        &self,
+        columns: &[&[f64]],
+        grid: &Grid,
+        method: Resampling,
+    ) -> Result<(Axis, Vec<Spectrum>)> {
+        let points = grid.points();
+        let (Some(values), Some(axis)) = (self.values(), self.with_values(points.clone())) else {
+            return Err(eyre!("Resampling needs wavelength or Raman shift axis"));
+        };
+        let columns = columns
+            .iter()
+            .map(|column| resample(values, column, &points, method))
+            .collect::<Result<_, _>>()?;
+        Ok((axis, columns))
+    }
+
+    fn range(&self, len: usize) -> Range<f64> {
+        (0..len)
+            .map(|pixel| self.value(pixel))
+            .fold(f64::INFINITY..f64::NEG_INFINITY, |r, x| r.start.min(x)..r.end.max(x))
+    }
+}
+
+/// Frame after processing, a value per pixel. NaN marks pixels that could not be measured
+pub type Spectrum = Vec<f64>;
+
+/// Pixels without a value are left as empty fields, same as infinities that can't be read back
+pub fn value_to_csv(value: f64) -> String {
+    if value.is_finite() {
+        value.to_string()
+    } else {
+        String::new()
+    }
+}
+
+/// Quotes field that has separators, quotes or line breaks in it, doubling quotes inside
+pub fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// Named values of a JSON line, written in given order. Values that are not finite have no
+/// JSON representation, so they are written as null
+pub struct JsonFields(pub Vec<(String, f64)>);
+
+impl Serialize for JsonFields {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.collect_map(
+            self.0
+                .iter()
+                .map(|(name, value)| (name, value.is_finite().then_some(*value))),
+        )
+    }
+}
+
+fn frame_to_csv(frame: &[f64]) -> String {
     log::trace!("Formatting frame as CSV");
     frame
         .iter()
-        .map(|pixel| pixel.to_string())
+        .map(|&pixel| value_to_csv(pixel))
         .collect::<Vec<_>>()
         .join(",")
 }
 
-fn frames_to_csv(frames: &[Frame]) -> String {
+fn frames_to_csv(frames: &[Spectrum]) -> String {
     log::trace!("Formatting frames as CSV");
     frames
         .iter()
-        .map(frame_to_csv)
+        .map(|frame| frame_to_csv(frame))
         .collect::<Vec<_>>()
         .join("\n")
 }
 
+/// Formats frames as columns, preceded by pixel number and axis value for every row
+fn frames_to_csv_table(frames: &[Spectrum], axis: &Axis) -> String {
+    log::trace!("Formatting frames as CSV table");
+    let headers = (1..=frames.len()).map(|idx| format!("Frame {idx}")).collect();
+    let columns: Vec<_> = frames.iter().map(|frame| frame.as_slice()).collect();
+    columns_to_csv_table(headers, &columns, axis, true)
+}
+
+/// Formats averaged frame as a column followed by a column with its uncertainty
+fn mean_to_csv_table(mean: &[f64], uncertainty: &[f64], axis: &Axis, pixel_column: bool) -> String {
+    log::trace!("Formatting averaged frame as CSV table");
+    let headers = vec![String::from("Mean"), String::from("Uncertainty")];
+    columns_to_csv_table(headers, &[mean, uncertainty], axis, pixel_column)
+}
+
+/// Rows of resampled data are grid points rather than pixels, so they go without pixel column
+fn columns_to_csv_table(
+    headers: Vec<String>,
+    columns: &[&[f64]],
+    axis: &Axis,
+    pixel_column: bool,
+) -> String {
+    let mut header = Vec::new();
+    if pixel_column {
+        header.push(String::from("Pixel #"));
+    }
+    match axis {
+        Axis::Pixel => {}
+        Axis::Wavelength(_) => header.push(String::from("Wavelength (nm)")),
+        Axis::RamanShift(_) => header.push(String::from("Raman shift (cm⁻¹)")),
+    }
+    header.extend(headers);
+
+    let rows = columns.first().map_or(0, |column| column.len());
+    let mut lines = vec![header.join(",")];
+    for row in 0..rows {
+        let mut line = Vec::new();
+        if pixel_column {
+            line.push(row.to_string());
+        }
+        if axis.values().is_some() {
+            line.push(format!("{:.3}", axis.value(row)));
+        }
+        line.extend(columns.iter().map(|column| value_to_csv(column[row])));
+        lines.push(line.join(","));
+    }
+    lines.join("\n")
+}
+
+/// Spectra read back from a CSV file written by `read`
+pub struct SavedSpectra {
+    pub axis: Axis,
+    /// Header and values of every frame column
+    pub columns: Vec<(String, Spectrum)>,
+}
+
+impl SavedSpectra {
+    pub fn read(path: &Path) -> Result<SavedSpectra> {
+        log::debug!("Reading spectra from {path:?}");
+        csv_to_spectra(&std::fs::read_to_string(path)?)
+    }
+
+    /// Axis value of every row
+    pub fn x(&self) -> Vec<f64> {
+        let rows = self.columns.first().map_or(0, |(_, column)| column.len());
+        (0..rows).map(|pixel| self.axis.value(pixel)).collect()
+    }
+
+    /// Columns with spectra, leaving out uncertainty written next to averaged frame
+    pub fn frames(&self) -> impl Iterator<Item = &(String, Spectrum)> {
+        self.columns.iter().filter(|(name, _)| name != "Uncertainty")
+    }
+
+    /// Interpolates every column onto grid of axis values
+    pub fn resample(&self, grid: &Grid, method: Resampling) -> Result<SavedSpectra> {
+        let columns: Vec<_> = self.columns.iter().map(|(_, column)| column.as_slice()).collect();
+        let (axis, columns) = self.axis.resample(&columns, grid, method)?;
+        let names = self.columns.iter().map(|(name, _)| name.clone());
+        Ok(SavedSpectra {
+            axis,
+            columns: names.zip(columns).collect(),
+        })
+    }
+
+    /// Appends columns of other spectra, which should be on the same axis
+    pub fn extend(&mut self, other: SavedSpectra) -> Result<()> {
+        if !self.axis.is_same_kind(&other.axis) || self.axis.values() != other.axis.values() {
+            return Err(eyre!("Spectra are on different axes"));
+        }
+        self.columns.extend(other.columns);
+        Ok(())
+    }
+
+    /// Writes columns as CSV table, with pixel column left out for resampled spectra
+    pub fn write(&self, path: &Path, pixel_column: bool) -> Result<()> {
+        log::debug!("Saving spectra to {path:?}");
+        let headers = self.columns.iter().map(|(name, _)| name.clone()).collect();
+        let columns: Vec<_> = self.columns.iter().map(|(_, column)| column.as_slice()).collect();
+        let data = columns_to_csv_table(headers, &columns, &self.axis, pixel_column);
+        File::create(path)?.write_all(data.as_bytes())?;
+        Ok(())
+    }
+}
+
+/// Parses every layout: a line per frame, or a table with a column per frame, with or without
+/// pixel column
+fn csv_to_spectra(data: &str) -> Result<SavedSpectra> {
+    let parse_line = |line: &str| -> Result<Vec<f64>> {
+        line.split(',')
+            .map(|field| match field.trim() {
+                "" => Ok(f64::NAN),
+                field => field
+                    .parse()
+                    .map_err(|_| eyre!("Expected a number in CSV, got {field:?}")),
+            })
+            .collect()
+    };
+    let mut lines = data.lines().filter(|line| !line.trim().is_empty());
+    let header = lines.next().ok_or_else(|| eyre!("CSV file is empty"))?;
+    let mut names: Vec<&str> = header.split(',').collect();
+    let pixel_column = names.first() == Some(&"Pixel #");
+    if pixel_column {
+        names.remove(0);
+    }
+    let axis_column: Option<fn(Vec<f64>) -> Axis> = match names.first() {
+        Some(&"Wavelength (nm)") => Some(Axis::Wavelength),
+        Some(&"Raman shift (cm⁻¹)") => Some(Axis::RamanShift),
+        _ => None,
+    };
+    if axis_column.is_some() {
+        names.remove(0);
+    }
+    if !pixel_column && axis_column.is_none() {
+        let columns = std::iter::once(header)
+            .chain(lines)
+            .enumerate()
+            .map(|(idx, line)| Ok((format!("Frame {}", idx + 1), parse_line(line)?)))
+            .collect::<Result<_>>()?;
+        return Ok(SavedSpectra {
+            axis: Axis::Pixel,
+            columns,
+        });
+    }
+
+    let offset = usize::from(pixel_column) + usize::from(axis_column.is_some());
+    let mut axis_values = Vec::new();
+    let mut columns: Vec<(String, Spectrum)> =
+        names.iter().map(|name| (name.to_string(), Vec::new())).collect();
+    for line in lines {
+        let values = parse_line(line)?;
+        if values.len() != offset + columns.len() {
+            return Err(eyre!("CSV line {line:?} does not match its header"));
+        }
+        if axis_column.is_some() {
+            axis_values.push(values[offset - 1]);
+        }
+        for ((_, column), value) in columns.iter_mut().zip(&values[offset..]) {
+            column.push(*value);
+        }
+    }
+    Ok(SavedSpectra {
+        axis: axis_column.map_or(Axis::Pixel, |axis| axis(axis_values)),
+        columns,
+    })
+}
+
 struct ChartData<'a> {
-    frame: &'a Frame,
+    frame: &'a [f64],
+    /// Drawn as a band around frame
+    uncertainty: Option<&'a [f64]>,
+    axis: &'a Axis,
+    quantity: Quantity,
     idx: usize,
     timestamp: OffsetDateTime,
 }
@@ -83,20 +459,45 @@ where
         )
         .set_label_area_size(LabelAreaPosition::Left, (8).percent())
         .set_label_area_size(LabelAreaPosition::Bottom, (5).percent())
-        .build_cartesian_2d(0..data.frame.len(), 0u32..100_000u32)?;
+        .build_cartesian_2d(
+            data.axis.range(data.frame.len()),
+            y_range(data.frame, data.uncertainty, data.quantity),
+        )?;
 
     log::trace!("Writing chart axes labels");
     chart
         .configure_mesh()
-        .x_desc("Pixel #")
-        .y_desc("Inverse intensity")
+        .x_desc(data.axis.label())
+        .y_desc(data.quantity.label())
         .draw()?;
 
+    if let Some(uncertainty) = data.uncertainty {
+        log::trace!("Drawing uncertainty band");
+        for sign in [-1.0, 1.0] {
+            let points: Vec<_> = data
+                .frame
+                .iter()
+                .zip(uncertainty)
+                .enumerate()
+                .map(|(x, (y, u))| (data.axis.value(x), y + sign * u))
+                .collect();
+            for segment in points.split(|(_, y)| y.is_nan()) {
+                chart.draw_series(LineSeries::new(segment.iter().copied(), RGBColor(180, 180, 180)))?;
+            }
+        }
+    }
+
     log::trace!("Drawing frame as a line chart");
-    chart.draw_series(LineSeries::new(
-        data.frame.iter().enumerate().map(|(x, y)| (x, *y as u32)),
-        BLACK,
-    ))?;
+    // Line is interrupted at pixels without a value
+    let points: Vec<_> = data
+        .frame
+        .iter()
+        .enumerate()
+        .map(|(x, y)| (data.axis.value(x), *y))
+        .collect();
+    for segment in points.split(|(_, y)| y.is_nan()) {
+        chart.draw_series(LineSeries::new(segment.iter().copied(), BLACK))?;
+    }
 
     log::trace!("Pushing frame chart to rendering backend");
     root.present()?;
@@ -104,9 +505,61 @@ where
     Ok(())
 }
 
+/// Range of finite values, uncertainty band included, with a margin on both sides. Falls back
+/// to full ADC range or 0 to 1 for ratios when there is nothing to show
+fn y_range(frame: &[f64], uncertainty: Option<&[f64]>, quantity: Quantity) -> Range<f64> {
+    let band = uncertainty
+        .into_iter()
+        .flat_map(|uncertainty| frame.iter().zip(uncertainty).flat_map(|(y, u)| [y - u, y + u]));
+    let (min, max) = frame
+        .iter()
+        .copied()
+        .chain(band)
+        .filter(|y| y.is_finite())
+        .fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), y| (min.min(y), max.max(y)));
+    if min > max {
+        return match quantity {
+            Quantity::RawCounts | Quantity::Intensity => 0f64..f64::from(ADC_FULL_SCALE),
+            Quantity::Transmittance | Quantity::Absorbance => 0f64..1f64,
+        };
+    }
+    // Flat line still needs some height to be drawn
+    let margin = if max > min { (max - min) * 0.05 } else { min.abs().max(1.0) * 0.05 };
+    min - margin..max + margin
+}
+
 impl Output {
-    pub fn write_frame(&self, frame: &Frame) -> Result<()> {
+    /// Resolves requested horizontal axis, wavelengths come from calibration stored for CCD
+    pub fn axis(&self, ccd: &mut SerialCCD, store: &Store) -> Result<Option<Axis>> {
+        self.x_axis.map(|x_axis| x_axis.resolve(ccd, store)).transpose()
+    }
+
+    /// Writes a single frame, with uncertainty of every pixel if it was averaged
+    pub fn write_frame(
+        &self,
+        frame: &[f64],
+        uncertainty: Option<&[f64]>,
+        axis: Option<&Axis>,
+        quantity: Quantity,
+    ) -> Result<()> {
         log::debug!("Saving frame to {:?}", self.output);
+        let resampled = match (self.resample, axis) {
+            (Some(grid), Some(axis)) => {
+                let mut columns = vec![frame];
+                columns.extend(uncertainty);
+                Some(axis.resample(&columns, &grid, self.interpolation.into())?)
+            }
+            _ => None,
+        };
+        let (frame, uncertainty, axis) = match &resampled {
+            Some((axis, columns)) => (
+                columns[0].as_slice(),
+                columns.get(1).map(|column| column.as_slice()),
+                Some(axis),
+            ),
+            None => (frame, uncertainty, axis),
+        };
+        let pixel_column = resampled.is_none();
         match self.format {
             OutputFormat::Chart => {
                 let root =
@@ -115,6 +568,9 @@ impl Output {
                     &root,
                     ChartData {
                         frame,
+                        uncertainty,
+                        axis: axis.unwrap_or(&Axis::Pixel),
+                        quantity,
                         idx: 1,
                         timestamp: OffsetDateTime::now_local()?,
                     },
@@ -122,15 +578,45 @@ impl Output {
             }
             OutputFormat::Csv => {
                 let mut out = File::create(self.output.as_path())?;
-                let data = frame_to_csv(frame);
+                let data = match (axis, uncertainty) {
+                    (axis, Some(uncertainty)) => mean_to_csv_table(
+                        frame,
+                        uncertainty,
+                        axis.unwrap_or(&Axis::Pixel),
+                        pixel_column,
+                    ),
+                    (Some(axis), None) => columns_to_csv_table(
+                        vec![String::from("Frame 1")],
+                        &[frame],
+                        axis,
+                        pixel_column,
+                    ),
+                    (None, None) => frame_to_csv(frame),
+                };
                 out.write_all(data.as_bytes())?;
             }
         };
         Ok(())
     }
 
-    pub fn write_frames(&self, frames: &[Frame]) -> Result<()> {
+    pub fn write_frames(
+        &self,
+        frames: &[Spectrum],
+        axis: Option<&Axis>,
+        quantity: Quantity,
+    ) -> Result<()> {
         log::debug!("Saving frames to {:?}", self.output);
+        let resampled = match (self.resample, axis) {
+            (Some(grid), Some(axis)) => {
+                let columns: Vec<_> = frames.iter().map(|frame| frame.as_slice()).collect();
+                Some(axis.resample(&columns, &grid, self.interpolation.into())?)
+            }
+            _ => None,
+        };
+        let (frames, axis) = match &resampled {
+            Some((axis, frames)) => (frames.as_slice(), Some(axis)),
+            None => (frames, axis),
+        };
         match self.format {
             OutputFormat::Chart => {
                 let root = BitMapBackend::gif(self.output.as_path(), (1280, 720), 500)?
@@ -141,6 +627,9 @@ impl Output {
                         &root,
                         ChartData {
                             frame,
+                            uncertainty: None,
+                            axis: axis.unwrap_or(&Axis::Pixel),
+                            quantity,
                             idx: frame_idx + 1,
                             timestamp,
                         },
@@ -149,7 +638,15 @@ impl Output {
             }
             OutputFormat::Csv => {
                 let mut out = File::create(self.output.as_path())?;
-                let data = frames_to_csv(frames);
+                let data = match axis {
+                    Some(axis) if resampled.is_some() => {
+                        let headers = (1..=frames.len()).map(|idx| format!("Frame {idx}")).collect();
+                        let columns: Vec<_> = frames.iter().map(|frame| frame.as_slice()).collect();
+                        columns_to_csv_table(headers, &columns, axis, false)
+                    }
+                    Some(axis) => frames_to_csv_table(frames, axis),
+                    None => frames_to_csv(frames),
+                };
                 out.write_all(data.as_bytes())?;
             }
         };
@@ -160,13 +657,129 @@ impl Output {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use ccd_lcamv06::FRAME_PIXEL_COUNT;
 
     #[test]
     fn convert_frame_to_csv() {
-        let frame: Frame = [1000; FRAME_PIXEL_COUNT];
+        let frame = [1000.0; FRAME_PIXEL_COUNT];
         let csv = frame_to_csv(&frame);
         let csv_fields: Vec<_> = csv.split(",").collect();
         assert_eq!(csv_fields[0], "1000");
     }
+
+    #[test]
+    fn leave_unmeasured_pixels_empty() {
+        let csv = frame_to_csv(&[0.5, f64::NAN, 0.25]);
+        assert_eq!(csv, "0.5,,0.25");
+    }
+
+    #[test]
+    fn quote_csv_fields() {
+        assert_eq!(csv_field("water, %"), "\"water, %\"");
+        assert_eq!(csv_field("a \"b\""), "\"a \"\"b\"\"\"");
+        assert_eq!(csv_field("T1"), "T1");
+        assert_eq!(value_to_csv(f64::INFINITY), "");
+    }
+
+    #[test]
+    fn write_non_finite_json_fields_as_null() {
+        let fields = JsonFields(vec![(String::from("b \"q\""), 1.5), (String::from("a"), f64::NAN)]);
+        assert_eq!(serde_json::to_string(&fields).unwrap(), r#"{"b \"q\"":1.5,"a":null}"#);
+    }
+
+    #[test]
+    fn fit_y_range_to_data() {
+        let close = |range: Range<f64>, expected: Range<f64>| {
+            (range.start - expected.start).abs() < 1e-9 && (range.end - expected.end).abs() < 1e-9
+        };
+        let range = y_range(&[100.0, f64::NAN, 300.0], None, Quantity::Intensity);
+        assert!(close(range, 90.0..310.0));
+        let range = y_range(&[100.0, 300.0], Some(&[10.0, 10.0]), Quantity::Intensity);
+        assert!(close(range, 79.0..321.0));
+        let range = y_range(&[f64::NAN; 3], None, Quantity::Absorbance);
+        assert_eq!(range, 0.0..1.0);
+    }
+
+    #[test]
+    fn interpolate_short_axis() {
+        let axis = Axis::Wavelength(vec![400.0, 401.0]);
+        assert_eq!(axis.interpolate(0.25), 400.25);
+        assert_eq!(Axis::Wavelength(vec![400.0]).interpolate(0.5), 400.0);
+        assert!(Axis::Wavelength(vec![]).interpolate(0.5).is_nan());
+    }
+
+    #[test]
+    fn convert_frames_to_csv_table() {
+        let frames = vec![vec![1000.0; FRAME_PIXEL_COUNT], vec![2000.5; FRAME_PIXEL_COUNT]];
+        let wavelengths = (0..FRAME_PIXEL_COUNT).map(|p| 400.0 + p as f64 * 0.1).collect();
+        let csv = frames_to_csv_table(&frames, &Axis::Wavelength(wavelengths));
+        let lines: Vec<_> = csv.lines().collect();
+        assert_eq!(lines.len(), FRAME_PIXEL_COUNT + 1);
+        assert_eq!(lines[0], "Pixel #,Wavelength (nm),Frame 1,Frame 2");
+        assert_eq!(lines[11], "10,401.000,1000,2000.5");
+    }
+
+    #[test]
+    fn write_raman_shift_column() {
+        let frames = vec![vec![1000.0; FRAME_PIXEL_COUNT]];
+        let shifts = (0..FRAME_PIXEL_COUNT).map(|p| p as f64 * 2.5 - 100.0).collect();
+        let csv = frames_to_csv_table(&frames, &Axis::RamanShift(shifts));
+        let lines: Vec<_> = csv.lines().collect();
+        assert_eq!(lines[0], "Pixel #,Raman shift (cm⁻¹),Frame 1");
+        assert_eq!(lines[1], "0,-100.000,1000");
+    }
+
+    #[test]
+    fn convert_mean_to_csv_table() {
+        let csv = mean_to_csv_table(
+            &[1000.5; FRAME_PIXEL_COUNT],
+            &[2.0; FRAME_PIXEL_COUNT],
+            &Axis::Pixel,
+            true,
+        );
+        let lines: Vec<_> = csv.lines().collect();
+        assert_eq!(lines[0], "Pixel #,Mean,Uncertainty");
+        assert_eq!(lines[1], "0,1000.5,2");
+    }
+
+    #[test]
+    fn read_back_both_csv_layouts() {
+        let frames = vec![vec![1000.0; FRAME_PIXEL_COUNT], vec![f64::NAN; FRAME_PIXEL_COUNT]];
+        let wavelengths = (0..FRAME_PIXEL_COUNT).map(|p| 400.0 + p as f64 * 0.5).collect();
+        let saved = csv_to_spectra(&frames_to_csv_table(&frames, &Axis::Wavelength(wavelengths))).unwrap();
+        assert!(matches!(saved.axis, Axis::Wavelength(_)));
+        assert_eq!(saved.axis.value(10), 405.0);
+        assert_eq!(saved.columns.len(), 2);
+        assert_eq!(saved.columns[0].0, "Frame 1");
+        assert_eq!(saved.columns[0].1[5], 1000.0);
+        assert!(saved.columns[1].1[5].is_nan());
+
+        let saved = csv_to_spectra(&frames_to_csv(&frames)).unwrap();
+        assert!(matches!(saved.axis, Axis::Pixel));
+        assert_eq!(saved.columns.len(), 2);
+        assert_eq!(saved.columns[0].1.len(), FRAME_PIXEL_COUNT);
+    }
+
+    #[test]
+    fn resample_onto_grid_without_pixel_column() {
+        let wavelengths: Vec<f64> = (0..FRAME_PIXEL_COUNT).map(|p| 400.0 + p as f64 * 0.1).collect();
+        let frame: Vec<f64> = wavelengths.iter().map(|w| w * 2.0).collect();
+        let saved = SavedSpectra {
+            axis: Axis::Wavelength(wavelengths),
+            columns: vec![(String::from("Frame 1"), frame)],
+        };
+        let grid: Grid = "390:500:0.5".parse().unwrap();
+        let resampled = saved.resample(&grid, Resampling::Linear).unwrap();
+        let columns: Vec<_> = resampled.columns.iter().map(|(_, c)| c.as_slice()).collect();
+        let csv = columns_to_csv_table(vec![String::from("Frame 1")], &columns, &resampled.axis, false);
+        let lines: Vec<_> = csv.lines().collect();
+        assert_eq!(lines.len(), grid.len() + 1);
+        assert_eq!(lines[0], "Wavelength (nm),Frame 1");
+        // Below measured range
+        assert_eq!(lines[1], "390.000,");
+        assert_eq!(lines[42], "410.500,821");
+
+        let read_back = csv_to_spectra(&csv).unwrap();
+        assert_eq!(read_back.axis.value(41), 410.5);
+        assert_eq!(read_back.columns[0].1[41], 821.0);
+    }
 }
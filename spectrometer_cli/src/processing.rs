@@ -0,0 +1,209 @@
+use crate::{output::Spectrum, serial::SerialCCD};
+use ccd_lcamv06::Frame;
+use clap::{ArgEnum, Args};
+use simple_eyre::{eyre::eyre, Result};
+use spectrometer_processing::{
+    absorbance::AbsorbanceOptions,
+    filter::{apply_chain, Edge},
+    intensity::intensity,
+    AbsorbanceMeasurement, Baseline, DarkFrame, DefectMap, Despike, Filter, Store,
+};
+
+#[derive(Args)]
+pub struct ProcessingConf {
+    /// Subtract dark frame stored for CCD at its current exposure time
+    #[clap(long)]
+    pub dark: bool,
+
+    /// Quantity computed for every pixel. Transmittance and absorbance require dark frame and
+    /// reference spectrum stored at current exposure time, pixels that can't be measured are left empty
+    #[clap(long, value_enum, default_value_t)]
+    pub measure: Measure,
+
+    /// Leave pixels from stored defect map as they are, instead of interpolating over them
+    #[clap(long)]
+    pub keep_defects: bool,
+
+    /// Replace spikes in raw frames before other processing. Batch methods need at least 3
+    /// frames of the same scene, one of: median:THRESHOLD, sigma:THRESHOLD. Works on single
+    /// frames: laplacian:THRESHOLD. Thresholds are in standard deviations
+    #[clap(long, value_parser)]
+    pub despike: Option<Despike>,
+
+    /// Keep raw CCD output for intensity, which is inverted: more light gives lower values
+    #[clap(long)]
+    pub raw: bool,
+
+    /// Remove estimated baseline before filtering. One of: als:LAMBDA:P[:ITERATIONS],
+    /// poly:ORDER[:ITERATIONS], ball:RADIUS
+    #[clap(long, value_parser)]
+    pub baseline: Option<Baseline>,
+
+    /// Filter applied after other processing, repeat to chain filters in given order. One of:
+    /// boxcar:WINDOW, gaussian:SIGMA, median:WINDOW, sg:WINDOW:ORDER[:DERIVATIVE]
+    #[clap(long = "filter", value_parser)]
+    pub filters: Vec<Filter>,
+
+    /// Way filters get samples past the ends of spectrum
+    #[clap(long, value_enum, default_value_t)]
+    pub filter_edge: FilterEdge,
+}
+
+#[derive(ArgEnum, Clone, Copy, Default)]
+pub enum FilterEdge {
+    /// Mirrored around the edge pixel
+    #[default]
+    Reflect,
+    /// Repeated edge pixel
+    Nearest,
+    Zero,
+}
+
+#[derive(ArgEnum, Clone, Copy, Default)]
+pub enum Measure {
+    #[default]
+    Intensity,
+    Transmittance,
+    Absorbance,
+}
+
+/// What values of processed spectrum represent
+#[derive(Clone, Copy)]
+pub enum Quantity {
+    RawCounts,
+    Intensity,
+    Transmittance,
+    Absorbance,
+}
+
+impl Quantity {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Quantity::RawCounts => "Inverse intensity",
+            Quantity::Intensity => "Intensity, counts",
+            Quantity::Transmittance => "Transmittance",
+            Quantity::Absorbance => "Absorbance, AU",
+        }
+    }
+}
+
+/// Processing steps with data resolved for a specific CCD
+pub struct Pipeline {
+    measure: Measure,
+    raw: bool,
+    despike: Option<Despike>,
+    dark: Option<DarkFrame>,
+    defects: Option<DefectMap>,
+    baseline: Option<Baseline>,
+    filters: Vec<Filter>,
+    absorbance: Option<AbsorbanceMeasurement>,
+}
+
+impl ProcessingConf {
+    pub fn pipeline(&self, ccd: &mut SerialCCD, store: &Store) -> Result<Pipeline> {
+        let mut pipeline = Pipeline {
+            measure: self.measure,
+            raw: self.raw,
+            despike: self.despike,
+            dark: None,
+            defects: None,
+            baseline: self.baseline.clone(),
+            filters: self
+                .filters
+                .iter()
+                .map(|filter| {
+                    filter.clone().with_edge(match self.filter_edge {
+                        FilterEdge::Reflect => Edge::Reflect,
+                        FilterEdge::Nearest => Edge::Nearest,
+                        FilterEdge::Zero => Edge::Zero,
+                    })
+                })
+                .collect(),
+            absorbance: None,
+        };
+        let needs_reference = !matches!(self.measure, Measure::Intensity);
+        if !self.dark && !needs_reference && self.keep_defects {
+            return Ok(pipeline);
+        }
+
+        let version = ccd.get_version()?;
+        let serial = version.serial_number();
+        if !self.keep_defects {
+            pipeline.defects = store.defect_map(serial)?.filter(|defects| !defects.is_empty());
+        }
+        if !self.dark && !needs_reference {
+            return Ok(pipeline);
+        }
+        // Reading exposure time also makes CCD attach it to captured frames
+        let exposure = ccd.get_exp_time()?;
+        let dark = store.dark_frame(serial, exposure)?.ok_or_else(|| {
+            eyre!("No dark frame stored for CCD {serial} at {exposure}, capture one with `dark capture`")
+        })?;
+        if needs_reference {
+            let reference = store.reference(serial, exposure)?.ok_or_else(|| {
+                eyre!("No reference spectrum stored for CCD {serial} at {exposure}, capture one with `reference capture`")
+            })?;
+            pipeline.absorbance = Some(AbsorbanceMeasurement::new(
+                dark,
+                reference,
+                AbsorbanceOptions::default(),
+            )?);
+        } else {
+            pipeline.dark = Some(dark);
+        }
+        Ok(pipeline)
+    }
+}
+
+impl Pipeline {
+    pub fn quantity(&self) -> Quantity {
+        match self.measure {
+            Measure::Intensity if self.raw => Quantity::RawCounts,
+            Measure::Intensity => Quantity::Intensity,
+            Measure::Transmittance => Quantity::Transmittance,
+            Measure::Absorbance => Quantity::Absorbance,
+        }
+    }
+
+    /// Replaces spikes in a batch of frames in place, returns amount of replaced pixels
+    pub fn despike(&self, frames: &mut [Frame]) -> Result<usize> {
+        match &self.despike {
+            Some(despike) => Ok(despike.apply(frames)?),
+            None => Ok(0),
+        }
+    }
+
+    /// Removes baseline step, so that caller can apply it elsewhere
+    pub fn take_baseline(&mut self) -> Option<Baseline> {
+        self.baseline.take()
+    }
+
+    /// Defective pixels that get interpolated over
+    pub fn defects(&self) -> Option<&DefectMap> {
+        self.defects.as_ref()
+    }
+
+    pub fn apply(&self, frame: &Frame) -> Result<Spectrum> {
+        let mut spectrum = match (&self.absorbance, &self.dark, self.raw) {
+            (Some(absorbance), _, _) => match self.measure {
+                Measure::Absorbance => absorbance.absorbance(frame)?,
+                _ => absorbance.transmittance(frame)?,
+            },
+            (None, Some(dark), true) => dark.subtract(frame),
+            (None, Some(dark), false) => dark.intensity(frame),
+            (None, None, true) => frame.iter().map(|&pixel| f64::from(pixel)).collect(),
+            (None, None, false) => intensity(frame),
+        };
+        if let Some(defects) = &self.defects {
+            defects.correct(&mut spectrum);
+        }
+        if let Some(baseline) = &self.baseline {
+            baseline.remove(&mut spectrum)?;
+        }
+        if !self.filters.is_empty() {
+            let mut scratch = vec![0.0; spectrum.len()];
+            apply_chain(&self.filters, &mut spectrum, &mut scratch);
+        }
+        Ok(spectrum)
+    }
+}
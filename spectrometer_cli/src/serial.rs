@@ -1,28 +1,25 @@
-use ccd_lcamv06::{BaudRate, CCD, StdIoAdapter, IoAdapter};
+use crate::cli::parse_baud_rate;
+use ccd_lcamv06::{BaudRate, Link, LinkAddress, CCD};
 use clap::Args;
-use num_traits::ToPrimitive;
-use serialport::SerialPort;
 use simple_eyre::{eyre::eyre, Result};
-use std::time::Duration;
 
 #[derive(Args)]
 pub struct SerialConf {
-    /// Name of serial port that should be used
+    /// Name of serial port that should be used. Network bridges are accepted as tcp://host:port,
+    /// or as rfc2217://host:port to also configure baud rate of the remote port
     #[clap(short, long, value_parser)]
     pub serial: String,
+    /// Baud rate of serial port. For rfc2217:// bridges it is set on the remote port, tcp://
+    /// bridges keep their own setting
+    #[clap(long, value_parser = parse_baud_rate, default_value = "115200")]
+    pub baud: BaudRate,
 }
 
-pub type SerialCCD = CCD<StdIoAdapter<Box<dyn SerialPort>>>;
+pub type SerialCCD = CCD<Link>;
 
 impl SerialConf {
     pub fn open_ccd(&self) -> Result<SerialCCD> {
-        let port = serialport::new(
-            &self.serial,
-            BaudRate::default().to_u32().unwrap(),
-        )
-        .timeout(Duration::from_millis(100))
-        .open()
-        .map_err(|_| eyre!("Could not open serial port"))?;
-        Ok(StdIoAdapter::new(port).open_ccd())
+        let address = LinkAddress::from(self.serial.as_str());
+        Link::connect(&address, self.baud).map_err(|err| eyre!("Could not open {address}: {err}"))
     }
 }
@@ -0,0 +1,219 @@
+//! Transmittance and absorbance of a sample relative to a blank reference
+
+use crate::{
+    dark::{average_pixels, DarkFrame},
+    error::{Error, Result},
+};
+use ccd_lcamv06::{ExposureTime, Frame, SATURATION_LEVEL};
+use serde::{Deserialize, Serialize};
+
+/// Average of raw frames taken through a blank, everything in the light path except analyte
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ReferenceSpectrum {
+    exposure: ExposureTime,
+    frame_count: usize,
+    pixels: Vec<f64>,
+    /// Active pixels that were saturated or clipped in any of averaged frames
+    #[serde(default)]
+    clipped: Vec<usize>,
+}
+
+impl ReferenceSpectrum {
+    /// Averages frames, which all have to be taken with the same known exposure time
+    pub fn average(frames: &[Frame]) -> Result<ReferenceSpectrum> {
+        let (exposure, pixels, clipped) = average_pixels(frames)?;
+        Ok(ReferenceSpectrum {
+            exposure,
+            frame_count: frames.len(),
+            pixels,
+            clipped,
+        })
+    }
+
+    pub fn exposure(&self) -> ExposureTime {
+        self.exposure
+    }
+
+    /// Amount of frames that were averaged
+    pub fn frame_count(&self) -> usize {
+        self.frame_count
+    }
+
+    pub fn pixels(&self) -> &[f64] {
+        &self.pixels
+    }
+
+    /// Active pixels that were saturated or clipped in any of averaged frames, they can't be
+    /// measured against this reference
+    pub fn clipped(&self) -> &[usize] {
+        &self.clipped
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct AbsorbanceOptions {
+    /// Minimum difference between reference and dark in ADC counts, pixels that get less light
+    /// through a blank would only amplify noise
+    pub min_signal: f64,
+    /// Raw value at or below which a pixel is saturated. CCD output is inverted, so more light
+    /// means lower values
+    pub saturation_level: f64,
+}
+
+impl Default for AbsorbanceOptions {
+    fn default() -> Self {
+        AbsorbanceOptions {
+            min_signal: 10.0,
+            saturation_level: f64::from(SATURATION_LEVEL),
+        }
+    }
+}
+
+/// Dark and reference spectra taken with the same exposure time, ready to be applied to samples.
+/// Pixels that can't be measured, either saturated or without light through reference, are NaN
+#[derive(Clone, Debug)]
+pub struct AbsorbanceMeasurement {
+    dark: DarkFrame,
+    reference: ReferenceSpectrum,
+    options: AbsorbanceOptions,
+}
+
+impl AbsorbanceMeasurement {
+    pub fn new(
+        dark: DarkFrame,
+        reference: ReferenceSpectrum,
+        options: AbsorbanceOptions,
+    ) -> Result<AbsorbanceMeasurement> {
+        if dark.exposure() != reference.exposure() {
+            return Err(Error::ExposureMismatch {
+                expected: reference.exposure(),
+                got: dark.exposure(),
+            });
+        }
+        Ok(AbsorbanceMeasurement {
+            dark,
+            reference,
+            options,
+        })
+    }
+
+    /// Fraction of reference light that passed through sample, `(S - D) / (R - D)`. Frames with
+    /// unknown exposure time are assumed to match reference
+    pub fn transmittance(&self, sample: &Frame) -> Result<Vec<f64>> {
+        match sample.exposure() {
+            Some(exposure) if exposure != self.reference.exposure() => {
+                return Err(Error::ExposureMismatch {
+                    expected: self.reference.exposure(),
+                    got: exposure,
+                })
+            }
+            _ => {}
+        }
+        let saturation = self.options.saturation_level;
+        let mut clipped = vec![false; sample.len()];
+        for &pixel in self.dark.clipped().iter().chain(self.reference.clipped()) {
+            clipped[pixel] = true;
+        }
+        Ok(sample
+            .iter()
+            .zip(self.dark.pixels())
+            .zip(self.reference.pixels())
+            .zip(clipped)
+            .map(|(((&sample, &dark), &reference), clipped)| {
+                let sample = f64::from(sample);
+                // Inverted output, light lowers values below dark level
+                let signal = dark - reference;
+                if clipped
+                    || signal < self.options.min_signal
+                    || sample <= saturation
+                    || reference <= saturation
+                {
+                    f64::NAN
+                } else {
+                    (dark - sample) / signal
+                }
+            })
+            .collect())
+    }
+
+    /// Absorbance in absorbance units, `-log10(T)`
+    pub fn absorbance(&self, sample: &Frame) -> Result<Vec<f64>> {
+        Ok(self
+            .transmittance(sample)?
+            .into_iter()
+            .map(absorbance)
+            .collect())
+    }
+}
+
+/// Converts transmittance to absorbance. Transmittance that dropped to zero or below due to
+/// noise has no finite absorbance, so it is NaN
+pub fn absorbance(transmittance: f64) -> f64 {
+    if transmittance > 0.0 {
+        -transmittance.log10()
+    } else {
+        f64::NAN
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::frame;
+    use claims::{assert_matches, assert_ok};
+
+    fn measurement(dark: u16, reference: u16) -> AbsorbanceMeasurement {
+        assert_ok!(AbsorbanceMeasurement::new(
+            assert_ok!(DarkFrame::average(&[frame(dark, 20)])),
+            assert_ok!(ReferenceSpectrum::average(&[frame(reference, 20)])),
+            AbsorbanceOptions::default(),
+        ))
+    }
+
+    #[test]
+    fn compute_transmittance_and_absorbance() {
+        let m = measurement(3000, 1000);
+        let t = assert_ok!(m.transmittance(&frame(2800, 20)));
+        assert!((t[0] - 0.1).abs() < 1e-12);
+        let a = assert_ok!(m.absorbance(&frame(2800, 20)));
+        assert!((a[0] - 1.0).abs() < 1e-12);
+        assert!(absorbance(-0.01).is_nan());
+    }
+
+    #[test]
+    fn mask_unmeasurable_pixels() {
+        // No light through reference, would divide by zero
+        let t = assert_ok!(measurement(3000, 3000).transmittance(&frame(2000, 20)));
+        assert!(t[0].is_nan());
+        // Saturated sample
+        let t = assert_ok!(measurement(3000, 1000).transmittance(&frame(0, 20)));
+        assert!(t[0].is_nan());
+        // Reference pixel saturated in one of averaged frames
+        let mut saturated = frame(1000, 20);
+        saturated[100] = 0;
+        let m = assert_ok!(AbsorbanceMeasurement::new(
+            assert_ok!(DarkFrame::average(&[frame(3000, 20)])),
+            assert_ok!(ReferenceSpectrum::average(&[frame(1000, 20), saturated])),
+            AbsorbanceOptions::default(),
+        ));
+        let t = assert_ok!(m.transmittance(&frame(2800, 20)));
+        assert!(t[100].is_nan());
+        assert!(!t[101].is_nan());
+    }
+
+    #[test]
+    fn reject_mismatched_exposure() {
+        assert_matches!(
+            measurement(3000, 1000).transmittance(&frame(2000, 30)),
+            Err(Error::ExposureMismatch { .. })
+        );
+        assert_matches!(
+            AbsorbanceMeasurement::new(
+                assert_ok!(DarkFrame::average(&[frame(3000, 30)])),
+                assert_ok!(ReferenceSpectrum::average(&[frame(1000, 20)])),
+                AbsorbanceOptions::default(),
+            ),
+            Err(Error::ExposureMismatch { .. })
+        );
+    }
+}
@@ -0,0 +1,199 @@
+use crate::error::{Error, Result};
+use core::fmt::{self, Display};
+use core::str::FromStr;
+use serde::{Deserialize, Serialize};
+
+/// Span of horizontal axis values, in any order and with both ends included
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Band {
+    pub start: f64,
+    pub end: f64,
+}
+
+impl Band {
+    pub fn contains(&self, x: f64) -> bool {
+        self.start.min(self.end) <= x && x <= self.start.max(self.end)
+    }
+
+    /// Axis values and data of measured pixels within band
+    fn select<'a>(&'a self, x: &'a [f64], y: &'a [f64]) -> impl Iterator<Item = (f64, f64)> + 'a {
+        x.iter()
+            .zip(y)
+            .filter(move |(x, y)| self.contains(**x) && !y.is_nan())
+            .map(|(x, y)| (*x, *y))
+    }
+
+    fn sum(&self, x: &[f64], y: &[f64]) -> f64 {
+        let mut values = self.select(x, y).map(|(_, y)| y).peekable();
+        if values.peek().is_none() {
+            return f64::NAN;
+        }
+        values.sum()
+    }
+}
+
+impl FromStr for Band {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Band> {
+        let invalid = || Error::InvalidBand(format!("Expected band as START:END, got {s:?}"));
+        let (start, end) = s.split_once(':').ok_or_else(invalid)?;
+        let start: f64 = start.trim().parse().map_err(|_| invalid())?;
+        let end: f64 = end.trim().parse().map_err(|_| invalid())?;
+        if !(start.is_finite() && end.is_finite()) {
+            return Err(invalid());
+        }
+        Ok(Band { start, end })
+    }
+}
+
+impl Display for Band {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.start, self.end)
+    }
+}
+
+/// Single number computed from a band of a spectrum, to follow a process over time. Pixels
+/// without a value are left out, and a band without any pixels gives NaN
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum BandMetric {
+    /// Sum of pixel values
+    Sum(Band),
+    Mean(Band),
+    /// Highest pixel value
+    Peak(Band),
+    /// Mean axis value weighted by pixel values, tracks position of a band
+    Centroid(Band),
+    /// Sum of the first band divided by sum of the second one
+    Ratio(Band, Band),
+}
+
+impl BandMetric {
+    /// Computes metric from data with axis value of every pixel in x
+    pub fn evaluate(&self, x: &[f64], y: &[f64]) -> f64 {
+        match self {
+            BandMetric::Sum(band) => band.sum(x, y),
+            BandMetric::Mean(band) => {
+                let (sum, count) = band
+                    .select(x, y)
+                    .fold((0.0, 0), |(sum, count), (_, y)| (sum + y, count + 1));
+                sum / f64::from(count)
+            }
+            BandMetric::Peak(band) => band
+                .select(x, y)
+                .map(|(_, y)| y)
+                .reduce(f64::max)
+                .unwrap_or(f64::NAN),
+            BandMetric::Centroid(band) => {
+                let (moment, sum) = band.select(x, y).fold((0.0, 0.0), |(moment, sum), (x, y)| {
+                    (moment + x * y, sum + y)
+                });
+                moment / sum
+            }
+            BandMetric::Ratio(numerator, denominator) => {
+                numerator.sum(x, y) / denominator.sum(x, y)
+            }
+        }
+    }
+}
+
+/// Parses metrics written as `sum:START:END`, `mean:START:END`, `peak:START:END`,
+/// `centroid:START:END` or `ratio:START:END/START:END`
+impl FromStr for BandMetric {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<BandMetric> {
+        let (name, bands) = s
+            .trim()
+            .split_once(':')
+            .ok_or_else(|| Error::InvalidBand(format!("Unknown band metric {s:?}")))?;
+        match name {
+            "sum" => Ok(BandMetric::Sum(bands.parse()?)),
+            "mean" => Ok(BandMetric::Mean(bands.parse()?)),
+            "peak" => Ok(BandMetric::Peak(bands.parse()?)),
+            "centroid" => Ok(BandMetric::Centroid(bands.parse()?)),
+            "ratio" => {
+                let (numerator, denominator) = bands.split_once('/').ok_or_else(|| {
+                    Error::InvalidBand(format!("Expected ratio as START:END/START:END, got {s:?}"))
+                })?;
+                Ok(BandMetric::Ratio(numerator.parse()?, denominator.parse()?))
+            }
+            _ => Err(Error::InvalidBand(format!("Unknown band metric {s:?}"))),
+        }
+    }
+}
+
+/// Written the same way it is parsed, so it can serve as a column name
+impl Display for BandMetric {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BandMetric::Sum(band) => write!(f, "sum:{band}"),
+            BandMetric::Mean(band) => write!(f, "mean:{band}"),
+            BandMetric::Peak(band) => write!(f, "peak:{band}"),
+            BandMetric::Centroid(band) => write!(f, "centroid:{band}"),
+            BandMetric::Ratio(numerator, denominator) => {
+                write!(f, "ratio:{numerator}/{denominator}")
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use claims::{assert_err, assert_ok};
+
+    fn spectrum() -> (Vec<f64>, Vec<f64>) {
+        let x: Vec<f64> = (0..20).map(|p| 500.0 + p as f64 * 5.0).collect();
+        let y = x
+            .iter()
+            .map(|&x| {
+                if (520.0..=540.0).contains(&x) {
+                    10.0
+                } else {
+                    1.0
+                }
+            })
+            .collect();
+        (x, y)
+    }
+
+    #[test]
+    fn evaluate_metrics() {
+        let (x, mut y) = spectrum();
+        // Peak at 530 nm, skewed towards 535 nm
+        y[6] = 30.0;
+        y[7] = 20.0;
+        let eval = |s: &str| assert_ok!(s.parse::<BandMetric>()).evaluate(&x, &y);
+        assert_eq!(eval("sum:520:540"), 10.0 * 3.0 + 30.0 + 20.0);
+        assert_eq!(eval("mean:540:520"), 80.0 / 5.0);
+        assert_eq!(eval("peak:500:595"), 30.0);
+        assert!(
+            (eval("centroid:525:535") - (525.0 * 10.0 + 530.0 * 30.0 + 535.0 * 20.0) / 60.0).abs()
+                < 1e-9
+        );
+        assert_eq!(eval("ratio:520:540/550:560"), 80.0 / 3.0);
+        assert!(eval("sum:0:100").is_nan());
+    }
+
+    #[test]
+    fn skip_unmeasured_pixels() {
+        let (x, mut y) = spectrum();
+        y[4] = f64::NAN;
+        let metric = BandMetric::Mean(Band {
+            start: 520.0,
+            end: 540.0,
+        });
+        assert_eq!(metric.evaluate(&x, &y), 10.0);
+    }
+
+    #[test]
+    fn parse_and_display_metrics() {
+        for s in ["sum:520:540", "centroid:1.5:-2", "ratio:520:540/600:620.5"] {
+            assert_eq!(assert_ok!(s.parse::<BandMetric>()).to_string(), s);
+        }
+        assert_err!("sum:520".parse::<BandMetric>());
+        assert_err!("ratio:520:540".parse::<BandMetric>());
+        assert_err!("median:1:2".parse::<BandMetric>());
+    }
+}
@@ -0,0 +1,384 @@
+//! Estimation of slowly varying background, like fluorescence or stray light, under spectral
+//! features. Pixels without a value (NaN) don't affect estimated baseline
+
+use crate::{
+    defects::interpolate_over,
+    error::{Error, Result},
+    polynomial::Polynomial,
+};
+use core::str::FromStr;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Baseline {
+    /// Asymmetric least squares smoothing. Larger `lambda` gives stiffer baseline, `p` is the
+    /// weight of points above it, usually 0.001 to 0.1
+    Als {
+        lambda: f64,
+        p: f64,
+        iterations: usize,
+    },
+    /// Polynomial refitted after clipping points above previous fit down to it
+    Polynomial { order: usize, iterations: usize },
+    /// Minimum then maximum over a window of `2 * radius + 1` pixels, smoothed with moving
+    /// average over the same window. Peaks narrower than the window are cut off
+    RollingBall { radius: usize },
+}
+
+impl Baseline {
+    /// Estimated baseline with a value per pixel of `data`
+    pub fn estimate(&self, data: &[f64]) -> Result<Vec<f64>> {
+        match *self {
+            Baseline::Als {
+                lambda,
+                p,
+                iterations,
+            } => als(data, lambda, p, iterations),
+            Baseline::Polynomial { order, iterations } => polynomial(data, order, iterations),
+            Baseline::RollingBall { radius } => Ok(rolling_ball(data, radius)),
+        }
+    }
+
+    /// Subtracts estimated baseline from data
+    pub fn remove(&self, data: &mut [f64]) -> Result<()> {
+        let baseline = self.estimate(data)?;
+        data.iter_mut().zip(baseline).for_each(|(y, b)| *y -= b);
+        Ok(())
+    }
+}
+
+/// Parses baselines written as `als:LAMBDA:P[:ITERATIONS]`, `poly:ORDER[:ITERATIONS]` or
+/// `ball:RADIUS`
+impl FromStr for Baseline {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Baseline> {
+        let invalid = || Error::InvalidBaseline(format!("Unknown baseline {s:?}"));
+        let mut parts = s.trim().split(':');
+        let name = parts.next().unwrap_or_default();
+        let params = parts
+            .map(|p| p.trim().parse::<f64>().map_err(|_| invalid()))
+            .collect::<Result<Vec<_>>>()?;
+        let whole = |value: f64| {
+            if value >= 0.0 && value.fract() == 0.0 {
+                Ok(value as usize)
+            } else {
+                Err(invalid())
+            }
+        };
+        let baseline = match (name, params.as_slice()) {
+            ("als", &[lambda, p]) => Baseline::Als {
+                lambda,
+                p,
+                iterations: 10,
+            },
+            ("als", &[lambda, p, iterations]) => Baseline::Als {
+                lambda,
+                p,
+                iterations: whole(iterations)?,
+            },
+            ("poly", &[order]) => Baseline::Polynomial {
+                order: whole(order)?,
+                iterations: 100,
+            },
+            ("poly", &[order, iterations]) => Baseline::Polynomial {
+                order: whole(order)?,
+                iterations: whole(iterations)?,
+            },
+            ("ball", &[radius]) => Baseline::RollingBall {
+                radius: whole(radius)?,
+            },
+            _ => return Err(invalid()),
+        };
+        match baseline {
+            Baseline::Als { lambda, p, .. } if !(lambda > 0.0 && p > 0.0 && p < 1.0) => Err(
+                Error::InvalidBaseline("ALS needs positive lambda and p between 0 and 1".into()),
+            ),
+            baseline => Ok(baseline),
+        }
+    }
+}
+
+/// Eilers and Boelens, minimizes `sum(w * (y - z)^2) + lambda * sum((second difference of z)^2)`
+/// with weights `p` above baseline and `1 - p` below it
+fn als(data: &[f64], lambda: f64, p: f64, iterations: usize) -> Result<Vec<f64>> {
+    let n = data.len();
+    if n < 3 {
+        return Ok(data.to_vec());
+    }
+    // Bands of lambda * D'D, where D takes second differences: diagonal, first and second
+    // sub-diagonals
+    let mut penalty = vec![[0.0; 3]; n];
+    for k in 0..n - 2 {
+        let d = [1.0, -2.0, 1.0];
+        for a in 0..3 {
+            for b in 0..=a {
+                penalty[k + a][a - b] += lambda * d[a] * d[b];
+            }
+        }
+    }
+
+    let mut weights: Vec<f64> = data
+        .iter()
+        .map(|y| if y.is_nan() { 0.0 } else { 1.0 })
+        .collect();
+    let mut baseline = vec![0.0; n];
+    for _ in 0..iterations.max(1) {
+        let mut bands = penalty.clone();
+        let mut rhs = vec![0.0; n];
+        for i in 0..n {
+            bands[i][0] += weights[i];
+            rhs[i] = if weights[i] > 0.0 {
+                weights[i] * data[i]
+            } else {
+                0.0
+            };
+        }
+        baseline = solve_pentadiagonal(bands, rhs)?;
+        let updated: Vec<f64> = data
+            .iter()
+            .zip(&baseline)
+            .map(|(y, z)| match y.partial_cmp(z) {
+                None => 0.0,
+                Some(core::cmp::Ordering::Greater) => p,
+                Some(_) => 1.0 - p,
+            })
+            .collect();
+        if updated == weights {
+            break;
+        }
+        weights = updated;
+    }
+    Ok(baseline)
+}
+
+/// Solves symmetric positive definite system given by bands `[a(i, i), a(i, i - 1),
+/// a(i, i - 2)]` of every row, with banded Cholesky decomposition
+fn solve_pentadiagonal(bands: Vec<[f64; 3]>, mut rhs: Vec<f64>) -> Result<Vec<f64>> {
+    let n = bands.len();
+    let a = |i: usize, j: usize| bands[i][i - j];
+    // Rows of lower triangular factor, same layout as bands
+    let mut l = vec![[0.0; 3]; n];
+    for i in 0..n {
+        for j in i.saturating_sub(2)..=i {
+            let mut sum = a(i, j);
+            for k in i.saturating_sub(2)..j {
+                if j - k <= 2 {
+                    sum -= l[i][i - k] * l[j][j - k];
+                }
+            }
+            if i == j {
+                if sum <= 0.0 {
+                    return Err(Error::SingularSystem);
+                }
+                l[i][0] = sum.sqrt();
+            } else {
+                l[i][i - j] = sum / l[j][0];
+            }
+        }
+    }
+    for i in 0..n {
+        for k in i.saturating_sub(2)..i {
+            rhs[i] -= l[i][i - k] * rhs[k];
+        }
+        rhs[i] /= l[i][0];
+    }
+    for i in (0..n).rev() {
+        for k in i + 1..n.min(i + 3) {
+            rhs[i] -= l[k][k - i] * rhs[k];
+        }
+        rhs[i] /= l[i][0];
+    }
+    Ok(rhs)
+}
+
+fn polynomial(data: &[f64], order: usize, iterations: usize) -> Result<Vec<f64>> {
+    let (xs, mut ys): (Vec<f64>, Vec<f64>) = data
+        .iter()
+        .enumerate()
+        .filter(|(_, y)| !y.is_nan())
+        .map(|(x, &y)| (x as f64, y))
+        .unzip();
+    let mut fit = Polynomial::fit(&xs, &ys, order)?;
+    for _ in 0..iterations {
+        let mut changed = false;
+        for (x, y) in xs.iter().zip(ys.iter_mut()) {
+            let value = fit.eval(*x);
+            if *y > value {
+                *y = value;
+                changed = true;
+            }
+        }
+        if !changed {
+            break;
+        }
+        fit = Polynomial::fit(&xs, &ys, order)?;
+    }
+    Ok((0..data.len()).map(|x| fit.eval(x as f64)).collect())
+}
+
+/// Skips NaN in every pass. Windows without any value are filled in by interpolating between
+/// neighbouring estimates, so that gaps don't spread into real data
+fn rolling_ball(data: &[f64], radius: usize) -> Vec<f64> {
+    let window = |idx: usize| idx.saturating_sub(radius)..(idx + radius + 1).min(data.len());
+    let pass = |values: &[f64], reduce: fn(&[f64]) -> f64| -> Vec<f64> {
+        (0..values.len())
+            .map(|idx| {
+                let known: Vec<f64> = values[window(idx)]
+                    .iter()
+                    .copied()
+                    .filter(|y| !y.is_nan())
+                    .collect();
+                if known.is_empty() {
+                    f64::NAN
+                } else {
+                    reduce(&known)
+                }
+            })
+            .collect()
+    };
+    let eroded = pass(data, |values| {
+        values.iter().copied().fold(f64::INFINITY, f64::min)
+    });
+    let opened = pass(&eroded, |values| {
+        values.iter().copied().fold(f64::NEG_INFINITY, f64::max)
+    });
+    let mut smoothed = pass(&opened, |values| {
+        values.iter().sum::<f64>() / values.len() as f64
+    });
+    let missing: Vec<bool> = smoothed.iter().map(|y| y.is_nan()).collect();
+    interpolate_over(&mut smoothed, &missing);
+    smoothed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use claims::{assert_err, assert_ok, assert_ok_eq};
+
+    /// Sloping background with two narrow peaks
+    fn spectrum() -> (Vec<f64>, Vec<f64>) {
+        let background: Vec<f64> = (0..500)
+            .map(|x| 100.0 + 0.2 * x as f64 + 1e-4 * (x as f64).powi(2))
+            .collect();
+        let data = background
+            .iter()
+            .enumerate()
+            .map(|(x, b)| {
+                let x = x as f64;
+                b + 500.0 * (-(x - 150.0).powi(2) / 20.0).exp()
+                    + 300.0 * (-(x - 350.0).powi(2) / 30.0).exp()
+            })
+            .collect();
+        (data, background)
+    }
+
+    fn max_error(estimate: &[f64], truth: &[f64]) -> f64 {
+        estimate
+            .iter()
+            .zip(truth)
+            .fold(0.0, |acc: f64, (e, t)| acc.max((e - t).abs()))
+    }
+
+    #[test]
+    fn solve_banded_system() {
+        // Tridiagonal [2 -1; -1 2 -1; -1 2] with zero second band
+        let bands = vec![[2.0, 0.0, 0.0], [2.0, -1.0, 0.0], [2.0, -1.0, 0.0]];
+        let x = assert_ok!(solve_pentadiagonal(bands, vec![1.0, 0.0, 1.0]));
+        for (value, expected) in x.iter().zip([1.0, 1.0, 1.0]) {
+            assert!((value - expected).abs() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn estimate_with_als() {
+        let (data, background) = spectrum();
+        let baseline = Baseline::Als {
+            lambda: 1e5,
+            p: 0.001,
+            iterations: 20,
+        };
+        let estimate = assert_ok!(baseline.estimate(&data));
+        assert!(max_error(&estimate, &background) < 5.0);
+    }
+
+    #[test]
+    fn estimate_with_polynomial() {
+        let (data, background) = spectrum();
+        let baseline = Baseline::Polynomial {
+            order: 2,
+            iterations: 200,
+        };
+        let estimate = assert_ok!(baseline.estimate(&data));
+        // Clipping converges to baseline from above, slowly
+        assert!(max_error(&estimate, &background) < 10.0);
+    }
+
+    #[test]
+    fn estimate_with_rolling_ball() {
+        let (mut data, background) = spectrum();
+        let estimate = Baseline::RollingBall { radius: 30 }.estimate(&data);
+        assert!(max_error(&assert_ok!(estimate), &background) < 10.0);
+
+        assert_ok!(Baseline::RollingBall { radius: 30 }.remove(&mut data));
+        assert!(data[50].abs() < 10.0);
+        assert!(data[150] > 450.0);
+    }
+
+    #[test]
+    fn ignore_gaps_without_values() {
+        let (mut data, background) = spectrum();
+        // Wider than rolling ball window, so some windows have no values at all
+        data[200..300].iter_mut().for_each(|y| *y = f64::NAN);
+        // Polynomial loses the points it converges with in the gap, so it ends up further off
+        let baselines = [
+            (
+                Baseline::Als {
+                    lambda: 1e5,
+                    p: 0.001,
+                    iterations: 20,
+                },
+                5.0,
+            ),
+            (
+                Baseline::Polynomial {
+                    order: 2,
+                    iterations: 200,
+                },
+                20.0,
+            ),
+            (Baseline::RollingBall { radius: 30 }, 10.0),
+        ];
+        for (baseline, tolerance) in baselines {
+            let estimate = assert_ok!(baseline.estimate(&data));
+            assert!(estimate.iter().all(|b| b.is_finite()), "{baseline:?}");
+            assert!(
+                max_error(&estimate, &background) < tolerance,
+                "{baseline:?}"
+            );
+
+            let mut corrected = data.clone();
+            assert_ok!(baseline.remove(&mut corrected));
+            for (y, c) in data.iter().zip(&corrected) {
+                assert_eq!(y.is_nan(), c.is_nan(), "{baseline:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn parse_baselines() {
+        assert_ok_eq!(
+            "als:1e5:0.01".parse::<Baseline>(),
+            Baseline::Als {
+                lambda: 1e5,
+                p: 0.01,
+                iterations: 10
+            }
+        );
+        assert_ok_eq!(
+            "ball:25".parse::<Baseline>(),
+            Baseline::RollingBall { radius: 25 }
+        );
+        assert_err!("als:1e5:2".parse::<Baseline>());
+        assert_err!("poly:1.5".parse::<Baseline>());
+    }
+}
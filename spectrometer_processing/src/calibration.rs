@@ -0,0 +1,156 @@
+use crate::{
+    error::{Error, Result},
+    polynomial::Polynomial,
+};
+use core::fmt::{self, Display};
+use serde::{Deserialize, Serialize};
+
+/// Known wavelength in nanometres observed at a (possibly fractional) pixel position
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct CalibrationPoint {
+    pub pixel: f64,
+    pub wavelength: f64,
+}
+
+/// Mapping from pixel position to wavelength in nanometres
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct WavelengthCalibration {
+    polynomial: Polynomial,
+    points: Vec<CalibrationPoint>,
+}
+
+impl WavelengthCalibration {
+    pub const MIN_ORDER: usize = 1;
+    pub const MAX_ORDER: usize = 3;
+
+    /// Fits polynomial of order 1 to 3 to calibration points
+    pub fn fit(points: Vec<CalibrationPoint>, order: usize) -> Result<WavelengthCalibration> {
+        if !(Self::MIN_ORDER..=Self::MAX_ORDER).contains(&order) {
+            return Err(Error::InvalidOrder {
+                min: Self::MIN_ORDER,
+                max: Self::MAX_ORDER,
+            });
+        }
+        let pixels: Vec<_> = points.iter().map(|p| p.pixel).collect();
+        let wavelengths: Vec<_> = points.iter().map(|p| p.wavelength).collect();
+        let polynomial = Polynomial::fit(&pixels, &wavelengths, order)?;
+        Ok(WavelengthCalibration { polynomial, points })
+    }
+
+    pub fn polynomial(&self) -> &Polynomial {
+        &self.polynomial
+    }
+
+    pub fn points(&self) -> &[CalibrationPoint] {
+        &self.points
+    }
+
+    pub fn wavelength(&self, pixel: f64) -> f64 {
+        self.polynomial.eval(pixel)
+    }
+
+    /// Wavelengths of first `count` pixels
+    pub fn wavelengths(&self, count: usize) -> Vec<f64> {
+        (0..count)
+            .map(|pixel| self.wavelength(pixel as f64))
+            .collect()
+    }
+
+    /// Difference between known and fitted wavelength for every calibration point, in nm
+    pub fn residuals(&self) -> Vec<f64> {
+        self.points
+            .iter()
+            .map(|p| p.wavelength - self.wavelength(p.pixel))
+            .collect()
+    }
+
+    /// Root mean square of residuals, in nm
+    pub fn rms_error(&self) -> f64 {
+        let residuals = self.residuals();
+        if residuals.is_empty() {
+            return 0.0;
+        }
+        (residuals.iter().map(|r| r * r).sum::<f64>() / residuals.len() as f64).sqrt()
+    }
+}
+
+impl Display for WavelengthCalibration {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Polynomial order: {}", self.polynomial.order())?;
+        writeln!(f, "Coefficients: {:?}", self.polynomial.coefficients())?;
+        writeln!(
+            f,
+            "{:>10} {:>12} {:>12} {:>10}",
+            "Pixel", "Known, nm", "Fitted, nm", "Residual"
+        )?;
+        for (point, residual) in self.points.iter().zip(self.residuals()) {
+            writeln!(
+                f,
+                "{:>10.2} {:>12.3} {:>12.3} {:>10.4}",
+                point.pixel,
+                point.wavelength,
+                self.wavelength(point.pixel),
+                residual
+            )?;
+        }
+        write!(f, "RMS error: {:.4} nm", self.rms_error())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use claims::{assert_matches, assert_ok};
+
+    fn points(pairs: &[(f64, f64)]) -> Vec<CalibrationPoint> {
+        pairs
+            .iter()
+            .map(|&(pixel, wavelength)| CalibrationPoint { pixel, wavelength })
+            .collect()
+    }
+
+    #[test]
+    fn fit_linear_calibration() {
+        let cal = assert_ok!(WavelengthCalibration::fit(
+            points(&[(100.0, 400.0), (1100.0, 500.0), (2100.0, 600.0)]),
+            1
+        ));
+        assert!((cal.wavelength(600.0) - 450.0).abs() < 1e-9);
+        assert!(cal.rms_error() < 1e-9);
+    }
+
+    #[test]
+    fn report_residuals() {
+        let cal = assert_ok!(WavelengthCalibration::fit(
+            points(&[(0.0, 400.0), (1000.0, 501.0), (2000.0, 600.0)]),
+            1
+        ));
+        let residuals = cal.residuals();
+        assert_eq!(residuals.len(), 3);
+        assert!(residuals[1] > 0.5);
+        assert!(cal.rms_error() > 0.4);
+    }
+
+    #[test]
+    fn reject_invalid_order() {
+        let pairs = points(&[
+            (0.0, 400.0),
+            (1.0, 401.0),
+            (2.0, 402.0),
+            (3.0, 403.0),
+            (4.0, 405.0),
+        ]);
+        assert_matches!(
+            WavelengthCalibration::fit(pairs.clone(), 0),
+            Err(Error::InvalidOrder { .. })
+        );
+        assert_matches!(
+            WavelengthCalibration::fit(pairs, 4),
+            Err(Error::InvalidOrder { .. })
+        );
+        assert_matches!(
+            WavelengthCalibration::fit(points(&[(0.0, 400.0), (1.0, 401.0)]), 2),
+            Err(Error::NotEnoughPoints { .. })
+        );
+    }
+}
@@ -0,0 +1,321 @@
+//! PCA and PLS models built offline and applied to processed spectra. Models are JSON files
+//! with a vector per variable set, so they are easy to export from any tool:
+//!
+//! ```json
+//! {
+//!   "name": "moisture",
+//!   "axis": [1100.0, 1102.0, 1104.0],
+//!   "normalization": "Snv",
+//!   "mean": [0.41, 0.43, 0.40],
+//!   "scale": [0.02, 0.03, 0.02],
+//!   "loadings": [[0.57, 0.60, 0.56]],
+//!   "score_variances": [2.7],
+//!   "regression": [{ "name": "water, %", "intercept": 12.1, "coefficients": [0.8, -0.1, 0.3] }],
+//!   "t_squared_limit": 9.2,
+//!   "q_limit": 0.05
+//! }
+//! ```
+//!
+//! Only `name`, `mean`, `loadings` and `score_variances` are required. PLS models give their
+//! score weights (rotated weights, `W (PᵀW)⁻¹`) as `weights`, PCA models project onto loadings
+
+use crate::{
+    error::{Error, Result},
+    library::Normalization,
+    resample::{resample, Resampling},
+};
+use serde::{Deserialize, Serialize};
+use std::{fs, path::Path};
+
+/// Linear prediction of a property from preprocessed variables
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Regression {
+    pub name: String,
+    pub intercept: f64,
+    pub coefficients: Vec<f64>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ChemometricModel {
+    pub name: String,
+    /// Axis values of variables, spectra are interpolated onto them. Without axis every pixel
+    /// is a variable
+    #[serde(default)]
+    pub axis: Option<Vec<f64>>,
+    /// Applied to variables before centring
+    #[serde(default)]
+    pub normalization: Option<Normalization>,
+    /// Subtracted from every variable
+    pub mean: Vec<f64>,
+    /// Every variable is divided by it after centring, like for autoscaling
+    #[serde(default)]
+    pub scale: Option<Vec<f64>>,
+    /// Loadings of every component
+    pub loadings: Vec<Vec<f64>>,
+    /// Vectors that give scores of every component, loadings are used when not given
+    #[serde(default)]
+    pub weights: Option<Vec<Vec<f64>>>,
+    /// Variance of calibration scores of every component, for Hotelling T²
+    pub score_variances: Vec<f64>,
+    #[serde(default)]
+    pub regression: Vec<Regression>,
+    #[serde(default)]
+    pub t_squared_limit: Option<f64>,
+    #[serde(default)]
+    pub q_limit: Option<f64>,
+}
+
+/// Result of applying model to a single spectrum
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ModelOutput {
+    pub scores: Vec<f64>,
+    /// Value of every regression, in the same order
+    pub predictions: Vec<f64>,
+    /// Distance from calibration samples within model space
+    pub t_squared: f64,
+    /// Squared residual left outside of model space
+    pub q_residual: f64,
+}
+
+impl ModelOutput {
+    /// Spectrum is further from calibration samples than model allows, so predictions from it
+    /// are extrapolated
+    pub fn t_squared_outlier(&self, model: &ChemometricModel) -> bool {
+        model
+            .t_squared_limit
+            .is_some_and(|limit| self.t_squared > limit)
+    }
+
+    /// Spectrum has features that were not present in calibration samples
+    pub fn q_outlier(&self, model: &ChemometricModel) -> bool {
+        model.q_limit.is_some_and(|limit| self.q_residual > limit)
+    }
+}
+
+impl ChemometricModel {
+    /// Reads model from a JSON file and checks that its vectors fit together
+    pub fn load(path: &Path) -> Result<ChemometricModel> {
+        let model: ChemometricModel = serde_json::from_slice(&fs::read(path)?)?;
+        model.validate()?;
+        Ok(model)
+    }
+
+    pub fn variables(&self) -> usize {
+        self.mean.len()
+    }
+
+    pub fn components(&self) -> usize {
+        self.loadings.len()
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        let invalid =
+            |what: &str| Err(Error::InvalidModel(format!("Model {:?} {what}", self.name)));
+        let variables = self.variables();
+        if variables == 0 || self.loadings.is_empty() {
+            return invalid("has no variables or components");
+        }
+        let vectors = self
+            .axis
+            .iter()
+            .chain(self.scale.iter())
+            .chain(self.loadings.iter())
+            .chain(self.weights.iter().flatten())
+            .chain(self.regression.iter().map(|r| &r.coefficients));
+        if vectors.clone().any(|v| v.len() != variables) {
+            return invalid(&format!(
+                "has vectors that are not {variables} variables long"
+            ));
+        }
+        if vectors
+            .chain([&self.mean])
+            .flatten()
+            .any(|v| !v.is_finite())
+        {
+            return invalid("has values that are not finite");
+        }
+        if self
+            .weights
+            .as_ref()
+            .is_some_and(|w| w.len() != self.components())
+            || self.score_variances.len() != self.components()
+        {
+            return invalid("should have weights and score variances for every component");
+        }
+        if self
+            .score_variances
+            .iter()
+            .any(|v| !v.is_finite() || *v <= 0.0)
+        {
+            return invalid("has score variances that are not positive");
+        }
+        if self.scale.iter().flatten().any(|s| *s == 0.0) {
+            return invalid("has zero scale for some variables");
+        }
+        Ok(())
+    }
+
+    /// Applies model to a processed spectrum with axis value of every pixel in x
+    pub fn apply(&self, x: &[f64], y: &[f64]) -> Result<ModelOutput> {
+        let mut values = match &self.axis {
+            Some(axis) => resample(x, y, axis, Resampling::Linear)?,
+            None if y.len() == self.variables() => y.to_vec(),
+            None => {
+                return Err(Error::InvalidModel(format!(
+                    "Model {:?} has {} variables, while spectrum has {} pixels",
+                    self.name,
+                    self.variables(),
+                    y.len()
+                )))
+            }
+        };
+        if let Some(normalization) = self.normalization {
+            normalization.apply(&mut values);
+        }
+        if values.iter().any(|v| v.is_nan()) {
+            return Err(Error::InvalidModel(format!(
+                "Spectrum has no value for some variables of model {:?}",
+                self.name
+            )));
+        }
+        for (idx, v) in values.iter_mut().enumerate() {
+            *v -= self.mean[idx];
+            if let Some(scale) = &self.scale {
+                *v /= scale[idx];
+            }
+        }
+
+        let dot = |a: &[f64], b: &[f64]| a.iter().zip(b).map(|(a, b)| a * b).sum::<f64>();
+        let scores: Vec<f64> = self
+            .weights
+            .as_ref()
+            .unwrap_or(&self.loadings)
+            .iter()
+            .map(|w| dot(w, &values))
+            .collect();
+        let q_residual = values
+            .iter()
+            .enumerate()
+            .map(|(idx, v)| {
+                let modelled: f64 = scores
+                    .iter()
+                    .zip(&self.loadings)
+                    .map(|(t, p)| t * p[idx])
+                    .sum();
+                (v - modelled).powi(2)
+            })
+            .sum();
+        let t_squared = scores
+            .iter()
+            .zip(&self.score_variances)
+            .map(|(t, var)| t * t / var)
+            .sum();
+        let predictions = self
+            .regression
+            .iter()
+            .map(|r| r.intercept + dot(&r.coefficients, &values))
+            .collect();
+        Ok(ModelOutput {
+            scores,
+            predictions,
+            t_squared,
+            q_residual,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use claims::{assert_matches, assert_ok};
+
+    /// Two orthonormal components over 4 variables, with a regression on the first one
+    fn model() -> ChemometricModel {
+        ChemometricModel {
+            name: "test".into(),
+            axis: None,
+            normalization: None,
+            mean: vec![1.0, 2.0, 3.0, 4.0],
+            scale: None,
+            loadings: vec![vec![0.5, 0.5, 0.5, 0.5], vec![0.5, -0.5, 0.5, -0.5]],
+            weights: None,
+            score_variances: vec![4.0, 1.0],
+            regression: vec![Regression {
+                name: "fat".into(),
+                intercept: 10.0,
+                coefficients: vec![0.25, 0.25, 0.25, 0.25],
+            }],
+            t_squared_limit: Some(6.0),
+            q_limit: Some(0.1),
+        }
+    }
+
+    #[test]
+    fn project_onto_components() {
+        let model = model();
+        assert_ok!(model.validate());
+        let x = [0.0, 1.0, 2.0, 3.0];
+        // Mean plus 2 of the first component and 1 of the second
+        let y = [2.5, 2.5, 4.5, 4.5];
+        let output = assert_ok!(model.apply(&x, &y));
+        assert_eq!(output.scores, vec![2.0, 1.0]);
+        assert_eq!(output.predictions, vec![11.0]);
+        assert_eq!(output.t_squared, 2.0);
+        assert!(output.q_residual < 1e-12);
+        assert!(!output.t_squared_outlier(&model) && !output.q_outlier(&model));
+
+        // Doesn't fit any of the components
+        let output = assert_ok!(model.apply(&x, &[2.0, 2.0, 3.0, 4.0]));
+        assert!((output.q_residual - 0.5).abs() < 1e-12);
+        assert!(output.q_outlier(&model));
+
+        let output = assert_ok!(model.apply(&x, &[4.0, 5.0, 6.0, 7.0]));
+        assert!(output.t_squared_outlier(&model));
+    }
+
+    #[test]
+    fn interpolate_onto_model_axis() {
+        let model = ChemometricModel {
+            axis: Some(vec![500.0, 510.0, 520.0, 530.0]),
+            ..model()
+        };
+        let x: Vec<f64> = (0..50).map(|p| 490.0 + p as f64).collect();
+        // Mean everywhere, so both scores are zero
+        let y: Vec<f64> = x.iter().map(|x| (x - 500.0) / 10.0 + 1.0).collect();
+        let output = assert_ok!(model.apply(&x, &y));
+        assert!(output.scores.iter().all(|t| t.abs() < 1e-12));
+
+        // Spectrum does not reach 530
+        assert_matches!(model.apply(&x[..30], &y[..30]), Err(Error::InvalidModel(_)));
+    }
+
+    #[test]
+    fn reject_inconsistent_models() {
+        let mut short_loading = model();
+        short_loading.loadings[1].pop();
+        assert_matches!(short_loading.validate(), Err(Error::InvalidModel(_)));
+        let mut missing_variance = model();
+        missing_variance.score_variances.pop();
+        assert_matches!(missing_variance.validate(), Err(Error::InvalidModel(_)));
+        assert_matches!(
+            model().apply(&[0.0, 1.0], &[1.0, 2.0]),
+            Err(Error::InvalidModel(_))
+        );
+    }
+
+    #[test]
+    fn parse_minimal_model() {
+        let json = r#"{
+            "name": "pca",
+            "mean": [0.0, 0.0],
+            "loadings": [[1.0, 0.0]],
+            "score_variances": [1.0]
+        }"#;
+        let model: ChemometricModel = assert_ok!(serde_json::from_str(json));
+        assert_ok!(model.validate());
+        let output = assert_ok!(model.apply(&[0.0, 1.0], &[3.0, 4.0]));
+        assert_eq!(output.scores, vec![3.0]);
+        assert_eq!(output.q_residual, 16.0);
+        assert!(output.predictions.is_empty());
+    }
+}
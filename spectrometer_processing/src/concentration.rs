@@ -0,0 +1,303 @@
+use crate::{
+    bands::BandMetric,
+    error::{Error, Result},
+    linalg::{solve_spd, Matrix},
+    polynomial::Polynomial,
+};
+use core::fmt::{self, Display};
+use serde::{Deserialize, Serialize};
+
+/// Sample with known concentration and response measured from its absorbance spectrum
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Standard {
+    pub concentration: f64,
+    pub response: f64,
+}
+
+/// Response of absorbance at a band to concentration of an analyte, which is linear by
+/// Beer–Lambert law, with an optional quadratic term for deviations at high concentrations
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ConcentrationCurve {
+    /// Name of analyte
+    pub name: String,
+    /// Unit of concentrations, like mg/L
+    pub unit: String,
+    /// Computes response from absorbance spectrum, like absorbance at a band or its integral
+    pub metric: BandMetric,
+    /// Response as a function of concentration
+    pub polynomial: Polynomial,
+    pub standards: Vec<Standard>,
+    pub r_squared: f64,
+    /// Standard deviation of standard responses around the curve
+    pub residual_std: f64,
+    /// Limit of detection, lowest concentration that stands out from noise of a blank
+    pub lod: f64,
+}
+
+/// Concentration found from response of a sample
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Estimate {
+    pub concentration: f64,
+    /// Standard uncertainty from scatter of standards around the curve
+    pub uncertainty: f64,
+    pub response: f64,
+    pub below_lod: bool,
+    /// Concentration is outside of the range covered by standards
+    pub extrapolated: bool,
+}
+
+impl ConcentrationCurve {
+    /// Fits polynomial of order 1 or 2 to standards. At least one standard more than there are
+    /// coefficients is needed, to tell how far they scatter
+    pub fn fit(
+        name: String,
+        unit: String,
+        metric: BandMetric,
+        standards: Vec<Standard>,
+        order: usize,
+    ) -> Result<ConcentrationCurve> {
+        if !(1..=2).contains(&order) {
+            return Err(Error::InvalidOrder { min: 1, max: 2 });
+        }
+        let concentrations: Vec<f64> = standards.iter().map(|s| s.concentration).collect();
+        let responses: Vec<f64> = standards.iter().map(|s| s.response).collect();
+        let mut distinct = concentrations.clone();
+        distinct.sort_by(f64::total_cmp);
+        distinct.dedup();
+        if distinct.len() < order + 2 {
+            return Err(Error::NotEnoughPoints {
+                required: order + 2,
+                got: distinct.len(),
+            });
+        }
+        let polynomial = Polynomial::fit(&concentrations, &responses, order)?;
+
+        let n = standards.len() as f64;
+        let mean = responses.iter().sum::<f64>() / n;
+        let residual_sum: f64 = standards
+            .iter()
+            .map(|s| (s.response - polynomial.eval(s.concentration)).powi(2))
+            .sum();
+        let total_sum: f64 = responses.iter().map(|r| (r - mean).powi(2)).sum();
+        let residual_std = (residual_sum / (n - (order + 1) as f64)).sqrt();
+        // Factor of 3.3 comes from ICH Q2 guideline
+        let sensitivity = polynomial.derivative().eval(0.0).abs();
+        Ok(ConcentrationCurve {
+            name,
+            unit,
+            metric,
+            polynomial,
+            standards,
+            r_squared: 1.0 - residual_sum / total_sum,
+            residual_std,
+            lod: 3.3 * residual_std / sensitivity,
+        })
+    }
+
+    /// Estimates concentration from response averaged over given amount of replicate samples
+    pub fn concentration(&self, response: f64, replicates: usize) -> Result<Estimate> {
+        let c = self.polynomial.coefficients();
+        let (lowest, highest) = self.range();
+        let concentration = match *c {
+            [c0, c1] => (response - c0) / c1,
+            [c0, c1, c2] => {
+                // Root closest to standards, the other one is on the far side of a vertex
+                let discriminant = c1 * c1 - 4.0 * c2 * (c0 - response);
+                if discriminant < 0.0 {
+                    return Err(Error::NoConcentration(response));
+                }
+                let middle = (lowest + highest) / 2.0;
+                [1.0, -1.0]
+                    .map(|sign| (-c1 + sign * discriminant.sqrt()) / (2.0 * c2))
+                    .into_iter()
+                    .min_by(|a, b| (a - middle).abs().total_cmp(&(b - middle).abs()))
+                    .unwrap_or(f64::NAN)
+            }
+            _ => f64::NAN,
+        };
+        if !concentration.is_finite() {
+            return Err(Error::NoConcentration(response));
+        }
+        let slope = self.polynomial.derivative().eval(concentration).abs();
+        let uncertainty = self.residual_std / slope
+            * (1.0 / replicates.max(1) as f64 + self.leverage(concentration)).sqrt();
+        Ok(Estimate {
+            concentration,
+            uncertainty,
+            response,
+            below_lod: concentration < self.lod,
+            extrapolated: concentration < lowest || concentration > highest,
+        })
+    }
+
+    /// Estimates concentration from an absorbance spectrum with axis value of every pixel in x
+    pub fn estimate(&self, x: &[f64], absorbance: &[f64]) -> Result<Estimate> {
+        self.concentration(self.metric.evaluate(x, absorbance), 1)
+    }
+
+    /// Lowest and highest concentration of standards
+    pub fn range(&self) -> (f64, f64) {
+        self.standards
+            .iter()
+            .fold((f64::INFINITY, f64::NEG_INFINITY), |(lo, hi), s| {
+                (lo.min(s.concentration), hi.max(s.concentration))
+            })
+    }
+
+    /// Variance of curve at concentration relative to variance of a single response. Powers
+    /// are taken of concentrations mapped around the middle of standards, which keeps normal
+    /// equations well conditioned and does not change the result
+    fn leverage(&self, concentration: f64) -> f64 {
+        let terms = self.polynomial.order() + 1;
+        let (lowest, highest) = self.range();
+        let (center, scale) = (
+            (lowest + highest) / 2.0,
+            ((highest - lowest) / 2.0).max(1e-12),
+        );
+        let powers = |c: f64| {
+            let t = (c - center) / scale;
+            (0..terms).map(move |k| t.powi(k as i32))
+        };
+        let mut normal = Matrix::zeros(terms, terms);
+        for standard in &self.standards {
+            let row: Vec<f64> = powers(standard.concentration).collect();
+            for i in 0..terms {
+                for j in 0..terms {
+                    normal[(i, j)] += row[i] * row[j];
+                }
+            }
+        }
+        let g: Vec<f64> = powers(concentration).collect();
+        match solve_spd(&normal, &g) {
+            Ok(solution) => g.iter().zip(solution).map(|(a, b)| a * b).sum(),
+            Err(_) => f64::NAN,
+        }
+    }
+}
+
+impl Display for ConcentrationCurve {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let c = self.polynomial.coefficients();
+        write!(
+            f,
+            "{}: {} = {:.6} + {:.6}·c",
+            self.name, self.metric, c[0], c[1]
+        )?;
+        if let Some(c2) = c.get(2) {
+            write!(f, " + {c2:.6}·c²")?;
+        }
+        let (lowest, highest) = self.range();
+        write!(
+            f,
+            ", R² = {:.5}, LOD = {:.4} {unit}, {} standards from {lowest} to {highest} {unit}",
+            self.r_squared,
+            self.lod,
+            self.standards.len(),
+            unit = self.unit
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bands::Band;
+    use claims::{assert_matches, assert_ok};
+
+    fn metric() -> BandMetric {
+        BandMetric::Mean(Band {
+            start: 520.0,
+            end: 540.0,
+        })
+    }
+
+    /// Absorbance of 0.05 per unit with a bit of alternating scatter
+    fn standards(quadratic: f64) -> Vec<Standard> {
+        [0.0, 1.0, 2.0, 4.0, 6.0, 8.0, 10.0]
+            .iter()
+            .enumerate()
+            .map(|(idx, &c)| Standard {
+                concentration: c,
+                response: 0.002
+                    + 0.05 * c
+                    + quadratic * c * c
+                    + if idx % 2 == 0 { 0.001 } else { -0.001 },
+            })
+            .collect()
+    }
+
+    #[test]
+    fn fit_linear_curve() {
+        let curve = assert_ok!(ConcentrationCurve::fit(
+            "nitrate".into(),
+            "mg/L".into(),
+            metric(),
+            standards(0.0),
+            1
+        ));
+        assert!(curve.r_squared > 0.999);
+        assert!((curve.polynomial.coefficients()[1] - 0.05).abs() < 1e-3);
+        // Scatter of about 0.001 over slope of 0.05
+        assert!(curve.lod > 0.03 && curve.lod < 0.15, "{curve}");
+
+        let estimate = assert_ok!(curve.concentration(0.002 + 0.05 * 5.0, 1));
+        assert!((estimate.concentration - 5.0).abs() < 0.05);
+        assert!(estimate.uncertainty > 0.01 && estimate.uncertainty < 0.05);
+        assert!(!estimate.below_lod && !estimate.extrapolated);
+        // Replicates shrink uncertainty
+        let averaged = assert_ok!(curve.concentration(0.002 + 0.05 * 5.0, 4));
+        assert!(averaged.uncertainty < estimate.uncertainty);
+
+        let high = assert_ok!(curve.concentration(1.0, 1));
+        assert!(high.extrapolated);
+        assert!(assert_ok!(curve.concentration(0.002, 1)).below_lod);
+    }
+
+    #[test]
+    fn fit_quadratic_curve() {
+        let curve = assert_ok!(ConcentrationCurve::fit(
+            "dye".into(),
+            "µM".into(),
+            metric(),
+            standards(-0.002),
+            2
+        ));
+        let response = 0.002 + 0.05 * 7.0 - 0.002 * 49.0;
+        let estimate = assert_ok!(curve.concentration(response, 1));
+        assert!((estimate.concentration - 7.0).abs() < 0.1, "{estimate:?}");
+        assert_matches!(curve.concentration(10.0, 1), Err(Error::NoConcentration(_)));
+    }
+
+    #[test]
+    fn estimate_from_spectrum() {
+        let curve = assert_ok!(ConcentrationCurve::fit(
+            "nitrate".into(),
+            "mg/L".into(),
+            metric(),
+            standards(0.0),
+            1
+        ));
+        let x: Vec<f64> = (0..100).map(|p| 500.0 + p as f64).collect();
+        let absorbance: Vec<f64> = x
+            .iter()
+            .map(|&x| {
+                if (520.0..=540.0).contains(&x) {
+                    0.202
+                } else {
+                    0.0
+                }
+            })
+            .collect();
+        let estimate = assert_ok!(curve.estimate(&x, &absorbance));
+        assert!((estimate.concentration - 4.0).abs() < 0.05);
+    }
+
+    #[test]
+    fn reject_too_few_standards() {
+        let standards = standards(0.0)[..3].to_vec();
+        assert_matches!(
+            ConcentrationCurve::fit("a".into(), "".into(), metric(), standards, 2),
+            Err(Error::NotEnoughPoints { required: 4, .. })
+        );
+    }
+}
@@ -0,0 +1,135 @@
+use crate::error::{Error, Result};
+use ccd_lcamv06::{ExposureTime, Frame, FRAME_PIXEL_COUNT};
+use serde::{Deserialize, Serialize};
+
+/// Average of frames taken with no light reaching CCD. Holds dark current and fixed pattern
+/// noise, which only match frames taken with the same exposure time
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct DarkFrame {
+    exposure: ExposureTime,
+    frame_count: usize,
+    pixels: Vec<f64>,
+    /// Active pixels that were saturated or clipped in any of averaged frames
+    #[serde(default)]
+    clipped: Vec<usize>,
+}
+
+impl DarkFrame {
+    /// Averages frames, which all have to be taken with the same known exposure time
+    pub fn average(frames: &[Frame]) -> Result<DarkFrame> {
+        let (exposure, pixels, clipped) = average_pixels(frames)?;
+        Ok(DarkFrame {
+            exposure,
+            frame_count: frames.len(),
+            pixels,
+            clipped,
+        })
+    }
+
+    pub fn exposure(&self) -> ExposureTime {
+        self.exposure
+    }
+
+    /// Amount of frames that were averaged
+    pub fn frame_count(&self) -> usize {
+        self.frame_count
+    }
+
+    pub fn pixels(&self) -> &[f64] {
+        &self.pixels
+    }
+
+    /// Active pixels that were saturated or clipped in any of averaged frames, their averages
+    /// don't reflect dark level
+    pub fn clipped(&self) -> &[usize] {
+        &self.clipped
+    }
+
+    /// Intensity in counts above dark level of every pixel, which takes place of black level
+    pub fn intensity(&self, frame: &Frame) -> Vec<f64> {
+        frame
+            .iter()
+            .zip(&self.pixels)
+            .map(|(&pixel, dark)| dark - f64::from(pixel))
+            .collect()
+    }
+
+    /// Subtracts dark from raw pixel values. CCD output is inverted, so pixels that got light
+    /// end up negative
+    pub fn subtract(&self, frame: &Frame) -> Vec<f64> {
+        frame
+            .iter()
+            .zip(&self.pixels)
+            .map(|(&pixel, dark)| f64::from(pixel) - dark)
+            .collect()
+    }
+}
+
+/// Per pixel mean of frames, which all have to be taken with the same known exposure time.
+/// Also gives active pixels clipped in any frame, since their means don't reflect amount of light
+pub(crate) fn average_pixels(frames: &[Frame]) -> Result<(ExposureTime, Vec<f64>, Vec<usize>)> {
+    let exposure = frames
+        .first()
+        .ok_or(Error::NoFrames)?
+        .exposure()
+        .ok_or(Error::UnknownExposure)?;
+    if frames
+        .iter()
+        .any(|frame| frame.exposure() != Some(exposure))
+    {
+        return Err(Error::UnknownExposure);
+    }
+    let mut clipped: Vec<usize> = frames.iter().flat_map(Frame::clipped_pixels).collect();
+    clipped.sort_unstable();
+    clipped.dedup();
+    let mut pixels = vec![0.0; FRAME_PIXEL_COUNT];
+    for frame in frames {
+        for (sum, &pixel) in pixels.iter_mut().zip(frame.iter()) {
+            *sum += f64::from(pixel);
+        }
+    }
+    pixels
+        .iter_mut()
+        .for_each(|sum| *sum /= frames.len() as f64);
+    Ok((exposure, pixels, clipped))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::frame;
+    use claims::{assert_matches, assert_ok};
+
+    #[test]
+    fn average_dark_frames() {
+        let dark = assert_ok!(DarkFrame::average(&[frame(1000, 20), frame(1010, 20)]));
+        assert_eq!(dark.frame_count(), 2);
+        assert_eq!(dark.exposure().as_millis(), 20);
+        assert_eq!(dark.pixels()[0], 1005.0);
+        assert_eq!(dark.subtract(&frame(900, 20))[0], -105.0);
+        assert_eq!(dark.intensity(&frame(900, 20))[0], 105.0);
+    }
+
+    #[test]
+    fn reject_mismatched_exposures() {
+        assert_matches!(DarkFrame::average(&[]), Err(Error::NoFrames));
+        assert_matches!(
+            DarkFrame::average(&[frame(1000, 20), frame(1000, 30)]),
+            Err(Error::UnknownExposure)
+        );
+        let unknown = Frame::new([1000; FRAME_PIXEL_COUNT]);
+        assert_matches!(DarkFrame::average(&[unknown]), Err(Error::UnknownExposure));
+    }
+
+    #[test]
+    fn mark_clipped_pixels() {
+        let (mut low, mut high) = (frame(1000, 20), frame(1000, 20));
+        low[100] = 0;
+        high[100] = u16::MAX;
+        high[2000] = u16::MAX;
+        // Dummy pixel stuck at the rail
+        high[3] = u16::MAX;
+        let dark = assert_ok!(DarkFrame::average(&[frame(1000, 20), low, high]));
+        assert_eq!(dark.clipped(), [100, 2000]);
+    }
+}
@@ -0,0 +1,216 @@
+use crate::{absorbance::ReferenceSpectrum, dark::DarkFrame, filter::Filter};
+use ccd_lcamv06::ACTIVE_PIXELS;
+use core::fmt;
+use serde::{Deserialize, Serialize};
+
+/// Limits for pixels to be considered defective
+#[derive(Clone, Copy, Debug)]
+pub struct DefectOptions {
+    /// Deviation from neighbouring pixels, in robust standard deviations of all pixels
+    pub threshold: f64,
+    /// Amount of pixels that a pixel is compared with, should be odd
+    pub window: usize,
+}
+
+impl Default for DefectOptions {
+    fn default() -> Self {
+        DefectOptions {
+            threshold: 8.0,
+            window: 9,
+        }
+    }
+}
+
+/// Pixels of a CCD that are stuck or respond to light differently from their neighbours
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DefectMap {
+    /// Pixels with excess dark current
+    pub hot: Vec<usize>,
+    /// Pixels that respond to light much weaker or stronger than neighbours
+    pub dead: Vec<usize>,
+}
+
+impl DefectMap {
+    /// Finds outliers in dark frame and in response to even illumination, which is the
+    /// difference of reference spectrum and dark frame. Both should be taken at the same exposure.
+    /// Pixels stuck at either end of ADC range are defects as well: hot when they are stuck in
+    /// the dark, dead when only under light
+    pub fn find(dark: &DarkFrame, flat: &ReferenceSpectrum, options: &DefectOptions) -> DefectMap {
+        let pixels = dark.pixels();
+        let response: Vec<f64> = pixels
+            .iter()
+            .zip(flat.pixels())
+            .map(|(d, f)| d - f)
+            .collect();
+        // Output is inverted, so dark current pushes pixels down
+        let mut hot: Vec<usize> = outliers(&pixels[ACTIVE_PIXELS], options, |value, local| {
+            local - value
+        })
+        .map(|idx| idx + ACTIVE_PIXELS.start)
+        .chain(dark.clipped().iter().copied())
+        .collect();
+        hot.sort_unstable();
+        hot.dedup();
+        let mut dead: Vec<usize> = outliers(&response[ACTIVE_PIXELS], options, |value, local| {
+            (value - local).abs()
+        })
+        .map(|idx| idx + ACTIVE_PIXELS.start)
+        .chain(flat.clipped().iter().copied())
+        .filter(|pixel| hot.binary_search(pixel).is_err())
+        .collect();
+        dead.sort_unstable();
+        dead.dedup();
+        DefectMap { hot, dead }
+    }
+
+    /// All defective pixels in ascending order
+    pub fn pixels(&self) -> Vec<usize> {
+        let mut pixels: Vec<usize> = self.hot.iter().chain(&self.dead).copied().collect();
+        pixels.sort_unstable();
+        pixels.dedup();
+        pixels
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.hot.is_empty() && self.dead.is_empty()
+    }
+
+    /// Replaces defective pixels by linear interpolation between closest good pixels on each
+    /// side, returns corrected pixels
+    pub fn correct(&self, spectrum: &mut [f64]) -> Vec<usize> {
+        let pixels: Vec<usize> = self
+            .pixels()
+            .into_iter()
+            .filter(|&idx| idx < spectrum.len())
+            .collect();
+        let mut defective = vec![false; spectrum.len()];
+        pixels.iter().for_each(|&idx| defective[idx] = true);
+        interpolate_over(spectrum, &defective);
+        pixels
+    }
+}
+
+/// Replaces flagged values by linear interpolation between closest unflagged values on each
+/// side, or copies the closest one at the ends
+pub(crate) fn interpolate_over(data: &mut [f64], flagged: &[bool]) {
+    for idx in (0..data.len()).filter(|&idx| flagged[idx]) {
+        let left = (0..idx).rev().find(|&i| !flagged[i]);
+        let right = (idx + 1..data.len()).find(|&i| !flagged[i]);
+        data[idx] = match (left, right) {
+            (Some(l), Some(r)) => {
+                let t = (idx - l) as f64 / (r - l) as f64;
+                data[l] + (data[r] - data[l]) * t
+            }
+            (Some(i), None) | (None, Some(i)) => data[i],
+            (None, None) => data[idx],
+        };
+    }
+}
+
+impl fmt::Display for DefectMap {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let list = |pixels: &[usize]| {
+            pixels
+                .iter()
+                .map(|p| p.to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        writeln!(f, "{} hot pixels: {}", self.hot.len(), list(&self.hot))?;
+        write!(f, "{} dead pixels: {}", self.dead.len(), list(&self.dead))
+    }
+}
+
+/// Indices where deviation from local median is over threshold. Spread is estimated with
+/// median absolute deviation, so outliers themselves don't inflate it
+fn outliers<'a>(
+    data: &'a [f64],
+    options: &DefectOptions,
+    deviation: impl Fn(f64, f64) -> f64 + 'a,
+) -> impl Iterator<Item = usize> + 'a {
+    let mut local = vec![0.0; data.len()];
+    if let Ok(median) = Filter::median(options.window) {
+        median.apply(data, &mut local);
+    } else {
+        local.copy_from_slice(data);
+    }
+    let mut residuals: Vec<f64> = data
+        .iter()
+        .zip(&local)
+        .map(|(v, l)| (v - l).abs())
+        .collect();
+    let mid = residuals.len() / 2;
+    let mad = if residuals.is_empty() {
+        0.0
+    } else {
+        *residuals.select_nth_unstable_by(mid, f64::total_cmp).1
+    };
+    // Scales MAD to standard deviation of normal distribution. Perfectly flat data still
+    // needs some room, or every bit of noise would be an outlier
+    let limit = options.threshold * (1.4826 * mad).max(1.0);
+    (0..data.len()).filter(move |&idx| deviation(data[idx], local[idx]) > limit)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::frame_of;
+    use ccd_lcamv06::FRAME_PIXEL_COUNT;
+
+    /// Slightly noisy frame, so that spread is not zero
+    fn noisy(level: u16) -> [u16; FRAME_PIXEL_COUNT] {
+        let mut pixels = [level; FRAME_PIXEL_COUNT];
+        pixels
+            .iter_mut()
+            .enumerate()
+            .for_each(|(idx, p)| *p += (idx * 7 % 5) as u16);
+        pixels
+    }
+
+    #[test]
+    fn find_hot_and_dead_pixels() {
+        let mut dark = noisy(3000);
+        dark[500] = 2500;
+        let mut flat = noisy(1000);
+        // Dark current of hot pixel adds to its response too
+        flat[500] = 500;
+        flat[1200] = 2990;
+        // Outside of active pixels, should be ignored
+        flat[5] = 3000;
+
+        let map = DefectMap::find(
+            &DarkFrame::average(&[frame_of(dark, 20)]).unwrap(),
+            &ReferenceSpectrum::average(&[frame_of(flat, 20)]).unwrap(),
+            &DefectOptions::default(),
+        );
+        assert_eq!(map.hot, vec![500]);
+        assert_eq!(map.dead, vec![1200]);
+    }
+
+    #[test]
+    fn find_pixels_stuck_at_rail() {
+        let (mut dark, mut flat) = (noisy(3000), noisy(1000));
+        dark[700] = u16::MAX;
+        flat[700] = u16::MAX;
+        flat[900] = 0;
+
+        let map = DefectMap::find(
+            &DarkFrame::average(&[frame_of(dark, 20)]).unwrap(),
+            &ReferenceSpectrum::average(&[frame_of(flat, 20)]).unwrap(),
+            &DefectOptions::default(),
+        );
+        assert_eq!(map.hot, vec![700]);
+        assert_eq!(map.dead, vec![900]);
+    }
+
+    #[test]
+    fn interpolate_defective_pixels() {
+        let map = DefectMap {
+            hot: vec![0, 3],
+            dead: vec![4, 3],
+        };
+        let mut spectrum = vec![100.0, 1.0, 2.0, 50.0, -50.0, 5.0];
+        assert_eq!(map.correct(&mut spectrum), vec![0, 3, 4]);
+        assert_eq!(spectrum, vec![1.0, 1.0, 2.0, 3.0, 4.0, 5.0]);
+    }
+}
@@ -0,0 +1,68 @@
+use ccd_lcamv06::ExposureTime;
+use core::result::Result as CoreResult;
+use thiserror::Error;
+
+pub type Result<T> = CoreResult<T, Error>;
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("Polynomial order should be in range of {min} to {max}")]
+    InvalidOrder { min: usize, max: usize },
+    #[error("At least {required} points with distinct positions are required, got {got}")]
+    NotEnoughPoints { required: usize, got: usize },
+    #[error("Could not fit data, system of equations is singular")]
+    SingularSystem,
+    #[error("Line {0} of line list is not a wavelength")]
+    InvalidLineList(usize),
+    #[error("Response {0} is not reached by calibration curve")]
+    NoConcentration(f64),
+    #[error("At least one peak is required to fit")]
+    NoPeaks,
+    #[error("Could not match lamp lines, at least {required} matches are required, found {found}")]
+    NoLineMatch { required: usize, found: usize },
+
+    #[error("Wavelength should be a positive amount of nm, got {0}")]
+    InvalidWavelength(f64),
+
+    #[error("{0}")]
+    InvalidFilter(String),
+    #[error("{0}")]
+    InvalidBaseline(String),
+    #[error("{0}")]
+    InvalidDespike(String),
+    #[error("{0}")]
+    InvalidGrid(String),
+    #[error("{0}")]
+    InvalidBand(String),
+    #[error("{0}")]
+    InvalidModel(String),
+    #[error("Unknown peak shape {0:?}, expected gaussian, lorentzian or voigt")]
+    InvalidShape(String),
+
+    #[error("At least one frame is required")]
+    NoFrames,
+    #[error("At least {required} frames are required, got {got}")]
+    NotEnoughFrames { required: usize, got: usize },
+    #[error("Frames should be taken with the same known exposure time")]
+    UnknownExposure,
+    #[error("Frame taken at {got} does not match reference data taken at {expected}")]
+    ExposureMismatch {
+        expected: ExposureTime,
+        got: ExposureTime,
+    },
+
+    #[error("Library spectrum {name:?} has {got} values, while sample has {expected}")]
+    LengthMismatch {
+        name: String,
+        expected: usize,
+        got: usize,
+    },
+
+    #[error("{name:?} would be stored in the same file as {existing:?}, choose another name")]
+    NameCollision { name: String, existing: String },
+
+    #[error("{0}")]
+    Io(#[from] std::io::Error),
+    #[error("Stored data is corrupted: {0}")]
+    Json(#[from] serde_json::Error),
+}
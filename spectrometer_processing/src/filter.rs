@@ -0,0 +1,345 @@
+//! Smoothing and derivative filters. Kernels are computed once when a filter is built, applying
+//! it only reads input and writes output, so it can run on every frame without allocations
+
+use crate::{
+    error::{Error, Result},
+    linalg::{least_squares, Matrix},
+};
+use core::str::FromStr;
+
+/// Largest window of any filter, already wider than a quarter of a frame
+pub const MAX_WINDOW: usize = 1023;
+
+/// Largest median window, samples are sorted in a buffer on stack
+pub const MAX_MEDIAN_WINDOW: usize = 255;
+
+/// Highest polynomial order of Savitzky-Golay filters, kernel takes a least squares fit per
+/// window sample, each growing with the order
+pub const MAX_SAVITZKY_GOLAY_ORDER: usize = 12;
+
+/// Way to get samples past the ends of data
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Edge {
+    /// Mirrored around the edge sample, `c b | a b c`
+    #[default]
+    Reflect,
+    /// Repeated edge sample, `a a | a b c`
+    Nearest,
+    /// Zeroes, `0 0 | a b c`
+    Zero,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Kernel {
+    /// Weights for offsets from `-half` to `half`
+    Convolution(Vec<f64>),
+    Median(usize),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Filter {
+    kernel: Kernel,
+    edge: Edge,
+}
+
+impl Filter {
+    /// Moving average over `window` samples
+    pub fn boxcar(window: usize) -> Result<Filter> {
+        check_window(window, 1)?;
+        Ok(Filter::convolution(vec![1.0 / window as f64; window]))
+    }
+
+    /// Gaussian weighted average, window covers 3 sigma on each side
+    pub fn gaussian(sigma: f64) -> Result<Filter> {
+        if !sigma.is_finite() || sigma <= 0.0 {
+            return Err(Error::InvalidFilter(format!(
+                "Gaussian sigma should be positive, got {sigma}"
+            )));
+        }
+        let half = (3.0 * sigma).ceil();
+        if 2.0 * half + 1.0 > MAX_WINDOW as f64 {
+            return Err(Error::InvalidFilter(format!(
+                "Gaussian sigma {sigma} needs a window over {MAX_WINDOW}"
+            )));
+        }
+        let half = half as isize;
+        let weights: Vec<f64> = (-half..=half)
+            .map(|x| (-(x as f64).powi(2) / (2.0 * sigma * sigma)).exp())
+            .collect();
+        let sum: f64 = weights.iter().sum();
+        Ok(Filter::convolution(
+            weights.into_iter().map(|w| w / sum).collect(),
+        ))
+    }
+
+    /// Median of `window` samples, removes spikes without blurring edges
+    pub fn median(window: usize) -> Result<Filter> {
+        check_window(window, 1)?;
+        if window > MAX_MEDIAN_WINDOW {
+            return Err(Error::InvalidFilter(format!(
+                "Median window should not exceed {MAX_MEDIAN_WINDOW}"
+            )));
+        }
+        Ok(Filter {
+            kernel: Kernel::Median(window),
+            edge: Edge::default(),
+        })
+    }
+
+    /// Least squares polynomial of `order` fitted over `window` samples, evaluated at the
+    /// middle. With `derivative` of 1 or 2 gives derivatives per pixel instead of values
+    pub fn savitzky_golay(window: usize, order: usize, derivative: usize) -> Result<Filter> {
+        if order > MAX_SAVITZKY_GOLAY_ORDER {
+            return Err(Error::InvalidFilter(format!(
+                "Polynomial order should not exceed {MAX_SAVITZKY_GOLAY_ORDER}, got {order}"
+            )));
+        }
+        check_window(window, order + 1)?;
+        if derivative > 2 || derivative > order {
+            return Err(Error::InvalidFilter(format!(
+                "Derivative should be 0 to 2 and not above polynomial order {order}, got {derivative}"
+            )));
+        }
+        let half = window / 2;
+        // Offsets are scaled into [-1, 1] to keep the system well conditioned
+        let scale = half as f64;
+        let mut a = Matrix::zeros(window, order + 1);
+        for row in 0..window {
+            let t = (row as f64 - scale) / scale;
+            let mut power = 1.0;
+            for col in 0..=order {
+                a[(row, col)] = power;
+                power *= t;
+            }
+        }
+        let factorial = (1..=derivative).product::<usize>() as f64;
+        let mut weights = Vec::with_capacity(window);
+        for idx in 0..window {
+            let mut unit = vec![0.0; window];
+            unit[idx] = 1.0;
+            let coefficients = least_squares(a.clone(), unit)?;
+            weights.push(coefficients[derivative] * factorial / scale.powi(derivative as i32));
+        }
+        Ok(Filter::convolution(weights))
+    }
+
+    fn convolution(weights: Vec<f64>) -> Filter {
+        Filter {
+            kernel: Kernel::Convolution(weights),
+            edge: Edge::default(),
+        }
+    }
+
+    pub fn with_edge(mut self, edge: Edge) -> Filter {
+        self.edge = edge;
+        self
+    }
+
+    pub fn window(&self) -> usize {
+        match &self.kernel {
+            Kernel::Convolution(weights) => weights.len(),
+            Kernel::Median(window) => *window,
+        }
+    }
+
+    /// Filters `input` into `output` of the same length
+    pub fn apply(&self, input: &[f64], output: &mut [f64]) {
+        assert_eq!(
+            input.len(),
+            output.len(),
+            "Output should match input length"
+        );
+        let half = (self.window() / 2) as isize;
+        match &self.kernel {
+            Kernel::Convolution(weights) => {
+                for (idx, out) in output.iter_mut().enumerate() {
+                    *out = weights
+                        .iter()
+                        .enumerate()
+                        .map(|(k, w)| {
+                            w * sample(input, idx as isize + k as isize - half, self.edge)
+                        })
+                        .sum();
+                }
+            }
+            Kernel::Median(window) => {
+                let mut buffer = [0.0; MAX_MEDIAN_WINDOW];
+                let buffer = &mut buffer[..*window];
+                for (idx, out) in output.iter_mut().enumerate() {
+                    for (k, value) in buffer.iter_mut().enumerate() {
+                        *value = sample(input, idx as isize + k as isize - half, self.edge);
+                    }
+                    buffer.sort_unstable_by(f64::total_cmp);
+                    *out = buffer[window / 2];
+                }
+            }
+        }
+    }
+}
+
+/// Parses filters written as `boxcar:WINDOW`, `gaussian:SIGMA`, `median:WINDOW` or
+/// `sg:WINDOW:ORDER[:DERIVATIVE]`
+impl FromStr for Filter {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Filter> {
+        let invalid = || Error::InvalidFilter(format!("Unknown filter {s:?}"));
+        let mut parts = s.trim().split(':');
+        let name = parts.next().unwrap_or_default();
+        let params = parts
+            .map(|p| p.trim().parse::<f64>().map_err(|_| invalid()))
+            .collect::<Result<Vec<_>>>()?;
+        let whole = |value: f64| {
+            if value >= 0.0 && value.fract() == 0.0 {
+                Ok(value as usize)
+            } else {
+                Err(invalid())
+            }
+        };
+        match (name, params.as_slice()) {
+            ("boxcar", &[window]) => Filter::boxcar(whole(window)?),
+            ("gaussian", &[sigma]) => Filter::gaussian(sigma),
+            ("median", &[window]) => Filter::median(whole(window)?),
+            ("sg", &[window, order]) => Filter::savitzky_golay(whole(window)?, whole(order)?, 0),
+            ("sg", &[window, order, derivative]) => {
+                Filter::savitzky_golay(whole(window)?, whole(order)?, whole(derivative)?)
+            }
+            _ => Err(invalid()),
+        }
+    }
+}
+
+/// Applies filters one after another to `data`, using `scratch` of the same length in between
+pub fn apply_chain(filters: &[Filter], data: &mut [f64], scratch: &mut [f64]) {
+    for filter in filters {
+        filter.apply(data, scratch);
+        data.copy_from_slice(scratch);
+    }
+}
+
+fn check_window(window: usize, min: usize) -> Result<()> {
+    if window.is_multiple_of(2) || window < min || window > MAX_WINDOW {
+        return Err(Error::InvalidFilter(format!(
+            "Window should be odd, from {min} to {MAX_WINDOW}, got {window}"
+        )));
+    }
+    Ok(())
+}
+
+fn sample(data: &[f64], idx: isize, edge: Edge) -> f64 {
+    let len = data.len() as isize;
+    if (0..len).contains(&idx) {
+        return data[idx as usize];
+    }
+    match edge {
+        Edge::Zero => 0.0,
+        Edge::Nearest => data[idx.clamp(0, len - 1) as usize],
+        Edge::Reflect => {
+            let period = 2 * (len - 1);
+            if period == 0 {
+                return data[0];
+            }
+            let idx = idx.rem_euclid(period);
+            data[(if idx < len { idx } else { period - idx }) as usize]
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use claims::{assert_err, assert_ok, assert_ok_eq};
+
+    fn filtered(filter: &Filter, input: &[f64]) -> Vec<f64> {
+        let mut output = vec![0.0; input.len()];
+        filter.apply(input, &mut output);
+        output
+    }
+
+    #[test]
+    fn handle_edges() {
+        let data = [1.0, 2.0, 3.0];
+        assert_eq!(sample(&data, -2, Edge::Reflect), 3.0);
+        assert_eq!(sample(&data, 4, Edge::Reflect), 1.0);
+        assert_eq!(sample(&data, -2, Edge::Nearest), 1.0);
+        assert_eq!(sample(&data, 3, Edge::Zero), 0.0);
+    }
+
+    #[test]
+    fn smooth_with_boxcar_and_gaussian() {
+        let boxcar = assert_ok!(Filter::boxcar(3)).with_edge(Edge::Nearest);
+        assert_eq!(
+            filtered(&boxcar, &[0.0, 3.0, 0.0, 3.0]),
+            [1.0, 1.0, 2.0, 2.0]
+        );
+
+        let gaussian = assert_ok!(Filter::gaussian(1.5));
+        assert_eq!(gaussian.window(), 11);
+        let flat = filtered(&gaussian, &[5.0; 20]);
+        assert!(flat.iter().all(|y| (y - 5.0).abs() < 1e-12));
+    }
+
+    #[test]
+    fn remove_spikes_with_median() {
+        let median = assert_ok!(Filter::median(3));
+        assert_eq!(
+            filtered(&median, &[1.0, 1.0, 9.0, 1.0, 2.0]),
+            [1.0, 1.0, 1.0, 2.0, 1.0]
+        );
+    }
+
+    #[test]
+    fn differentiate_with_savitzky_golay() {
+        let data: Vec<f64> = (0..30)
+            .map(|x| 0.5 * (x as f64).powi(2) + x as f64)
+            .collect();
+        let smooth = filtered(&assert_ok!(Filter::savitzky_golay(7, 2, 0)), &data);
+        let first = filtered(&assert_ok!(Filter::savitzky_golay(7, 2, 1)), &data);
+        let second = filtered(&assert_ok!(Filter::savitzky_golay(7, 2, 2)), &data);
+        // Quadratic is reproduced exactly away from edges
+        for x in 3..27 {
+            assert!((smooth[x] - data[x]).abs() < 1e-9);
+            assert!((first[x] - (x as f64 + 1.0)).abs() < 1e-9);
+            assert!((second[x] - 1.0).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn parse_filters() {
+        assert_ok_eq!("boxcar:5".parse::<Filter>(), assert_ok!(Filter::boxcar(5)));
+        assert_ok_eq!(
+            "sg:11:3:1".parse::<Filter>(),
+            assert_ok!(Filter::savitzky_golay(11, 3, 1))
+        );
+        assert_err!("boxcar:4".parse::<Filter>());
+        assert_err!("sg:5:5".parse::<Filter>());
+        assert_err!("mean:5".parse::<Filter>());
+    }
+
+    #[test]
+    fn reject_oversized_kernels() {
+        assert_err!("gaussian:inf".parse::<Filter>());
+        assert_err!("gaussian:nan".parse::<Filter>());
+        assert_err!("gaussian:1e9".parse::<Filter>());
+        assert_ok!(Filter::gaussian(170.0));
+        assert_err!(Filter::gaussian(171.0));
+        assert_err!("boxcar:999999999".parse::<Filter>());
+        assert_err!("boxcar:1e300".parse::<Filter>());
+        assert_ok!(Filter::boxcar(MAX_WINDOW));
+        assert_err!(Filter::boxcar(MAX_WINDOW + 2));
+        assert_err!(Filter::savitzky_golay(MAX_WINDOW + 2, 3, 0));
+        assert_err!(Filter::savitzky_golay(1001, 999, 0));
+    }
+
+    #[test]
+    fn chain_filters() {
+        let filters = [
+            assert_ok!(Filter::median(3)),
+            assert_ok!(Filter::boxcar(3)).with_edge(Edge::Nearest),
+        ];
+        let mut data = [0.0, 0.0, 9.0, 0.0, 0.0];
+        let mut scratch = [0.0; 5];
+        apply_chain(&filters, &mut data, &mut scratch);
+        assert_eq!(data, [0.0; 5]);
+    }
+}
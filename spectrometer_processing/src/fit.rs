@@ -0,0 +1,592 @@
+use crate::{
+    error::{Error, Result},
+    linalg::{solve_spd, Matrix},
+    peaks::Peak,
+};
+use core::f64::consts::{LN_2, PI};
+use core::fmt::{self, Display};
+use core::ops::Range;
+use core::str::FromStr;
+use serde::{Deserialize, Serialize};
+
+/// Line shape of a fitted peak, every one is parametrised with center, height and FWHM
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Shape {
+    #[default]
+    Gaussian,
+    Lorentzian,
+    /// Weighted sum of Gaussian and Lorentzian with the same FWHM, weight of Lorentzian is fitted
+    PseudoVoigt,
+}
+
+impl Shape {
+    /// Amount of fitted parameters per peak
+    fn params(&self) -> usize {
+        match self {
+            Shape::PseudoVoigt => 4,
+            _ => 3,
+        }
+    }
+
+    fn eval(&self, x: f64, p: &[f64]) -> f64 {
+        let (center, height, fwhm) = (p[0], p[1], p[2]);
+        let d = (x - center) / fwhm;
+        let gaussian = || (-4.0 * LN_2 * d * d).exp();
+        let lorentzian = || 1.0 / (1.0 + 4.0 * d * d);
+        height
+            * match self {
+                Shape::Gaussian => gaussian(),
+                Shape::Lorentzian => lorentzian(),
+                Shape::PseudoVoigt => p[3] * lorentzian() + (1.0 - p[3]) * gaussian(),
+            }
+    }
+}
+
+impl FromStr for Shape {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Shape> {
+        match s.trim() {
+            "gaussian" => Ok(Shape::Gaussian),
+            "lorentzian" => Ok(Shape::Lorentzian),
+            "voigt" | "pseudo-voigt" => Ok(Shape::PseudoVoigt),
+            _ => Err(Error::InvalidShape(s.to_string())),
+        }
+    }
+}
+
+/// Starting point of a fitted peak
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PeakGuess {
+    pub center: f64,
+    pub height: f64,
+    pub fwhm: f64,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct FitOptions {
+    pub shape: Shape,
+    pub max_iterations: usize,
+    /// Fit is converged once relative decrease of residual sum of squares drops below this
+    pub tolerance: f64,
+}
+
+impl Default for FitOptions {
+    fn default() -> Self {
+        FitOptions {
+            shape: Shape::default(),
+            max_iterations: 200,
+            tolerance: 1e-9,
+        }
+    }
+}
+
+/// Fitted value with its standard uncertainty
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Param {
+    pub value: f64,
+    pub uncertainty: f64,
+}
+
+impl Display for Param {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let precision = f.precision().unwrap_or(3);
+        write!(
+            f,
+            "{:.*} ± {:.*}",
+            precision, self.value, precision, self.uncertainty
+        )
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct FittedPeak {
+    pub center: Param,
+    pub height: Param,
+    pub fwhm: Param,
+    /// Weight of Lorentzian in pseudo-Voigt, from 0 to 1
+    pub eta: Option<Param>,
+}
+
+impl FittedPeak {
+    /// Area under the peak, in units of height times x
+    pub fn area(&self) -> f64 {
+        let gaussian = self.height.value * self.fwhm.value * (PI / (4.0 * LN_2)).sqrt();
+        let lorentzian = self.height.value * self.fwhm.value * PI / 2.0;
+        match self.eta {
+            None => gaussian,
+            Some(eta) => eta.value * lorentzian + (1.0 - eta.value) * gaussian,
+        }
+    }
+}
+
+/// Peaks on a linear background fitted to data, with goodness of fit
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Fit {
+    pub shape: Shape,
+    pub peaks: Vec<FittedPeak>,
+    /// Background value at `origin`
+    pub offset: Param,
+    pub slope: Param,
+    /// Point background is expanded around, middle of fitted range
+    pub origin: f64,
+    /// Lowest and highest x of fitted data
+    pub x_range: Range<f64>,
+    /// Sum of squared residuals
+    pub chi_squared: f64,
+    /// Sum of squared residuals per degree of freedom, estimate of noise variance
+    pub reduced_chi_squared: f64,
+    pub r_squared: f64,
+    pub iterations: usize,
+    pub converged: bool,
+}
+
+impl Fit {
+    /// Value of fitted model at x
+    pub fn eval(&self, x: f64) -> f64 {
+        self.peaks
+            .iter()
+            .map(|peak| {
+                let eta = peak.eta.map_or(0.0, |eta| eta.value);
+                let p = [peak.center.value, peak.height.value, peak.fwhm.value, eta];
+                self.shape.eval(x, &p)
+            })
+            .sum::<f64>()
+            + self.background(x)
+    }
+
+    /// Value of fitted background at x
+    pub fn background(&self, x: f64) -> f64 {
+        self.offset.value + self.slope.value * (x - self.origin)
+    }
+}
+
+/// Linear background, followed by parameters of every peak
+fn model(shape: Shape, origin: f64, params: &[f64], x: f64) -> f64 {
+    let background = params[0] + params[1] * (x - origin);
+    params[2..]
+        .chunks(shape.params())
+        .map(|p| shape.eval(x, p))
+        .sum::<f64>()
+        + background
+}
+
+/// Keeps parameters physically meaningful after a step
+fn constrain(shape: Shape, params: &mut [f64]) {
+    for p in params[2..].chunks_mut(shape.params()) {
+        p[2] = p[2].abs().max(f64::EPSILON);
+        if shape == Shape::PseudoVoigt {
+            p[3] = p[3].clamp(0.0, 1.0);
+        }
+    }
+}
+
+/// Fits peaks starting from guesses to data with Levenberg-Marquardt. Points with NaN in
+/// either coordinate are skipped
+pub fn fit_peaks(x: &[f64], y: &[f64], guesses: &[PeakGuess], options: &FitOptions) -> Result<Fit> {
+    let (x, y): (Vec<f64>, Vec<f64>) = x
+        .iter()
+        .zip(y)
+        .filter(|(x, y)| !x.is_nan() && !y.is_nan())
+        .map(|(x, y)| (*x, *y))
+        .unzip();
+    let shape = options.shape;
+    let n_params = 2 + guesses.len() * shape.params();
+    if guesses.is_empty() {
+        return Err(Error::NoPeaks);
+    }
+    if x.len() <= n_params {
+        return Err(Error::NotEnoughPoints {
+            required: n_params + 1,
+            got: x.len(),
+        });
+    }
+
+    // Background starts as a line through the end points
+    let (first, last) = (0, x.len() - 1);
+    let origin = (x[first] + x[last]) / 2.0;
+    let slope = if x[last] != x[first] {
+        (y[last] - y[first]) / (x[last] - x[first])
+    } else {
+        0.0
+    };
+    let mut params = vec![(y[first] + y[last]) / 2.0, slope];
+    for guess in guesses {
+        let background = params[0] + slope * (guess.center - origin);
+        params.extend([guess.center, guess.height - background, guess.fwhm]);
+        if shape == Shape::PseudoVoigt {
+            params.push(0.5);
+        }
+    }
+    constrain(shape, &mut params);
+
+    let residuals = |params: &[f64]| -> Vec<f64> {
+        x.iter()
+            .zip(&y)
+            .map(|(&x, &y)| y - model(shape, origin, params, x))
+            .collect()
+    };
+    let sum_squares = |r: &[f64]| r.iter().map(|r| r * r).sum::<f64>();
+
+    let mut r = residuals(&params);
+    let mut chi_squared = sum_squares(&r);
+    let mut lambda = 1e-3;
+    let mut converged = false;
+    let mut iterations = 0;
+    let mut jtj = Matrix::zeros(n_params, n_params);
+    while iterations < options.max_iterations {
+        iterations += 1;
+        let jacobian = jacobian(shape, origin, &x, &params);
+        let mut gradient = vec![0.0; n_params];
+        for i in 0..n_params {
+            gradient[i] = jacobian[i].iter().zip(&r).map(|(j, r)| j * r).sum();
+            for k in 0..=i {
+                let v: f64 = jacobian[i]
+                    .iter()
+                    .zip(&jacobian[k])
+                    .map(|(a, b)| a * b)
+                    .sum();
+                jtj[(i, k)] = v;
+                jtj[(k, i)] = v;
+            }
+        }
+
+        // Raises damping until a step lowers residuals, which falls back to gradient descent
+        let mut improved = None;
+        while lambda < 1e12 {
+            let mut damped = jtj.clone();
+            for i in 0..n_params {
+                damped[(i, i)] += lambda * jtj[(i, i)].max(f64::EPSILON);
+            }
+            if let Ok(step) = solve_spd(&damped, &gradient) {
+                let mut candidate: Vec<f64> =
+                    params.iter().zip(&step).map(|(p, s)| p + s).collect();
+                constrain(shape, &mut candidate);
+                let candidate_r = residuals(&candidate);
+                let candidate_chi = sum_squares(&candidate_r);
+                if candidate_chi <= chi_squared {
+                    lambda = (lambda / 10.0).max(1e-12);
+                    improved = Some((candidate, candidate_r, candidate_chi));
+                    break;
+                }
+            }
+            lambda *= 10.0;
+        }
+        let Some((candidate, candidate_r, candidate_chi)) = improved else {
+            // No step helps, so this is as close as it gets
+            converged = true;
+            break;
+        };
+        let decrease = (chi_squared - candidate_chi) / chi_squared.max(f64::MIN_POSITIVE);
+        params = candidate;
+        r = candidate_r;
+        chi_squared = candidate_chi;
+        if decrease < options.tolerance {
+            converged = true;
+            break;
+        }
+    }
+
+    // Covariance of parameters is inverse of JᵀJ scaled by noise variance
+    let dof = (x.len() - n_params) as f64;
+    let reduced_chi_squared = chi_squared / dof;
+    let jacobian = jacobian(shape, origin, &x, &params);
+    for i in 0..n_params {
+        for k in 0..=i {
+            let v: f64 = jacobian[i]
+                .iter()
+                .zip(&jacobian[k])
+                .map(|(a, b)| a * b)
+                .sum();
+            jtj[(i, k)] = v;
+            jtj[(k, i)] = v;
+        }
+    }
+    let uncertainties: Vec<f64> = (0..n_params)
+        .map(|i| {
+            let mut unit = vec![0.0; n_params];
+            unit[i] = 1.0;
+            solve_spd(&jtj, &unit)
+                .map(|column| (column[i] * reduced_chi_squared).sqrt())
+                .unwrap_or(f64::NAN)
+        })
+        .collect();
+    let param = |i: usize| Param {
+        value: params[i],
+        uncertainty: uncertainties[i],
+    };
+
+    let mean = y.iter().sum::<f64>() / y.len() as f64;
+    let total = y.iter().map(|y| (y - mean).powi(2)).sum::<f64>();
+    let peaks = (0..guesses.len())
+        .map(|peak| {
+            let base = 2 + peak * shape.params();
+            FittedPeak {
+                center: param(base),
+                height: param(base + 1),
+                fwhm: param(base + 2),
+                eta: (shape == Shape::PseudoVoigt).then(|| param(base + 3)),
+            }
+        })
+        .collect();
+    Ok(Fit {
+        shape,
+        peaks,
+        offset: param(0),
+        slope: param(1),
+        origin,
+        x_range: x.iter().fold(f64::INFINITY..f64::NEG_INFINITY, |r, &x| {
+            r.start.min(x)..r.end.max(x)
+        }),
+        chi_squared,
+        reduced_chi_squared,
+        r_squared: 1.0 - chi_squared / total,
+        iterations,
+        converged,
+    })
+}
+
+/// Derivatives of model by every parameter at every point, a row per parameter. Computed with
+/// central differences, which is plenty for a handful of smooth parameters
+fn jacobian(shape: Shape, origin: f64, x: &[f64], params: &[f64]) -> Vec<Vec<f64>> {
+    let mut shifted = params.to_vec();
+    (0..params.len())
+        .map(|i| {
+            let h = f64::EPSILON.cbrt() * params[i].abs().max(1e-3);
+            shifted[i] = params[i] + h;
+            let upper: Vec<f64> = x
+                .iter()
+                .map(|&x| model(shape, origin, &shifted, x))
+                .collect();
+            shifted[i] = params[i] - h;
+            let row = x
+                .iter()
+                .zip(upper)
+                .map(|(&x, upper)| (upper - model(shape, origin, &shifted, x)) / (2.0 * h))
+                .collect();
+            shifted[i] = params[i];
+            row
+        })
+        .collect()
+}
+
+/// Value of x at a sub-pixel position, interpolated between neighbouring pixels
+fn at_position(x: &[f64], position: f64) -> f64 {
+    let position = position.clamp(0.0, (x.len() - 1) as f64);
+    let (lo, hi) = (position.floor() as usize, position.ceil() as usize);
+    x[lo] + (x[hi] - x[lo]) * (position - lo as f64)
+}
+
+/// Fits peaks found with `detect_peaks` in `y`, grouping the ones with overlapping
+/// surroundings into a single fit. Every group is fitted on a window a few widths around its
+/// peaks, with x being the horizontal axis value of every pixel. Groups that fail to fit are
+/// left out
+pub fn fit_detected(x: &[f64], y: &[f64], peaks: &[Peak], options: &FitOptions) -> Vec<Fit> {
+    let window = |peak: &Peak| {
+        let reach = peak.fwhm().max(1.0) * 3.0;
+        let start = (peak.position - reach).floor().max(0.0) as usize;
+        let end = ((peak.position + reach).ceil() as usize + 1).min(y.len());
+        start..end
+    };
+    let mut peaks: Vec<&Peak> = peaks.iter().collect();
+    peaks.sort_by(|a, b| a.position.total_cmp(&b.position));
+
+    let mut groups: Vec<(Range<usize>, Vec<&Peak>)> = Vec::new();
+    for peak in peaks {
+        let range = window(peak);
+        match groups.last_mut() {
+            Some((group, members)) if range.start < group.end => {
+                group.end = group.end.max(range.end);
+                members.push(peak);
+            }
+            _ => groups.push((range, vec![peak])),
+        }
+    }
+    groups
+        .into_iter()
+        .filter_map(|(range, members)| {
+            let guesses: Vec<PeakGuess> = members
+                .iter()
+                .map(|peak| PeakGuess {
+                    center: at_position(x, peak.position),
+                    height: peak.height,
+                    fwhm: (at_position(x, peak.position + peak.fwhm().max(1.0) / 2.0)
+                        - at_position(x, peak.position - peak.fwhm().max(1.0) / 2.0))
+                    .abs(),
+                })
+                .collect();
+            fit_peaks(&x[range.clone()], &y[range], &guesses, options).ok()
+        })
+        .collect()
+}
+
+impl Display for Fit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (idx, peak) in self.peaks.iter().enumerate() {
+            write!(
+                f,
+                "Peak {}: center {}, height {:.1}, FWHM {}, area {:.1}",
+                idx + 1,
+                peak.center,
+                peak.height,
+                peak.fwhm,
+                peak.area()
+            )?;
+            if let Some(eta) = peak.eta {
+                write!(f, ", Lorentzian fraction {eta}")?;
+            }
+            writeln!(f)?;
+        }
+        writeln!(
+            f,
+            "Background: {:.1} at {:.3}, slope {}",
+            self.offset, self.origin, self.slope
+        )?;
+        write!(
+            f,
+            "χ² = {:.4}, reduced χ² = {:.4}, R² = {:.6}, {} iterations{}",
+            self.chi_squared,
+            self.reduced_chi_squared,
+            self.r_squared,
+            self.iterations,
+            if self.converged {
+                ""
+            } else {
+                ", not converged"
+            }
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::peaks::{detect_peaks, PeakOptions};
+    use claims::{assert_matches, assert_ok};
+
+    /// Deterministic noise in range of ±amplitude
+    fn noise(i: usize, amplitude: f64) -> f64 {
+        ((i * 7919 % 101) as f64 / 50.0 - 1.0) * amplitude
+    }
+
+    fn synthetic(shape: Shape, peaks: &[[f64; 4]], len: usize) -> (Vec<f64>, Vec<f64>) {
+        let x: Vec<f64> = (0..len).map(|i| i as f64 * 0.5).collect();
+        let y = x
+            .iter()
+            .enumerate()
+            .map(|(i, &x)| {
+                peaks.iter().map(|p| shape.eval(x, p)).sum::<f64>() + 10.0 + 0.1 * x + noise(i, 0.5)
+            })
+            .collect();
+        (x, y)
+    }
+
+    #[test]
+    fn fit_every_shape() {
+        for (shape, truth) in [
+            (Shape::Gaussian, [50.0, 100.0, 6.0, 0.0]),
+            (Shape::Lorentzian, [50.0, 100.0, 6.0, 0.0]),
+            (Shape::PseudoVoigt, [50.0, 100.0, 6.0, 0.3]),
+        ] {
+            let (x, y) = synthetic(shape, &[truth], 200);
+            let guess = PeakGuess {
+                center: 48.0,
+                height: 80.0,
+                fwhm: 10.0,
+            };
+            let options = FitOptions {
+                shape,
+                ..Default::default()
+            };
+            let fit = assert_ok!(fit_peaks(&x, &y, &[guess], &options));
+            let peak = &fit.peaks[0];
+            assert!(fit.converged, "{shape:?}");
+            assert!((peak.center.value - 50.0).abs() < 0.05, "{shape:?} {fit}");
+            assert!((peak.height.value - 100.0).abs() < 1.0, "{shape:?} {fit}");
+            assert!((peak.fwhm.value - 6.0).abs() < 0.1, "{shape:?} {fit}");
+            assert!(peak.center.uncertainty > 0.0 && peak.center.uncertainty < 0.05);
+            assert!(fit.r_squared > 0.999);
+            assert!((fit.slope.value - 0.1).abs() < 0.01);
+            if let Some(eta) = peak.eta {
+                assert!((eta.value - 0.3).abs() < 0.05, "{fit}");
+            }
+        }
+    }
+
+    #[test]
+    fn eval_deserialized_fit() {
+        let (x, y) = synthetic(Shape::PseudoVoigt, &[[50.0, 100.0, 6.0, 0.3]], 200);
+        let guess = PeakGuess {
+            center: 48.0,
+            height: 80.0,
+            fwhm: 10.0,
+        };
+        let options = FitOptions {
+            shape: Shape::PseudoVoigt,
+            ..Default::default()
+        };
+        let fit = assert_ok!(fit_peaks(&x, &y, &[guess], &options));
+        let json = assert_ok!(serde_json::to_string(&fit));
+        let loaded: Fit = assert_ok!(serde_json::from_str(&json));
+        for x in [0.0, 45.0, 50.0, 53.5, 99.5] {
+            assert_eq!(loaded.eval(x), fit.eval(x));
+        }
+    }
+
+    #[test]
+    fn separate_overlapping_peaks() {
+        let truth = [[45.0, 80.0, 5.0, 0.0], [53.0, 50.0, 5.0, 0.0]];
+        let (x, y) = synthetic(Shape::Gaussian, &truth, 200);
+        let guesses = [
+            PeakGuess {
+                center: 44.0,
+                height: 80.0,
+                fwhm: 6.0,
+            },
+            PeakGuess {
+                center: 54.0,
+                height: 50.0,
+                fwhm: 6.0,
+            },
+        ];
+        let fit = assert_ok!(fit_peaks(&x, &y, &guesses, &FitOptions::default()));
+        for (peak, truth) in fit.peaks.iter().zip(truth) {
+            assert!((peak.center.value - truth[0]).abs() < 0.1, "{fit}");
+            assert!((peak.height.value - truth[1]).abs() < 1.5, "{fit}");
+        }
+    }
+
+    #[test]
+    fn fit_detected_peaks() {
+        let (x, y) = synthetic(Shape::Gaussian, &[[100.0, 100.0, 8.0, 0.0]], 400);
+        let peaks = detect_peaks(
+            &y,
+            &PeakOptions {
+                min_prominence: 20.0,
+                ..Default::default()
+            },
+        );
+        let fits = fit_detected(&x, &y, &peaks, &FitOptions::default());
+        assert_eq!(fits.len(), 1);
+        assert!((fits[0].peaks[0].center.value - 100.0).abs() < 0.05);
+        assert!((fits[0].peaks[0].fwhm.value - 8.0).abs() < 0.1);
+    }
+
+    #[test]
+    fn reject_too_few_points() {
+        let guess = PeakGuess {
+            center: 1.0,
+            height: 1.0,
+            fwhm: 1.0,
+        };
+        assert_matches!(
+            fit_peaks(
+                &[0.0, 1.0, 2.0],
+                &[0.0, 1.0, 0.0],
+                &[guess],
+                &FitOptions::default()
+            ),
+            Err(Error::NotEnoughPoints { .. })
+        );
+    }
+}
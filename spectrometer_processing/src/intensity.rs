@@ -0,0 +1,30 @@
+//! Conversion of inverted raw CCD output into intensity, where more light gives higher values
+
+use ccd_lcamv06::Frame;
+
+/// Intensity in counts above black level of the frame itself, which comes from its light
+/// shielded pixels. Pixels with less light than those end up slightly negative
+pub fn intensity(frame: &Frame) -> Vec<f64> {
+    let black = frame.black_level();
+    frame
+        .iter()
+        .map(|&pixel| black - f64::from(pixel))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ccd_lcamv06::{FRAME_PIXEL_COUNT, SHIELDED_PIXELS};
+
+    #[test]
+    fn flip_relative_to_black_level() {
+        let mut pixels = [400; FRAME_PIXEL_COUNT];
+        pixels[SHIELDED_PIXELS].fill(1000);
+        pixels[SHIELDED_PIXELS.start] = 1010;
+        pixels[SHIELDED_PIXELS.start + 1] = 990;
+        let intensity = intensity(&Frame::new(pixels));
+        assert_eq!(intensity[SHIELDED_PIXELS.start + 2], 0.0);
+        assert_eq!(intensity[1000], 600.0);
+    }
+}
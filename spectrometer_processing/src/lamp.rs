@@ -0,0 +1,337 @@
+//! Wavelength calibration from a spectrum of a lamp with known emission lines
+
+use crate::{
+    calibration::{CalibrationPoint, WavelengthCalibration},
+    error::{Error, Result},
+    peaks::{find_peaks, Peak},
+};
+use core::{
+    fmt::{self, Display},
+    ops::RangeInclusive,
+};
+
+/// Mercury and argon lines of Hg-Ar lamps, wavelengths in air, nm
+const HG_AR_LINES: &[f64] = &[
+    253.652, 296.728, 302.150, 313.155, 334.148, 365.015, 404.656, 407.783, 435.833, 546.074,
+    576.960, 579.066, 696.543, 706.722, 714.704, 727.294, 738.398, 750.387, 763.511, 772.376,
+    794.818, 800.616, 811.531, 826.452, 842.465, 852.144, 866.794, 912.297, 922.450,
+];
+
+/// Neon lines of Ne lamps, wavelengths in air, nm
+const NE_LINES: &[f64] = &[
+    540.056, 585.249, 588.190, 594.483, 597.553, 602.000, 607.434, 609.616, 614.306, 616.359,
+    621.728, 626.650, 630.479, 633.443, 638.299, 640.225, 650.653, 653.288, 659.895, 667.828,
+    671.704, 692.947, 703.241, 717.394, 724.517, 743.890, 747.244, 748.887, 753.577, 754.404,
+];
+
+/// Catalogue of emission lines, wavelengths in nm
+#[derive(Clone, Debug, PartialEq)]
+pub struct LineList {
+    lines: Vec<f64>,
+}
+
+impl LineList {
+    pub fn new(mut lines: Vec<f64>) -> LineList {
+        lines.sort_by(f64::total_cmp);
+        lines.dedup();
+        LineList { lines }
+    }
+
+    pub fn hg_ar() -> LineList {
+        LineList::new(HG_AR_LINES.to_vec())
+    }
+
+    pub fn ne() -> LineList {
+        LineList::new(NE_LINES.to_vec())
+    }
+
+    /// Parses a list with a wavelength in nm per line. Anything after the first column and
+    /// after `#` is ignored, so lists with relative intensities or comments can be used as is
+    pub fn parse(s: &str) -> Result<LineList> {
+        let mut lines = Vec::new();
+        for (idx, line) in s.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or_default();
+            let Some(wavelength) = line
+                .split(|c: char| c.is_whitespace() || c == ',')
+                .find(|s| !s.is_empty())
+            else {
+                continue;
+            };
+            let wavelength = wavelength
+                .parse()
+                .map_err(|_| Error::InvalidLineList(idx + 1))?;
+            lines.push(wavelength);
+        }
+        Ok(LineList::new(lines))
+    }
+
+    pub fn lines(&self) -> &[f64] {
+        &self.lines
+    }
+
+    fn nearest(&self, wavelength: f64) -> Option<f64> {
+        let idx = self.lines.partition_point(|&line| line < wavelength);
+        let after = self.lines.get(idx);
+        let before = idx.checked_sub(1).and_then(|idx| self.lines.get(idx));
+        match (before, after) {
+            (Some(&b), Some(&a)) if wavelength - b < a - wavelength => Some(b),
+            (_, Some(&a)) => Some(a),
+            (Some(&b), None) => Some(b),
+            (None, None) => None,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct LampOptions {
+    /// Order of fitted polynomial
+    pub order: usize,
+    /// Plausible range of absolute dispersion, nm per pixel
+    pub dispersion: RangeInclusive<f64>,
+    /// Maximum distance between detected peak and predicted line position, in pixels
+    pub tolerance: f64,
+    /// Minimum peak prominence as a fraction of spectrum dynamic range
+    pub min_prominence: f64,
+    /// Amount of most prominent peaks used to form hypotheses
+    pub max_peaks: usize,
+}
+
+impl Default for LampOptions {
+    fn default() -> Self {
+        LampOptions {
+            order: 2,
+            dispersion: 0.02..=1.0,
+            tolerance: 2.0,
+            min_prominence: 0.02,
+            max_peaks: 25,
+        }
+    }
+}
+
+/// Calibration found from a lamp spectrum, with metrics of how well it fits
+#[derive(Clone, Debug)]
+pub struct LampCalibration {
+    pub calibration: WavelengthCalibration,
+    pub detected_peaks: usize,
+    pub matched_lines: usize,
+    /// Fraction of pixels between outermost matched lines, extrapolation is less reliable
+    pub coverage: f64,
+    /// Largest absolute residual, nm
+    pub max_error: f64,
+}
+
+impl Display for LampCalibration {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{}", self.calibration)?;
+        writeln!(
+            f,
+            "Matched lines: {} of {} detected peaks",
+            self.matched_lines, self.detected_peaks
+        )?;
+        writeln!(f, "Max error: {:.4} nm", self.max_error)?;
+        write!(f, "Coverage: {:.1}% of pixels", self.coverage * 100.0)
+    }
+}
+
+/// Straight line through two peak/line pairs, used as RANSAC hypothesis
+struct LinearModel {
+    pixel: f64,
+    wavelength: f64,
+    dispersion: f64,
+}
+
+/// Finds peaks in `intensities` and matches them to `lines`. Every pair of prominent peaks
+/// is paired with every pair of lines with a plausible dispersion, matches that agree with
+/// the best of those hypotheses are used to fit a polynomial, which is then refined
+pub fn calibrate(
+    intensities: &[f64],
+    lines: &LineList,
+    options: &LampOptions,
+) -> Result<LampCalibration> {
+    if !(WavelengthCalibration::MIN_ORDER..=WavelengthCalibration::MAX_ORDER)
+        .contains(&options.order)
+    {
+        return Err(Error::InvalidOrder {
+            min: WavelengthCalibration::MIN_ORDER,
+            max: WavelengthCalibration::MAX_ORDER,
+        });
+    }
+    // An extra match over the minimum, otherwise residuals say nothing about quality
+    let required = options.order + 2;
+
+    let (min, max) = intensities
+        .iter()
+        .fold((f64::INFINITY, f64::NEG_INFINITY), |(lo, hi), &y| {
+            (lo.min(y), hi.max(y))
+        });
+    let peaks = find_peaks(intensities, (max - min) * options.min_prominence);
+    let mut strongest = peaks.clone();
+    strongest.sort_by(|a, b| b.prominence.total_cmp(&a.prominence));
+    strongest.truncate(options.max_peaks);
+    strongest.sort_by(|a, b| a.position.total_cmp(&b.position));
+
+    let mut best: Vec<CalibrationPoint> = Vec::new();
+    let mut best_error = f64::INFINITY;
+    for (i, p1) in strongest.iter().enumerate() {
+        for p2 in &strongest[i + 1..] {
+            for &l1 in lines.lines() {
+                for &l2 in lines.lines() {
+                    let dispersion = (l2 - l1) / (p2.position - p1.position);
+                    if !options.dispersion.contains(&dispersion.abs()) {
+                        continue;
+                    }
+                    let model = LinearModel {
+                        pixel: p1.position,
+                        wavelength: l1,
+                        dispersion,
+                    };
+                    let (matches, error) = match_lines(&strongest, lines, options.tolerance, |p| {
+                        (
+                            model.wavelength + model.dispersion * (p - model.pixel),
+                            model.dispersion.abs(),
+                        )
+                    });
+                    if matches.len() > best.len()
+                        || (matches.len() == best.len() && error < best_error)
+                    {
+                        best = matches;
+                        best_error = error;
+                    }
+                }
+            }
+        }
+    }
+    if best.len() < required {
+        return Err(Error::NoLineMatch {
+            required,
+            found: best.len(),
+        });
+    }
+
+    // Refining with a polynomial against all detected peaks, weaker lines help at the edges
+    let mut calibration = WavelengthCalibration::fit(best, options.order)?;
+    for _ in 0..5 {
+        let derivative = calibration.polynomial().derivative();
+        let (matches, _) = match_lines(&peaks, lines, options.tolerance, |p| {
+            (calibration.wavelength(p), derivative.eval(p).abs())
+        });
+        if matches.len() < required || matches == calibration.points() {
+            break;
+        }
+        calibration = WavelengthCalibration::fit(matches, options.order)?;
+    }
+
+    let points = calibration.points();
+    let span = points
+        .iter()
+        .map(|p| p.pixel)
+        .fold((f64::INFINITY, f64::NEG_INFINITY), |(lo, hi), p| {
+            (lo.min(p), hi.max(p))
+        });
+    Ok(LampCalibration {
+        detected_peaks: peaks.len(),
+        matched_lines: points.len(),
+        coverage: ((span.1 - span.0) / (intensities.len().max(2) - 1) as f64).clamp(0.0, 1.0),
+        max_error: calibration
+            .residuals()
+            .iter()
+            .fold(0.0, |acc: f64, r| acc.max(r.abs())),
+        calibration,
+    })
+}
+
+/// Pairs peaks with nearest lines predicted by `model`, which returns wavelength and local
+/// dispersion for a pixel position. Every line is used once, by the closest peak. Returns
+/// matches ordered by pixel and sum of their errors in pixels
+fn match_lines<F: Fn(f64) -> (f64, f64)>(
+    peaks: &[Peak],
+    lines: &LineList,
+    tolerance: f64,
+    model: F,
+) -> (Vec<CalibrationPoint>, f64) {
+    let mut matches: Vec<(CalibrationPoint, f64)> = Vec::new();
+    for peak in peaks {
+        let (predicted, dispersion) = model(peak.position);
+        let Some(line) = lines.nearest(predicted) else {
+            continue;
+        };
+        let error = (line - predicted).abs() / dispersion;
+        if error.is_nan() || error > tolerance {
+            continue;
+        }
+        let point = CalibrationPoint {
+            pixel: peak.position,
+            wavelength: line,
+        };
+        match matches.iter_mut().find(|(p, _)| p.wavelength == line) {
+            Some(existing) if existing.1 > error => *existing = (point, error),
+            Some(_) => {}
+            None => matches.push((point, error)),
+        }
+    }
+    let error = matches.iter().map(|(_, e)| e).sum();
+    (matches.into_iter().map(|(p, _)| p).collect(), error)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use claims::{assert_matches, assert_ok};
+
+    /// Synthetic lamp spectrum with Gaussian lines on a flat background
+    fn spectrum(lines: &[f64], wavelength: impl Fn(f64) -> f64) -> Vec<f64> {
+        (0..3694)
+            .map(|p| {
+                let w = wavelength(p as f64);
+                100.0
+                    + lines
+                        .iter()
+                        .map(|l| 1000.0 * (-(w - l).powi(2) / 0.5).exp())
+                        .sum::<f64>()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn parse_line_list() {
+        let list = assert_ok!(LineList::parse("# Hg\n546.074 1000\n\n435.833, strong\n"));
+        assert_eq!(list.lines(), &[435.833, 546.074]);
+        assert_matches!(
+            LineList::parse("546.074\nabc\n"),
+            Err(Error::InvalidLineList(2))
+        );
+    }
+
+    #[test]
+    fn calibrate_from_hg_ar_lamp() {
+        let truth = |p: f64| 340.0 + 0.17 * p - 4e-6 * p * p;
+        let data = spectrum(HG_AR_LINES, truth);
+        let result = assert_ok!(calibrate(
+            &data,
+            &LineList::hg_ar(),
+            &LampOptions::default()
+        ));
+        assert!(result.matched_lines >= 10);
+        assert!(result.calibration.rms_error() < 0.05);
+        for p in [0.0, 1800.0, 3693.0] {
+            assert!((result.calibration.wavelength(p) - truth(p)).abs() < 0.1);
+        }
+    }
+
+    #[test]
+    fn calibrate_mirrored_spectrometer() {
+        let truth = |p: f64| 760.0 - 0.06 * p;
+        let data = spectrum(NE_LINES, truth);
+        let result = assert_ok!(calibrate(&data, &LineList::ne(), &LampOptions::default()));
+        assert!((result.calibration.wavelength(1000.0) - truth(1000.0)).abs() < 0.1);
+    }
+
+    #[test]
+    fn fail_without_lines() {
+        let data = vec![100.0; 3694];
+        assert_matches!(
+            calibrate(&data, &LineList::hg_ar(), &LampOptions::default()),
+            Err(Error::NoLineMatch { .. })
+        );
+    }
+}
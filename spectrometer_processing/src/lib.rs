@@ -0,0 +1,46 @@
+//! Spectrum processing shared between CLI and SBC front ends
+
+pub mod absorbance;
+pub mod bands;
+pub mod baseline;
+pub mod calibration;
+pub mod chemometrics;
+pub mod concentration;
+pub mod dark;
+pub mod defects;
+pub mod error;
+pub mod filter;
+pub mod fit;
+pub mod intensity;
+pub mod lamp;
+pub mod library;
+pub(crate) mod linalg;
+pub mod peaks;
+pub mod polynomial;
+pub mod raman;
+pub mod resample;
+pub mod saturation;
+pub mod spikes;
+pub mod stats;
+pub mod store;
+#[cfg(test)]
+pub(crate) mod test_utils;
+
+pub use absorbance::{AbsorbanceMeasurement, ReferenceSpectrum};
+pub use bands::BandMetric;
+pub use baseline::Baseline;
+pub use calibration::{CalibrationPoint, WavelengthCalibration};
+pub use chemometrics::ChemometricModel;
+pub use concentration::ConcentrationCurve;
+pub use dark::DarkFrame;
+pub use defects::DefectMap;
+pub use filter::Filter;
+pub use fit::Fit;
+pub use library::Library;
+pub use polynomial::Polynomial;
+pub use raman::Laser;
+pub use resample::{Grid, Resampling, MAX_GRID_POINTS};
+pub use saturation::Saturation;
+pub use spikes::Despike;
+pub use stats::Statistics;
+pub use store::Store;
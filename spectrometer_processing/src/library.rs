@@ -0,0 +1,414 @@
+use crate::{
+    baseline::Baseline,
+    error::{Error, Result},
+    resample::{resample, Resampling},
+    store::{load, load_all, sanitize, save},
+};
+use core::fmt::{self, Display};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// Score of library spectrum similarity to a sample
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Metric {
+    /// Pearson correlation, from -1 to 1 with 1 being identical shape
+    #[default]
+    Correlation,
+    /// Euclidean distance, 0 for identical spectra
+    Euclidean,
+    /// Angle between spectra as vectors in radians, 0 for identical shape
+    SpectralAngle,
+}
+
+impl Metric {
+    /// Whether lower scores mean better match
+    pub fn is_distance(&self) -> bool {
+        !matches!(self, Metric::Correlation)
+    }
+
+    fn score(&self, a: &[f64], b: &[f64]) -> f64 {
+        match self {
+            Metric::Correlation => {
+                let n = a.len() as f64;
+                let (mean_a, mean_b) = (a.iter().sum::<f64>() / n, b.iter().sum::<f64>() / n);
+                let (mut cov, mut var_a, mut var_b) = (0.0, 0.0, 0.0);
+                for (x, y) in a.iter().zip(b) {
+                    cov += (x - mean_a) * (y - mean_b);
+                    var_a += (x - mean_a).powi(2);
+                    var_b += (y - mean_b).powi(2);
+                }
+                cov / (var_a * var_b).sqrt()
+            }
+            Metric::Euclidean => a
+                .iter()
+                .zip(b)
+                .map(|(x, y)| (x - y).powi(2))
+                .sum::<f64>()
+                .sqrt(),
+            Metric::SpectralAngle => {
+                let dot: f64 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+                let norm = |v: &[f64]| v.iter().map(|x| x * x).sum::<f64>().sqrt();
+                (dot / (norm(a) * norm(b))).clamp(-1.0, 1.0).acos()
+            }
+        }
+    }
+}
+
+/// Scaling applied to spectra before scoring, so that overall signal level does not matter
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Normalization {
+    None,
+    /// Highest value becomes 1
+    Max,
+    /// Sum of absolute values becomes 1
+    Area,
+    /// Euclidean norm becomes 1
+    #[default]
+    Vector,
+    /// Standard normal variate: zero mean and unit standard deviation
+    Snv,
+}
+
+impl Normalization {
+    pub fn apply(&self, data: &mut [f64]) {
+        let values = || data.iter().filter(|v| !v.is_nan());
+        let (offset, scale) = match self {
+            Normalization::None => return,
+            Normalization::Max => (0.0, values().fold(f64::NEG_INFINITY, |m, &v| m.max(v))),
+            Normalization::Area => (0.0, values().map(|v| v.abs()).sum()),
+            Normalization::Vector => (0.0, values().map(|v| v * v).sum::<f64>().sqrt()),
+            Normalization::Snv => {
+                let n = values().count() as f64;
+                let mean = values().sum::<f64>() / n;
+                let var = values().map(|v| (v - mean).powi(2)).sum::<f64>() / (n - 1.0);
+                (mean, var.sqrt())
+            }
+        };
+        if scale.is_finite() && scale != 0.0 {
+            data.iter_mut().for_each(|v| *v = (*v - offset) / scale);
+        }
+    }
+}
+
+/// Preprocessing and metric used to compare sample with library spectra
+#[derive(Clone, Debug, Default)]
+pub struct MatchOptions {
+    pub metric: Metric,
+    pub normalization: Normalization,
+    /// Removed from both sample and library spectra before normalisation
+    pub baseline: Option<Baseline>,
+    /// Interpolation of library spectra onto sample wavelengths
+    pub resampling: Resampling,
+}
+
+/// JSON has no NaN, so pixels that could not be measured are stored as null
+mod nan_as_null {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(values: &[f64], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(values.iter().map(|v| (!v.is_nan()).then_some(*v)))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<f64>, D::Error> {
+        let values = Vec::<Option<f64>>::deserialize(deserializer)?;
+        Ok(values.into_iter().map(|v| v.unwrap_or(f64::NAN)).collect())
+    }
+}
+
+/// Named spectrum of a known material
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct LibraryEntry {
+    pub name: String,
+    /// Wavelength of every value in nm, if spectrum was taken with calibrated CCD
+    pub wavelengths: Option<Vec<f64>>,
+    #[serde(with = "nan_as_null")]
+    pub values: Vec<f64>,
+}
+
+/// Library spectrum and how well it matches a sample
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Hit {
+    pub name: String,
+    pub score: f64,
+}
+
+/// Collection of reference spectra to identify samples with. Kept either as a directory with
+/// a JSON file per entry, or as a single JSON file with a list of entries
+#[derive(Clone, Debug, Default)]
+pub struct Library {
+    entries: Vec<LibraryEntry>,
+}
+
+impl Library {
+    /// Loads every entry from a directory or a single file, missing path is an empty library
+    pub fn load(path: &Path) -> Result<Library> {
+        let entries = if path.is_file() {
+            load(path)?.unwrap_or_default()
+        } else {
+            load_all(path)?
+        };
+        Ok(Library { entries })
+    }
+
+    /// Saves entry into a library, replacing entry with the same name. Paths of existing files
+    /// or ending with `.json` are single file libraries, anything else is a directory. In a
+    /// directory names that only differ in characters unsafe for file names would share a file,
+    /// so entry with such a name is refused instead of replacing another one
+    pub fn save_entry(path: &Path, entry: &LibraryEntry) -> Result<PathBuf> {
+        if path.is_file() || path.extension().is_some_and(|ext| ext == "json") {
+            let mut entries: Vec<LibraryEntry> = load(path)?.unwrap_or_default();
+            match entries
+                .iter_mut()
+                .find(|existing| existing.name == entry.name)
+            {
+                Some(existing) => *existing = entry.clone(),
+                None => entries.push(entry.clone()),
+            }
+            save(path, &entries)?;
+            return Ok(path.to_path_buf());
+        }
+        let path = path.join(format!("{}.json", sanitize(&entry.name)));
+        if let Some(existing) = load::<LibraryEntry>(&path)? {
+            if existing.name != entry.name {
+                return Err(Error::NameCollision {
+                    name: entry.name.clone(),
+                    existing: existing.name,
+                });
+            }
+        }
+        save(&path, entry)?;
+        Ok(path)
+    }
+
+    pub fn entries(&self) -> &[LibraryEntry] {
+        &self.entries
+    }
+
+    /// Scores sample against every entry, best matches first. If both sample and entry have
+    /// wavelengths, entry is resampled onto sample wavelengths, so spectra from different
+    /// instruments can be compared. Otherwise they should have the same length. Pixels that are
+    /// NaN in either spectrum are left out of both before preprocessing, which includes pixels
+    /// past the range of a resampled entry, so both are normalised over the same pixels
+    pub fn identify(
+        &self,
+        sample: &[f64],
+        wavelengths: Option<&[f64]>,
+        options: &MatchOptions,
+    ) -> Result<Vec<Hit>> {
+        let mut hits = Vec::with_capacity(self.entries.len());
+        for entry in &self.entries {
+            let values = match (wavelengths, &entry.wavelengths) {
+                (Some(target), Some(source)) => {
+                    resample(source, &entry.values, target, options.resampling)?
+                }
+                _ if entry.values.len() != sample.len() => {
+                    return Err(Error::LengthMismatch {
+                        name: entry.name.clone(),
+                        expected: sample.len(),
+                        got: entry.values.len(),
+                    });
+                }
+                _ => entry.values.clone(),
+            };
+            let (mut masked_sample, mut reference) = (sample.to_vec(), values);
+            for (x, y) in masked_sample.iter_mut().zip(reference.iter_mut()) {
+                if x.is_nan() || y.is_nan() {
+                    (*x, *y) = (f64::NAN, f64::NAN);
+                }
+            }
+            let masked_sample = preprocess(&masked_sample, options)?;
+            let reference = preprocess(&reference, options)?;
+            let (a, b): (Vec<f64>, Vec<f64>) = masked_sample
+                .iter()
+                .zip(&reference)
+                .filter(|(x, y)| !x.is_nan() && !y.is_nan())
+                .unzip();
+            hits.push(Hit {
+                name: entry.name.clone(),
+                score: options.metric.score(&a, &b),
+            });
+        }
+        // Undefined scores, like correlation with a flat line, go last
+        let key = |hit: &Hit| match hit.score {
+            score if score.is_nan() => f64::INFINITY,
+            score if options.metric.is_distance() => score,
+            score => -score,
+        };
+        hits.sort_by(|a, b| key(a).total_cmp(&key(b)));
+        Ok(hits)
+    }
+}
+
+fn preprocess(data: &[f64], options: &MatchOptions) -> Result<Vec<f64>> {
+    let mut data = data.to_vec();
+    if let Some(baseline) = &options.baseline {
+        baseline.remove(&mut data)?;
+    }
+    options.normalization.apply(&mut data);
+    Ok(data)
+}
+
+impl Display for Hit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:<32} {:>10.4}", self.name, self.score)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use claims::{assert_matches, assert_ok};
+    use std::fs;
+
+    fn gaussian(center: f64, len: usize) -> Vec<f64> {
+        (0..len)
+            .map(|x| (-(x as f64 - center).powi(2) / 50.0).exp())
+            .collect()
+    }
+
+    fn library() -> Library {
+        Library {
+            entries: vec![
+                LibraryEntry {
+                    name: "left".into(),
+                    wavelengths: None,
+                    values: gaussian(30.0, 100),
+                },
+                LibraryEntry {
+                    name: "right".into(),
+                    wavelengths: None,
+                    values: gaussian(70.0, 100),
+                },
+            ],
+        }
+    }
+
+    #[test]
+    fn rank_hits_with_every_metric() {
+        // Scaled and offset copy of right peak
+        let sample: Vec<f64> = gaussian(70.0, 100).iter().map(|y| y * 500.0).collect();
+        for metric in [
+            Metric::Correlation,
+            Metric::Euclidean,
+            Metric::SpectralAngle,
+        ] {
+            let options = MatchOptions {
+                metric,
+                ..Default::default()
+            };
+            let hits = assert_ok!(library().identify(&sample, None, &options));
+            assert_eq!(hits[0].name, "right", "{metric:?}");
+            assert_eq!(hits.len(), 2);
+        }
+        let options = MatchOptions::default();
+        let hits = assert_ok!(library().identify(&sample, None, &options));
+        assert!((hits[0].score - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn normalise_over_common_pixels() {
+        // Part of the peak was not measured, so entry has to be normalised without it too
+        let mut sample: Vec<f64> = gaussian(70.0, 100).iter().map(|y| y * 500.0).collect();
+        sample[60..70].fill(f64::NAN);
+        let options = MatchOptions {
+            metric: Metric::Euclidean,
+            ..Default::default()
+        };
+        let hits = assert_ok!(library().identify(&sample, None, &options));
+        assert_eq!(hits[0].name, "right");
+        assert!(hits[0].score < 1e-9, "{}", hits[0].score);
+    }
+
+    #[test]
+    fn match_spectra_from_other_instrument() {
+        // Library spectra were taken at 1 nm per pixel from 400 nm, sample at 0.5 nm from 410 nm
+        let mut library = library();
+        for entry in &mut library.entries {
+            entry.wavelengths = Some((0..100).map(|p| 400.0 + p as f64).collect());
+        }
+        let wavelengths: Vec<f64> = (0..150).map(|p| 410.0 + p as f64 * 0.5).collect();
+        let sample: Vec<f64> = wavelengths
+            .iter()
+            .map(|w| (-(w - 430.0).powi(2) / 50.0).exp())
+            .collect();
+        let hits =
+            assert_ok!(library.identify(&sample, Some(&wavelengths), &MatchOptions::default()));
+        assert_eq!(hits[0].name, "left");
+        assert!(hits[0].score > 0.999);
+    }
+
+    #[test]
+    fn normalise_spectra() {
+        let mut data = vec![1.0, 2.0, f64::NAN, 4.0];
+        Normalization::Max.apply(&mut data);
+        assert_eq!(data[3], 1.0);
+        assert!(data[2].is_nan());
+
+        let mut data = vec![1.0, 2.0, 3.0];
+        Normalization::Snv.apply(&mut data);
+        assert_eq!(data, vec![-1.0, 0.0, 1.0]);
+    }
+
+    #[test]
+    fn reject_mismatched_lengths() {
+        assert_matches!(
+            library().identify(&[1.0; 10], None, &MatchOptions::default()),
+            Err(Error::LengthMismatch { got: 100, .. })
+        );
+    }
+
+    #[test]
+    fn store_entries_in_directory() {
+        let dir = std::env::temp_dir().join(format!("spectrometer-library-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        assert!(assert_ok!(Library::load(&dir)).entries().is_empty());
+
+        let mut entry = library().entries[0].clone();
+        entry.name = "water, tap".into();
+        entry.values[5] = f64::NAN;
+        assert_ok!(Library::save_entry(&dir, &entry));
+        let loaded = assert_ok!(Library::load(&dir));
+        assert_eq!(loaded.entries().len(), 1);
+        assert!(loaded.entries()[0].values[5].is_nan());
+        assert!((loaded.entries()[0].values[0] - entry.values[0]).abs() < 1e-12);
+
+        // Same name replaces entry, while a name that maps onto the same file is refused
+        assert_ok!(Library::save_entry(&dir, &entry));
+        let colliding = LibraryEntry {
+            name: "water; tap".into(),
+            ..entry.clone()
+        };
+        assert_matches!(
+            Library::save_entry(&dir, &colliding),
+            Err(Error::NameCollision { .. })
+        );
+        assert_eq!(
+            assert_ok!(Library::load(&dir)).entries()[0].name,
+            entry.name
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn store_entries_in_file() {
+        let dir =
+            std::env::temp_dir().join(format!("spectrometer-library-file-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let path = dir.join("lib.json");
+
+        let mut entries = library().entries;
+        assert_eq!(assert_ok!(Library::save_entry(&path, &entries[0])), path);
+        assert_ok!(Library::save_entry(&path, &entries[1]));
+        entries[0].values[0] = 42.0;
+        assert_ok!(Library::save_entry(&path, &entries[0]));
+        assert!(path.is_file());
+
+        let loaded = assert_ok!(Library::load(&path));
+        assert_eq!(loaded.entries().len(), 2);
+        assert_eq!(loaded.entries()[0].values[0], 42.0);
+        assert_eq!(loaded.entries()[1].name, entries[1].name);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,155 @@
+//! Small dense linear algebra, enough for fitting a handful of parameters
+
+use crate::error::{Error, Result};
+use core::ops::{Index, IndexMut};
+
+/// Row-major dense matrix
+#[derive(Clone, Debug, PartialEq)]
+pub struct Matrix {
+    rows: usize,
+    cols: usize,
+    data: Vec<f64>,
+}
+
+impl Matrix {
+    pub fn zeros(rows: usize, cols: usize) -> Matrix {
+        Matrix {
+            rows,
+            cols,
+            data: vec![0.0; rows * cols],
+        }
+    }
+}
+
+impl Index<(usize, usize)> for Matrix {
+    type Output = f64;
+
+    fn index(&self, (row, col): (usize, usize)) -> &f64 {
+        &self.data[row * self.cols + col]
+    }
+}
+
+impl IndexMut<(usize, usize)> for Matrix {
+    fn index_mut(&mut self, (row, col): (usize, usize)) -> &mut f64 {
+        &mut self.data[row * self.cols + col]
+    }
+}
+
+/// Solves overdetermined system `a * x = b` in least squares sense with Householder QR, which
+/// avoids squaring condition number like normal equations do
+pub fn least_squares(mut a: Matrix, mut b: Vec<f64>) -> Result<Vec<f64>> {
+    let (m, n) = (a.rows, a.cols);
+    assert_eq!(m, b.len(), "Right hand side should have a value per row");
+    if m < n {
+        return Err(Error::SingularSystem);
+    }
+    let scale = a.data.iter().fold(0f64, |acc, v| acc.max(v.abs()));
+    let tolerance = scale * f64::EPSILON * (m as f64) * 16.0;
+
+    let mut diag = vec![0.0; n];
+    for k in 0..n {
+        let norm = (k..m).map(|i| a[(i, k)].powi(2)).sum::<f64>().sqrt();
+        if norm <= tolerance {
+            return Err(Error::SingularSystem);
+        }
+        // Reflecting onto the axis with opposite sign keeps subtraction below stable
+        let alpha = if a[(k, k)] > 0.0 { -norm } else { norm };
+        a[(k, k)] -= alpha;
+        let v_norm2: f64 = (k..m).map(|i| a[(i, k)].powi(2)).sum();
+        for j in k + 1..n {
+            let dot: f64 = (k..m).map(|i| a[(i, k)] * a[(i, j)]).sum();
+            let f = 2.0 * dot / v_norm2;
+            for i in k..m {
+                let v = a[(i, k)];
+                a[(i, j)] -= f * v;
+            }
+        }
+        let dot: f64 = (k..m).map(|i| a[(i, k)] * b[i]).sum();
+        let f = 2.0 * dot / v_norm2;
+        for i in k..m {
+            b[i] -= f * a[(i, k)];
+        }
+        diag[k] = alpha;
+    }
+
+    // Back substitution with upper triangle, diagonal was kept aside
+    let mut x = vec![0.0; n];
+    for k in (0..n).rev() {
+        let sum: f64 = (k + 1..n).map(|j| a[(k, j)] * x[j]).sum();
+        x[k] = (b[k] - sum) / diag[k];
+    }
+    Ok(x)
+}
+
+/// Solves `a * x = b` for symmetric positive definite `a` with Cholesky decomposition
+pub fn solve_spd(a: &Matrix, b: &[f64]) -> Result<Vec<f64>> {
+    let n = a.rows;
+    assert_eq!(n, a.cols, "Matrix should be square");
+    assert_eq!(n, b.len(), "Right hand side should have a value per row");
+    let mut l = Matrix::zeros(n, n);
+    for j in 0..n {
+        let diag = a[(j, j)] - (0..j).map(|k| l[(j, k)].powi(2)).sum::<f64>();
+        if diag <= 0.0 || !diag.is_finite() {
+            return Err(Error::SingularSystem);
+        }
+        l[(j, j)] = diag.sqrt();
+        for i in j + 1..n {
+            let sum: f64 = (0..j).map(|k| l[(i, k)] * l[(j, k)]).sum();
+            l[(i, j)] = (a[(i, j)] - sum) / l[(j, j)];
+        }
+    }
+    // Forward substitution with L, then backward with its transpose
+    let mut y = vec![0.0; n];
+    for i in 0..n {
+        let sum: f64 = (0..i).map(|k| l[(i, k)] * y[k]).sum();
+        y[i] = (b[i] - sum) / l[(i, i)];
+    }
+    let mut x = vec![0.0; n];
+    for i in (0..n).rev() {
+        let sum: f64 = (i + 1..n).map(|k| l[(k, i)] * x[k]).sum();
+        x[i] = (y[i] - sum) / l[(i, i)];
+    }
+    Ok(x)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use claims::{assert_err, assert_ok};
+
+    #[test]
+    fn solve_exact_system() {
+        let mut a = Matrix::zeros(2, 2);
+        a[(0, 0)] = 2.0;
+        a[(0, 1)] = 1.0;
+        a[(1, 0)] = 1.0;
+        a[(1, 1)] = 3.0;
+        let x = assert_ok!(least_squares(a, vec![5.0, 10.0]));
+        assert!((x[0] - 1.0).abs() < 1e-12);
+        assert!((x[1] - 3.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn solve_positive_definite_system() {
+        let mut a = Matrix::zeros(2, 2);
+        a[(0, 0)] = 4.0;
+        a[(0, 1)] = 2.0;
+        a[(1, 0)] = 2.0;
+        a[(1, 1)] = 3.0;
+        let x = assert_ok!(solve_spd(&a, &[8.0, 7.0]));
+        assert!((x[0] - 1.25).abs() < 1e-12);
+        assert!((x[1] - 1.5).abs() < 1e-12);
+        a[(1, 1)] = 1.0;
+        assert_err!(solve_spd(&a, &[8.0, 7.0]));
+    }
+
+    #[test]
+    fn detect_singular_system() {
+        let mut a = Matrix::zeros(3, 2);
+        for row in 0..3 {
+            a[(row, 0)] = 1.0;
+            a[(row, 1)] = 2.0;
+        }
+        assert_err!(least_squares(a, vec![1.0, 2.0, 3.0]));
+    }
+}
@@ -0,0 +1,287 @@
+/// Local maximum of a spectrum
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Peak {
+    /// Index of the highest sample, middle one for flat tops
+    pub index: usize,
+    /// Sub-pixel position of the maximum, interpolated as set in options
+    pub position: f64,
+    pub height: f64,
+    /// Height above the higher of the two lowest points separating peak from higher ground
+    pub prominence: f64,
+    /// Sub-pixel positions where peak crosses half of its prominence on either side
+    pub left: f64,
+    pub right: f64,
+    /// Sum over samples between the two bases, above the higher of the bases
+    pub area: f64,
+}
+
+impl Peak {
+    /// Full width at half maximum, in pixels
+    pub fn fwhm(&self) -> f64 {
+        self.right - self.left
+    }
+}
+
+/// Way to find peak position between samples
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Interpolation {
+    /// Parabola through the maximum and its neighbours
+    #[default]
+    Parabolic,
+    /// Parabola through logarithms, exact for Gaussian line shapes
+    Gaussian,
+    /// Mean position of samples above half maximum, weighted by their height over it
+    Centroid,
+}
+
+#[derive(Clone, Debug)]
+pub struct PeakOptions {
+    pub min_prominence: f64,
+    /// Minimum full width at half maximum, in pixels
+    pub min_width: f64,
+    /// Minimum absolute height
+    pub threshold: Option<f64>,
+    pub interpolation: Interpolation,
+}
+
+impl Default for PeakOptions {
+    fn default() -> Self {
+        PeakOptions {
+            min_prominence: 0.0,
+            min_width: 0.0,
+            threshold: None,
+            interpolation: Interpolation::default(),
+        }
+    }
+}
+
+/// Finds local maxima that stand out by at least `min_prominence`, ordered by position
+pub fn find_peaks(data: &[f64], min_prominence: f64) -> Vec<Peak> {
+    detect_peaks(
+        data,
+        &PeakOptions {
+            min_prominence,
+            ..Default::default()
+        },
+    )
+}
+
+/// Finds local maxima that pass all limits in options, ordered by position
+pub fn detect_peaks(data: &[f64], options: &PeakOptions) -> Vec<Peak> {
+    let mut peaks = Vec::new();
+    let mut i = 1;
+    while i + 1 < data.len() {
+        if data[i] <= data[i - 1] {
+            i += 1;
+            continue;
+        }
+        let mut end = i;
+        while end + 1 < data.len() && data[end + 1] == data[i] {
+            end += 1;
+        }
+        if end + 1 < data.len() && data[end + 1] < data[i] {
+            let index = (i + end) / 2;
+            if let Some(peak) = measure_peak(data, index, options) {
+                peaks.push(peak);
+            }
+        }
+        i = end + 1;
+    }
+    peaks
+}
+
+fn measure_peak(data: &[f64], index: usize, options: &PeakOptions) -> Option<Peak> {
+    let height = data[index];
+    if options
+        .threshold
+        .is_some_and(|threshold| height < threshold)
+    {
+        return None;
+    }
+    let (left_base, right_base) = bases(data, index);
+    let base = data[left_base].max(data[right_base]);
+    let prominence = height - base;
+    if prominence < options.min_prominence {
+        return None;
+    }
+
+    let half = height - prominence / 2.0;
+    let left = half_crossing(data, index, half, left_base, -1);
+    let right = half_crossing(data, index, half, right_base, 1);
+    if right - left < options.min_width {
+        return None;
+    }
+    let area = data[left_base..=right_base]
+        .iter()
+        .map(|y| (y - base).max(0.0))
+        .sum();
+    let position = match options.interpolation {
+        Interpolation::Parabolic => index as f64 + parabolic_offset(data, index),
+        Interpolation::Gaussian => index as f64 + gaussian_offset(data, index, base),
+        Interpolation::Centroid => centroid(data, left, right, half),
+    };
+    Some(Peak {
+        index,
+        position,
+        height,
+        prominence,
+        left,
+        right,
+        area,
+    })
+}
+
+/// Indices of the lowest points on each side before data rises above peak height
+fn bases(data: &[f64], index: usize) -> (usize, usize) {
+    let height = data[index];
+    let lowest = |acc: usize, idx: usize| if data[idx] < data[acc] { idx } else { acc };
+    let left = (0..index)
+        .rev()
+        .take_while(|&idx| data[idx] <= height)
+        .fold(index, lowest);
+    let right = (index + 1..data.len())
+        .take_while(|&idx| data[idx] <= height)
+        .fold(index, lowest);
+    (left, right)
+}
+
+/// Position where data drops to `level` walking from `index` towards `limit` in `step`
+/// direction, linearly interpolated between samples
+fn half_crossing(data: &[f64], index: usize, level: f64, limit: usize, step: isize) -> f64 {
+    let mut idx = index;
+    while idx != limit {
+        let next = idx.wrapping_add_signed(step);
+        if data[next] <= level {
+            let fraction = (data[idx] - level) / (data[idx] - data[next]);
+            return idx as f64 + step as f64 * fraction;
+        }
+        idx = next;
+    }
+    limit as f64
+}
+
+/// Offset of parabola vertex from `index`, within half a pixel
+fn parabolic_offset(data: &[f64], index: usize) -> f64 {
+    if index == 0 || index + 1 >= data.len() {
+        return 0.0;
+    }
+    vertex_offset(data[index - 1], data[index], data[index + 1])
+}
+
+/// Parabolic offset on logarithms of heights above `base`, falls back to plain parabola when
+/// neighbours don't rise above it
+fn gaussian_offset(data: &[f64], index: usize, base: f64) -> f64 {
+    if index == 0 || index + 1 >= data.len() {
+        return 0.0;
+    }
+    let (left, mid, right) = (
+        data[index - 1] - base,
+        data[index] - base,
+        data[index + 1] - base,
+    );
+    if left <= 0.0 || right <= 0.0 {
+        return parabolic_offset(data, index);
+    }
+    vertex_offset(left.ln(), mid.ln(), right.ln())
+}
+
+fn vertex_offset(left: f64, mid: f64, right: f64) -> f64 {
+    let curvature = left - 2.0 * mid + right;
+    if curvature == 0.0 {
+        return 0.0;
+    }
+    (0.5 * (left - right) / curvature).clamp(-0.5, 0.5)
+}
+
+fn centroid(data: &[f64], left: f64, right: f64, level: f64) -> f64 {
+    let first = left.ceil() as usize;
+    let last = (right.floor() as usize).min(data.len() - 1);
+    let (sum, weight) = (first..=last).fold((0.0, 0.0), |(sum, weight), idx| {
+        let w = (data[idx] - level).max(0.0);
+        (sum + w * idx as f64, weight + w)
+    });
+    if weight > 0.0 {
+        sum / weight
+    } else {
+        (left + right) / 2.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn gaussian(center: f64, sigma: f64) -> Vec<f64> {
+        (0..40)
+            .map(|x| 10.0 + 100.0 * (-(x as f64 - center).powi(2) / (2.0 * sigma * sigma)).exp())
+            .collect()
+    }
+
+    #[test]
+    fn find_prominent_peaks() {
+        let data = [0.0, 1.0, 0.0, 5.0, 4.0, 4.5, 0.0, 2.0, 2.0, 2.0, 1.0];
+        let peaks = find_peaks(&data, 0.8);
+        let indices: Vec<_> = peaks.iter().map(|p| p.index).collect();
+        assert_eq!(indices, vec![1, 3, 8]);
+        assert_eq!(peaks[1].prominence, 5.0);
+        assert_eq!(peaks[2].prominence, 1.0);
+        // Shoulder at 5 only rises by 0.5 above the dip between it and the main peak
+        assert_eq!(find_peaks(&data, 0.4).len(), 4);
+    }
+
+    #[test]
+    fn interpolate_peak_position() {
+        let data: Vec<_> = (0..20)
+            .map(|x| (-(x as f64 - 10.3).powi(2) / 8.0).exp())
+            .collect();
+        let peaks = find_peaks(&data, 0.5);
+        assert_eq!(peaks.len(), 1);
+        assert!((peaks[0].position - 10.3).abs() < 0.05);
+    }
+
+    #[test]
+    fn interpolate_gaussian_exactly() {
+        let data = gaussian(20.37, 2.0);
+        let options = PeakOptions {
+            interpolation: Interpolation::Gaussian,
+            ..Default::default()
+        };
+        let peaks = detect_peaks(&data, &options);
+        assert_eq!(peaks.len(), 1);
+        assert!((peaks[0].position - 20.37).abs() < 1e-3);
+
+        let options = PeakOptions {
+            interpolation: Interpolation::Centroid,
+            ..Default::default()
+        };
+        let peaks = detect_peaks(&data, &options);
+        assert!((peaks[0].position - 20.37).abs() < 0.1);
+    }
+
+    #[test]
+    fn measure_width_and_area() {
+        let sigma = 2.0;
+        let peak = detect_peaks(&gaussian(20.0, sigma), &PeakOptions::default())[0];
+        let fwhm = 2.0 * (2.0 * 2f64.ln()).sqrt() * sigma;
+        assert!((peak.fwhm() - fwhm).abs() < 0.1);
+        let area = 100.0 * sigma * (2.0 * std::f64::consts::PI).sqrt();
+        assert!((peak.area - area).abs() < 1.0);
+    }
+
+    #[test]
+    fn filter_by_width_and_threshold() {
+        let mut data = gaussian(10.0, 3.0);
+        data[30] = 60.0;
+        assert_eq!(detect_peaks(&data, &PeakOptions::default()).len(), 2);
+        let narrow_rejected = PeakOptions {
+            min_width: 2.0,
+            ..Default::default()
+        };
+        assert_eq!(detect_peaks(&data, &narrow_rejected)[0].index, 10);
+        let low_rejected = PeakOptions {
+            threshold: Some(80.0),
+            ..Default::default()
+        };
+        assert_eq!(detect_peaks(&data, &low_rejected).len(), 1);
+    }
+}
@@ -0,0 +1,120 @@
+use crate::{
+    error::{Error, Result},
+    linalg::{least_squares, Matrix},
+};
+use serde::{Deserialize, Serialize};
+
+/// Polynomial with coefficients in ascending order, `c[0] + c[1] * x + c[2] * x^2 + ...`
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Polynomial {
+    coefficients: Vec<f64>,
+}
+
+impl Polynomial {
+    pub fn new(coefficients: Vec<f64>) -> Polynomial {
+        Polynomial { coefficients }
+    }
+
+    pub fn coefficients(&self) -> &[f64] {
+        &self.coefficients
+    }
+
+    pub fn order(&self) -> usize {
+        self.coefficients.len().saturating_sub(1)
+    }
+
+    pub fn eval(&self, x: f64) -> f64 {
+        self.coefficients
+            .iter()
+            .rev()
+            .fold(0.0, |acc, c| acc * x + c)
+    }
+
+    pub fn derivative(&self) -> Polynomial {
+        Polynomial::new(
+            self.coefficients
+                .iter()
+                .enumerate()
+                .skip(1)
+                .map(|(power, c)| c * power as f64)
+                .collect(),
+        )
+    }
+
+    /// Least squares fit of polynomial of given order to points
+    pub fn fit(xs: &[f64], ys: &[f64], order: usize) -> Result<Polynomial> {
+        assert_eq!(xs.len(), ys.len(), "Every x should have a matching y");
+        let terms = order + 1;
+        let mut distinct: Vec<f64> = xs.to_vec();
+        distinct.sort_by(f64::total_cmp);
+        distinct.dedup();
+        if distinct.len() < terms {
+            return Err(Error::NotEnoughPoints {
+                required: terms,
+                got: distinct.len(),
+            });
+        }
+
+        // Pixel positions go up to thousands, so powers are computed on x mapped into [-1, 1]
+        let center = (distinct[0] + distinct[distinct.len() - 1]) / 2.0;
+        let half_span = (distinct[distinct.len() - 1] - distinct[0]) / 2.0;
+        let half_span = if half_span > 0.0 { half_span } else { 1.0 };
+
+        let mut a = Matrix::zeros(xs.len(), terms);
+        for (row, x) in xs.iter().enumerate() {
+            let t = (x - center) / half_span;
+            let mut power = 1.0;
+            for col in 0..terms {
+                a[(row, col)] = power;
+                power *= t;
+            }
+        }
+        let scaled = least_squares(a, ys.to_vec())?;
+        Ok(Polynomial::new(unscale(&scaled, center, half_span)))
+    }
+}
+
+/// Expands `sum(c[k] * ((x - center) / scale)^k)` back into coefficients of plain powers of x
+fn unscale(scaled: &[f64], center: f64, scale: f64) -> Vec<f64> {
+    let mut coefficients = vec![0.0; scaled.len()];
+    for (k, c) in scaled.iter().enumerate() {
+        let c = c / scale.powi(k as i32);
+        let mut binomial = 1.0;
+        for (j, coefficient) in coefficients.iter_mut().enumerate().take(k + 1) {
+            *coefficient += c * binomial * (-center).powi((k - j) as i32);
+            binomial = binomial * (k - j) as f64 / (j + 1) as f64;
+        }
+    }
+    coefficients
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use claims::{assert_err, assert_ok};
+
+    #[test]
+    fn evaluate_polynomial() {
+        let p = Polynomial::new(vec![1.0, 2.0, 3.0]);
+        assert_eq!(p.eval(2.0), 17.0);
+        assert_eq!(p.derivative().eval(2.0), 14.0);
+        assert_eq!(p.order(), 2);
+    }
+
+    #[test]
+    fn fit_exact_cubic() {
+        let expected = Polynomial::new(vec![350.0, 0.12, -3e-6, 2e-10]);
+        let xs: Vec<f64> = (0..10).map(|i| i as f64 * 400.0).collect();
+        let ys: Vec<f64> = xs.iter().map(|x| expected.eval(*x)).collect();
+        let p = assert_ok!(Polynomial::fit(&xs, &ys, 3));
+        for x in [0.0, 1234.5, 3693.0] {
+            assert!((p.eval(x) - expected.eval(x)).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn reject_underdetermined_fit() {
+        assert_err!(Polynomial::fit(&[1.0, 1.0, 2.0], &[1.0, 1.0, 2.0], 2));
+    }
+}
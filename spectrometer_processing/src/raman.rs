@@ -0,0 +1,111 @@
+use crate::error::{Error, Result};
+use core::fmt::{self, Display};
+use core::str::FromStr;
+use serde::{Deserialize, Serialize};
+
+/// Nanometres in a centimetre, converts wavelength in nm to wavenumber in cm⁻¹
+const NM_PER_CM: f64 = 1e7;
+
+/// Side of laser line that Raman shift is measured on
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Side {
+    /// Scattered light lost energy, shows up at longer wavelengths than laser
+    #[default]
+    Stokes,
+    /// Scattered light gained energy, shows up at shorter wavelengths than laser
+    AntiStokes,
+}
+
+/// Excitation laser of an instrument
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Laser {
+    wavelength: f64,
+}
+
+impl Laser {
+    pub fn new(wavelength: f64) -> Result<Laser> {
+        if !(wavelength.is_finite() && wavelength > 0.0) {
+            return Err(Error::InvalidWavelength(wavelength));
+        }
+        Ok(Laser { wavelength })
+    }
+
+    /// Wavelength in nm
+    pub fn wavelength(&self) -> f64 {
+        self.wavelength
+    }
+
+    /// Raman shift in cm⁻¹ of light at given wavelength in nm. Positive on requested side of
+    /// laser line, negative on the other one
+    pub fn raman_shift(&self, wavelength: f64, side: Side) -> f64 {
+        let stokes = NM_PER_CM / self.wavelength - NM_PER_CM / wavelength;
+        match side {
+            Side::Stokes => stokes,
+            Side::AntiStokes => -stokes,
+        }
+    }
+
+    /// Wavelength in nm of light with given Raman shift in cm⁻¹
+    pub fn wavelength_at(&self, shift: f64, side: Side) -> f64 {
+        let stokes = match side {
+            Side::Stokes => shift,
+            Side::AntiStokes => -shift,
+        };
+        NM_PER_CM / (NM_PER_CM / self.wavelength - stokes)
+    }
+
+    pub fn raman_shifts(&self, wavelengths: &[f64], side: Side) -> Vec<f64> {
+        wavelengths
+            .iter()
+            .map(|&wavelength| self.raman_shift(wavelength, side))
+            .collect()
+    }
+}
+
+impl FromStr for Laser {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let wavelength = s
+            .trim()
+            .trim_end_matches("nm")
+            .trim()
+            .parse()
+            .map_err(|_| Error::InvalidWavelength(f64::NAN))?;
+        Laser::new(wavelength)
+    }
+}
+
+impl Display for Laser {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} nm", self.wavelength)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use claims::{assert_err, assert_ok};
+
+    #[test]
+    fn convert_wavelength_to_raman_shift() {
+        let laser = assert_ok!(Laser::new(532.0));
+        // Silicon line at 520.7 cm⁻¹
+        let shift = laser.raman_shift(547.15, Side::Stokes);
+        assert!((shift - 520.5).abs() < 0.5, "{shift}");
+        assert!((laser.wavelength_at(shift, Side::Stokes) - 547.15).abs() < 1e-9);
+
+        let anti_stokes = laser.raman_shift(517.6, Side::AntiStokes);
+        assert!((anti_stokes - 522.9).abs() < 0.5, "{anti_stokes}");
+        assert!(laser.raman_shift(517.6, Side::Stokes) < 0.0);
+        assert_eq!(laser.raman_shift(532.0, Side::Stokes), 0.0);
+    }
+
+    #[test]
+    fn parse_laser() {
+        assert_eq!(assert_ok!("785nm".parse::<Laser>()).wavelength(), 785.0);
+        assert_eq!(assert_ok!("532.1".parse::<Laser>()).wavelength(), 532.1);
+        assert_err!("-5".parse::<Laser>());
+        assert_err!("green".parse::<Laser>());
+    }
+}
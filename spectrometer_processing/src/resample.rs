@@ -0,0 +1,237 @@
+use crate::error::{Error, Result};
+use core::fmt::{self, Display};
+use core::str::FromStr;
+use serde::{Deserialize, Serialize};
+
+/// Way to find values between measured points
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Resampling {
+    /// Straight line between neighbouring points, never overshoots
+    #[default]
+    Linear,
+    /// Natural cubic spline through all points, smooth but may ring around sharp peaks
+    CubicSpline,
+}
+
+/// Most points a grid may have, far more than any spectrum is worth resampling onto
+pub const MAX_GRID_POINTS: usize = 1_000_000;
+
+/// Evenly spaced points from start to stop, both included if stop lands on a step
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Grid {
+    start: f64,
+    stop: f64,
+    step: f64,
+}
+
+impl Grid {
+    pub fn new(start: f64, stop: f64, step: f64) -> Result<Grid> {
+        if !(start.is_finite() && stop.is_finite() && step.is_finite()) {
+            return Err(Error::InvalidGrid("Grid bounds should be finite".into()));
+        }
+        if step <= 0.0 || start >= stop {
+            return Err(Error::InvalidGrid(
+                "Grid step should be positive, with start below stop".into(),
+            ));
+        }
+        let grid = Grid { start, stop, step };
+        if grid.steps() >= MAX_GRID_POINTS as f64 {
+            return Err(Error::InvalidGrid(format!(
+                "Grid {grid} has more than {MAX_GRID_POINTS} points, use a larger step"
+            )));
+        }
+        Ok(grid)
+    }
+
+    /// Amount of whole steps from start to stop
+    fn steps(&self) -> f64 {
+        // Tolerates rounding, so that 400:700:0.1 ends at 700
+        ((self.stop - self.start) / self.step + 1e-9).floor()
+    }
+
+    pub fn len(&self) -> usize {
+        self.steps() as usize + 1
+    }
+
+    pub fn is_empty(&self) -> bool {
+        false
+    }
+
+    pub fn points(&self) -> Vec<f64> {
+        (0..self.len())
+            .map(|idx| self.start + idx as f64 * self.step)
+            .collect()
+    }
+}
+
+/// Parses grids written as `START:STOP:STEP`
+impl FromStr for Grid {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Grid> {
+        let invalid = || Error::InvalidGrid(format!("Expected START:STOP:STEP, got {s:?}"));
+        let values = s
+            .split(':')
+            .map(|v| v.trim().parse::<f64>().map_err(|_| invalid()))
+            .collect::<Result<Vec<_>>>()?;
+        match values[..] {
+            [start, stop, step] => Grid::new(start, stop, step),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+impl Display for Grid {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}:{}", self.start, self.stop, self.step)
+    }
+}
+
+/// Interpolates values measured at x onto target points. Measured points may come in either
+/// order, NaN ones are left out. Targets outside of measured range are NaN, since there is
+/// nothing to interpolate between
+pub fn resample(x: &[f64], y: &[f64], target: &[f64], method: Resampling) -> Result<Vec<f64>> {
+    let mut points: Vec<(f64, f64)> = x
+        .iter()
+        .zip(y)
+        .filter(|(x, y)| !x.is_nan() && !y.is_nan())
+        .map(|(x, y)| (*x, *y))
+        .collect();
+    points.sort_by(|a, b| a.0.total_cmp(&b.0));
+    points.dedup_by(|a, b| a.0 == b.0);
+    if points.len() < 2 {
+        return Err(Error::NotEnoughPoints {
+            required: 2,
+            got: points.len(),
+        });
+    }
+    let (x, y): (Vec<f64>, Vec<f64>) = points.into_iter().unzip();
+    let curvature = match method {
+        Resampling::Linear => None,
+        Resampling::CubicSpline => Some(spline_curvature(&x, &y)),
+    };
+
+    Ok(target
+        .iter()
+        .map(|&t| {
+            if !(x[0] <= t && t <= x[x.len() - 1]) {
+                return f64::NAN;
+            }
+            // Interval from x[i] to x[i + 1] that contains target
+            let i = x.partition_point(|&x| x <= t).clamp(1, x.len() - 1) - 1;
+            let h = x[i + 1] - x[i];
+            let a = (x[i + 1] - t) / h;
+            let b = 1.0 - a;
+            let linear = a * y[i] + b * y[i + 1];
+            match &curvature {
+                None => linear,
+                Some(m) => {
+                    linear + ((a.powi(3) - a) * m[i] + (b.powi(3) - b) * m[i + 1]) * h * h / 6.0
+                }
+            }
+        })
+        .collect())
+}
+
+/// Second derivative of natural cubic spline at every point, zero at both ends. Solves the
+/// tridiagonal system with Thomas algorithm
+fn spline_curvature(x: &[f64], y: &[f64]) -> Vec<f64> {
+    let n = x.len();
+    let mut m = vec![0.0; n];
+    if n < 3 {
+        return m;
+    }
+    let mut diag = vec![0.0; n];
+    let mut rhs = vec![0.0; n];
+    for i in 1..n - 1 {
+        let (h0, h1) = (x[i] - x[i - 1], x[i + 1] - x[i]);
+        diag[i] = 2.0 * (h0 + h1);
+        rhs[i] = 6.0 * ((y[i + 1] - y[i]) / h1 - (y[i] - y[i - 1]) / h0);
+        if i > 1 {
+            // Eliminates sub-diagonal h0 with previous row, whose super-diagonal is h0 as well
+            let factor = h0 / diag[i - 1];
+            diag[i] -= factor * h0;
+            rhs[i] -= factor * rhs[i - 1];
+        }
+    }
+    for i in (1..n - 1).rev() {
+        let h1 = x[i + 1] - x[i];
+        m[i] = (rhs[i] - h1 * m[i + 1]) / diag[i];
+    }
+    m
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use claims::{assert_err, assert_ok};
+
+    /// Non-uniform spacing, like pixels calibrated with a quadratic
+    fn nonuniform(len: usize) -> Vec<f64> {
+        (0..len)
+            .map(|p| 400.0 + p as f64 * 0.1 + (p as f64).powi(2) * 1e-4)
+            .collect()
+    }
+
+    #[test]
+    fn parse_grid() {
+        let grid = assert_ok!("400:700:0.1".parse::<Grid>());
+        assert_eq!(grid.len(), 3001);
+        let points = grid.points();
+        assert!((points[3000] - 700.0).abs() < 1e-9);
+        assert_err!("700:400:1".parse::<Grid>());
+        assert_err!("400:700:0".parse::<Grid>());
+        assert_err!("400:700".parse::<Grid>());
+        assert_err!("0:1e300:1e-300".parse::<Grid>());
+        assert_ok!(Grid::new(0.0, (MAX_GRID_POINTS - 1) as f64, 1.0));
+        assert_err!(Grid::new(0.0, MAX_GRID_POINTS as f64, 1.0));
+    }
+
+    #[test]
+    fn resample_linear_data_exactly() {
+        let x = nonuniform(200);
+        let y: Vec<f64> = x.iter().map(|x| 3.0 * x - 100.0).collect();
+        let grid = assert_ok!(Grid::new(401.0, 420.0, 0.5)).points();
+        for method in [Resampling::Linear, Resampling::CubicSpline] {
+            let resampled = assert_ok!(resample(&x, &y, &grid, method));
+            for (t, v) in grid.iter().zip(resampled) {
+                assert!((v - (3.0 * t - 100.0)).abs() < 1e-9, "{method:?} at {t}");
+            }
+        }
+    }
+
+    #[test]
+    fn spline_follows_smooth_curve() {
+        let x = nonuniform(100);
+        let y: Vec<f64> = x.iter().map(|x| (x / 2.0).sin()).collect();
+        let grid = assert_ok!(Grid::new(401.0, 409.0, 0.05)).points();
+        let error = |method| {
+            let resampled = assert_ok!(resample(&x, &y, &grid, method));
+            grid.iter()
+                .zip(resampled)
+                .map(|(t, v)| (v - (t / 2.0).sin()).abs())
+                .fold(0.0, f64::max)
+        };
+        assert!(error(Resampling::CubicSpline) < 1e-5);
+        assert!(error(Resampling::CubicSpline) < error(Resampling::Linear));
+    }
+
+    #[test]
+    fn leave_out_targets_past_edges() {
+        // Descending axis, like anti-Stokes shift
+        let x = vec![10.0, 8.0, 6.0, 4.0, 2.0];
+        let y = vec![5.0, 4.0, f64::NAN, 2.0, 1.0];
+        let resampled = assert_ok!(resample(
+            &x,
+            &y,
+            &[0.0, 2.0, 6.0, 9.0, 11.0],
+            Resampling::Linear
+        ));
+        assert!(resampled[0].is_nan());
+        assert_eq!(resampled[1], 1.0);
+        assert_eq!(resampled[2], 3.0);
+        assert_eq!(resampled[3], 4.5);
+        assert!(resampled[4].is_nan());
+        assert_err!(resample(&[1.0], &[1.0], &[1.0], Resampling::Linear));
+    }
+}
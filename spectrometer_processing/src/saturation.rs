@@ -0,0 +1,119 @@
+use ccd_lcamv06::{Frame, ACTIVE_PIXELS, CLIPPING_LEVEL, SATURATION_LEVEL};
+use core::fmt;
+use core::ops::Range;
+use serde::{Deserialize, Serialize};
+
+/// Pixels of a frame that were at either end of ADC range
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Saturation {
+    /// Pixel ranges at or below `SATURATION_LEVEL`, which got more light than CCD can measure
+    pub saturated: Vec<Range<usize>>,
+    /// Pixel ranges at or above `CLIPPING_LEVEL`
+    pub clipped: Vec<Range<usize>>,
+}
+
+impl Saturation {
+    pub fn analyze(frame: &Frame) -> Saturation {
+        Saturation {
+            saturated: runs(frame, |p| p <= SATURATION_LEVEL),
+            clipped: runs(frame, |p| p >= CLIPPING_LEVEL),
+        }
+    }
+
+    /// Amount of saturated pixels
+    pub fn saturated_count(&self) -> usize {
+        self.saturated.iter().map(|r| r.len()).sum()
+    }
+
+    /// Amount of clipped pixels
+    pub fn clipped_count(&self) -> usize {
+        self.clipped.iter().map(|r| r.len()).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.saturated.is_empty() && self.clipped.is_empty()
+    }
+}
+
+/// Contiguous ranges of active pixels matching predicate
+fn runs(frame: &Frame, pred: impl Fn(u16) -> bool) -> Vec<Range<usize>> {
+    let mut runs: Vec<Range<usize>> = Vec::new();
+    for (idx, &pixel) in frame
+        .iter()
+        .enumerate()
+        .take(ACTIVE_PIXELS.end)
+        .skip(ACTIVE_PIXELS.start)
+    {
+        if !pred(pixel) {
+            continue;
+        }
+        match runs.last_mut() {
+            Some(run) if run.end == idx => run.end += 1,
+            _ => runs.push(idx..idx + 1),
+        }
+    }
+    runs
+}
+
+fn write_ranges(f: &mut fmt::Formatter, ranges: &[Range<usize>]) -> fmt::Result {
+    for (i, range) in ranges.iter().enumerate() {
+        if i > 0 {
+            write!(f, ", ")?;
+        }
+        if range.len() == 1 {
+            write!(f, "{}", range.start)?;
+        } else {
+            write!(f, "{}..{}", range.start, range.end)?;
+        }
+    }
+    Ok(())
+}
+
+impl fmt::Display for Saturation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if !self.saturated.is_empty() {
+            write!(f, "{} saturated pixels at ", self.saturated_count())?;
+            write_ranges(f, &self.saturated)?;
+        }
+        if !self.clipped.is_empty() {
+            if !self.saturated.is_empty() {
+                write!(f, "; ")?;
+            }
+            write!(f, "{} clipped pixels at ", self.clipped_count())?;
+            write_ranges(f, &self.clipped)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ccd_lcamv06::FRAME_PIXEL_COUNT;
+
+    #[test]
+    fn find_saturated_ranges() {
+        let mut pixels = [3000; FRAME_PIXEL_COUNT];
+        pixels[100..105].fill(0);
+        pixels[200] = 10;
+        pixels[3000..3002].fill(u16::MAX);
+        // Dummy pixel, which never gets light
+        pixels[5] = 0;
+        let saturation = Saturation::analyze(&Frame::new(pixels));
+
+        assert_eq!(saturation.saturated, vec![100..105, 200..201]);
+        assert_eq!(saturation.clipped, vec![3000..3002]);
+        assert_eq!(saturation.saturated_count(), 6);
+        assert_eq!(
+            saturation.to_string(),
+            "6 saturated pixels at 100..105, 200; 2 clipped pixels at 3000..3002"
+        );
+    }
+
+    #[test]
+    fn report_nothing_for_clean_frame() {
+        let saturation = Saturation::analyze(&Frame::new([3000; FRAME_PIXEL_COUNT]));
+        assert!(saturation.is_empty());
+        assert_eq!(saturation.to_string(), "");
+    }
+}
@@ -0,0 +1,268 @@
+use crate::{
+    defects::interpolate_over,
+    error::{Error, Result},
+};
+use ccd_lcamv06::{Frame, ACTIVE_PIXELS};
+use core::str::FromStr;
+
+/// Least amount of frames for batch methods to tell spikes from signal
+pub const MIN_BATCH: usize = 3;
+
+/// Way to find and replace spikes left by cosmic rays and readout glitches. Thresholds are in
+/// robust standard deviations, estimated with median absolute deviation and never taken below
+/// one count, since raw values are whole
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Despike {
+    /// Replaces values that deviate from per-pixel median of a batch with that median
+    Median { threshold: f64 },
+    /// Repeatedly drops values outside of threshold around per-pixel mean of a batch, and
+    /// replaces them with mean of the rest
+    SigmaClip { threshold: f64 },
+    /// Finds pixels in a single frame that stand out from both neighbours by discrete
+    /// Laplacian, and interpolates over them. Only extra charge is considered a spike, which
+    /// makes pixels lower than neighbours since output is inverted
+    Laplacian { threshold: f64 },
+}
+
+impl Despike {
+    /// Whether method needs a batch of at least `MIN_BATCH` frames of the same scene
+    pub fn needs_batch(&self) -> bool {
+        !matches!(self, Despike::Laplacian { .. })
+    }
+
+    /// Replaces spikes in raw pixel values, returns amount of replaced values
+    pub fn apply(&self, frames: &mut [Frame]) -> Result<usize> {
+        if self.needs_batch() && frames.len() < MIN_BATCH {
+            return Err(Error::NotEnoughFrames {
+                required: MIN_BATCH,
+                got: frames.len(),
+            });
+        }
+        match *self {
+            Despike::Median { threshold } => Ok(per_pixel(frames, |values| {
+                clip_to_median(values, threshold)
+            })),
+            Despike::SigmaClip { threshold } => {
+                Ok(per_pixel(frames, |values| sigma_clip(values, threshold)))
+            }
+            Despike::Laplacian { threshold } => Ok(frames
+                .iter_mut()
+                .map(|frame| laplacian(frame, threshold))
+                .sum()),
+        }
+    }
+}
+
+/// Runs replacement over values of every pixel across frames
+fn per_pixel(frames: &mut [Frame], mut replace: impl FnMut(&mut [f64]) -> usize) -> usize {
+    let mut values = vec![0.0; frames.len()];
+    let mut replaced = 0;
+    for pixel in 0..frames[0].len() {
+        values
+            .iter_mut()
+            .zip(frames.iter())
+            .for_each(|(v, frame)| *v = f64::from(frame[pixel]));
+        let count = replace(&mut values);
+        if count > 0 {
+            for (frame, v) in frames.iter_mut().zip(&values) {
+                frame[pixel] = v.round() as u16;
+            }
+        }
+        replaced += count;
+    }
+    replaced
+}
+
+fn median(values: &mut [f64]) -> f64 {
+    let mid = values.len() / 2;
+    *values.select_nth_unstable_by(mid, f64::total_cmp).1
+}
+
+/// Median and robust standard deviation
+fn median_spread(values: &[f64]) -> (f64, f64) {
+    let mut scratch = values.to_vec();
+    let center = median(&mut scratch);
+    scratch
+        .iter_mut()
+        .zip(values)
+        .for_each(|(s, v)| *s = (v - center).abs());
+    (center, (1.4826 * median(&mut scratch)).max(1.0))
+}
+
+fn clip_to_median(values: &mut [f64], threshold: f64) -> usize {
+    let (center, spread) = median_spread(values);
+    let mut replaced = 0;
+    for v in values.iter_mut() {
+        if (*v - center).abs() > threshold * spread {
+            *v = center;
+            replaced += 1;
+        }
+    }
+    replaced
+}
+
+fn sigma_clip(values: &mut [f64], threshold: f64) -> usize {
+    let mut kept = vec![true; values.len()];
+    let kept_mean = |kept: &[bool]| {
+        let (sum, n) = values
+            .iter()
+            .zip(kept)
+            .filter(|(_, &k)| k)
+            .fold((0.0, 0.0), |(sum, n), (v, _)| (sum + v, n + 1.0));
+        sum / n
+    };
+    let mean = loop {
+        let n = kept.iter().filter(|&&k| k).count() as f64;
+        let kept_values = || values.iter().zip(&kept).filter(|(_, &k)| k).map(|(v, _)| v);
+        let mean = kept_mean(&kept);
+        let std = (kept_values().map(|v| (v - mean).powi(2)).sum::<f64>() / (n - 1.0))
+            .sqrt()
+            .max(1.0);
+        let mut changed = false;
+        for (v, k) in values.iter().zip(kept.iter_mut()) {
+            if *k && (v - mean).abs() > threshold * std {
+                *k = false;
+                changed = true;
+            }
+        }
+        if !changed {
+            break mean;
+        }
+        // Two values are not enough to tell which one is off. Mean of what is left still
+        // replaces rejected ones, so that they don't leak into their own replacement
+        match kept.iter().filter(|&&k| k).count() {
+            0 => break mean,
+            count if count < MIN_BATCH => break kept_mean(&kept),
+            _ => {}
+        }
+    };
+    let mut replaced = 0;
+    for (v, k) in values.iter_mut().zip(&kept) {
+        if !k {
+            *v = mean;
+            replaced += 1;
+        }
+    }
+    replaced
+}
+
+fn laplacian(frame: &mut Frame, threshold: f64) -> usize {
+    let data: Vec<f64> = frame[ACTIVE_PIXELS].iter().map(|&p| f64::from(p)).collect();
+    // Positive where pixel is below both neighbours, edges have only one and are left out
+    let mut response = vec![0.0; data.len()];
+    for i in 1..data.len() - 1 {
+        response[i] = data[i - 1] + data[i + 1] - 2.0 * data[i];
+    }
+    let (center, spread) = median_spread(&response[1..data.len() - 1]);
+    let flagged: Vec<bool> = response
+        .iter()
+        .map(|&r| r - center > threshold * spread)
+        .collect();
+    let replaced = flagged.iter().filter(|&&f| f).count();
+    if replaced > 0 {
+        let mut data = data;
+        interpolate_over(&mut data, &flagged);
+        for (pixel, v) in frame[ACTIVE_PIXELS].iter_mut().zip(data) {
+            *pixel = v.round() as u16;
+        }
+    }
+    replaced
+}
+
+/// Parses methods written as `median:THRESHOLD`, `sigma:THRESHOLD` or `laplacian:THRESHOLD`
+impl FromStr for Despike {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Despike> {
+        let invalid = || Error::InvalidDespike(format!("Unknown spike removal method {s:?}"));
+        let (name, threshold) = s.trim().split_once(':').ok_or_else(invalid)?;
+        let threshold: f64 = threshold.trim().parse().map_err(|_| invalid())?;
+        if threshold.is_nan() || threshold <= 0.0 {
+            return Err(Error::InvalidDespike(
+                "Spike threshold should be positive".into(),
+            ));
+        }
+        match name {
+            "median" => Ok(Despike::Median { threshold }),
+            "sigma" => Ok(Despike::SigmaClip { threshold }),
+            "laplacian" => Ok(Despike::Laplacian { threshold }),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ccd_lcamv06::FRAME_PIXEL_COUNT;
+    use claims::{assert_matches, assert_ok, assert_ok_eq};
+
+    /// Smooth broad peak with a bit of noise, varying between frames
+    fn frames(count: usize) -> Vec<Frame> {
+        (0..count)
+            .map(|n| {
+                let mut pixels = [0; FRAME_PIXEL_COUNT];
+                for (idx, p) in pixels.iter_mut().enumerate() {
+                    let peak = 1000.0 * (-((idx as f64 - 1800.0) / 40.0).powi(2)).exp();
+                    *p = (3000.0 - peak) as u16 + ((idx * 7 + n * 3) % 5) as u16;
+                }
+                Frame::new(pixels)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn replace_spikes_across_batch() {
+        for method in [
+            Despike::Median { threshold: 5.0 },
+            Despike::SigmaClip { threshold: 2.0 },
+        ] {
+            let mut batch = frames(7);
+            let clean = batch.clone();
+            batch[2][1000] = 200;
+            batch[4][1801] = 100;
+            assert_ok_eq!(method.apply(&mut batch), 2, "{method:?}");
+            assert!(batch[2][1000].abs_diff(clean[2][1000]) < 5);
+            assert!(batch[4][1801].abs_diff(clean[4][1801]) < 5);
+            assert_eq!(batch[0], clean[0]);
+        }
+        assert_matches!(
+            Despike::Median { threshold: 5.0 }.apply(&mut frames(2)),
+            Err(Error::NotEnoughFrames { .. })
+        );
+    }
+
+    #[test]
+    fn leave_rejected_values_out_of_replacement() {
+        let mut values = [100.0, 101.0, 10000.0];
+        assert_eq!(sigma_clip(&mut values, 1.0), 1);
+        assert_eq!(values, [100.0, 101.0, 100.5]);
+    }
+
+    #[test]
+    fn replace_spikes_in_single_frame() {
+        let mut batch = frames(1);
+        let clean = batch.clone();
+        batch[0][1000] = 500;
+        batch[0][1001] = 700;
+        let method = Despike::Laplacian { threshold: 10.0 };
+        assert_ok_eq!(method.apply(&mut batch), 2);
+        assert!(batch[0][1000].abs_diff(clean[0][1000]) < 5);
+        // Peak itself is left as is
+        assert_eq!(batch[0][1800], clean[0][1800]);
+    }
+
+    #[test]
+    fn parse_methods() {
+        assert_eq!(
+            assert_ok!("median:5".parse::<Despike>()),
+            Despike::Median { threshold: 5.0 }
+        );
+        assert_eq!(
+            assert_ok!("laplacian:8".parse::<Despike>()),
+            Despike::Laplacian { threshold: 8.0 }
+        );
+        assert!("sigma:-1".parse::<Despike>().is_err());
+        assert!("median".parse::<Despike>().is_err());
+    }
+}
@@ -0,0 +1,141 @@
+//! Per pixel statistics across a batch of spectra
+
+/// Statistics accumulated one spectrum at a time with Welford's algorithm, so batches of any
+/// size take memory of a few spectra and stay numerically stable
+#[derive(Clone, Debug, PartialEq)]
+pub struct Statistics {
+    count: usize,
+    mean: Vec<f64>,
+    /// Sum of squared differences from the mean
+    m2: Vec<f64>,
+    min: Vec<f64>,
+    max: Vec<f64>,
+}
+
+impl Statistics {
+    /// Empty statistics for spectra of `len` pixels
+    pub fn new(len: usize) -> Statistics {
+        Statistics {
+            count: 0,
+            mean: vec![0.0; len],
+            m2: vec![0.0; len],
+            min: vec![f64::INFINITY; len],
+            max: vec![f64::NEG_INFINITY; len],
+        }
+    }
+
+    pub fn from_spectra<'a>(
+        len: usize,
+        spectra: impl IntoIterator<Item = &'a [f64]>,
+    ) -> Statistics {
+        let mut stats = Statistics::new(len);
+        spectra
+            .into_iter()
+            .for_each(|spectrum| stats.push(spectrum));
+        stats
+    }
+
+    pub fn push(&mut self, spectrum: &[f64]) {
+        assert_eq!(
+            spectrum.len(),
+            self.mean.len(),
+            "Spectra should have the same length"
+        );
+        self.count += 1;
+        let count = self.count as f64;
+        for (idx, &value) in spectrum.iter().enumerate() {
+            let delta = value - self.mean[idx];
+            self.mean[idx] += delta / count;
+            self.m2[idx] += delta * (value - self.mean[idx]);
+            self.min[idx] = self.min[idx].min(value);
+            self.max[idx] = self.max[idx].max(value);
+        }
+    }
+
+    /// Amount of accumulated spectra
+    pub fn count(&self) -> usize {
+        self.count
+    }
+
+    pub fn mean(&self) -> &[f64] {
+        &self.mean
+    }
+
+    pub fn min(&self) -> &[f64] {
+        &self.min
+    }
+
+    pub fn max(&self) -> &[f64] {
+        &self.max
+    }
+
+    /// Sample variance, NaN with less than two spectra
+    pub fn variance(&self) -> Vec<f64> {
+        let dof = self.count as f64 - 1.0;
+        self.m2
+            .iter()
+            .map(|m2| if dof > 0.0 { m2 / dof } else { f64::NAN })
+            .collect()
+    }
+
+    /// Sample standard deviation of a single spectrum
+    pub fn std_dev(&self) -> Vec<f64> {
+        self.variance().into_iter().map(f64::sqrt).collect()
+    }
+
+    /// Standard error of the mean, uncertainty of averaged spectrum
+    pub fn std_error(&self) -> Vec<f64> {
+        let count = (self.count as f64).sqrt();
+        self.std_dev().into_iter().map(|sd| sd / count).collect()
+    }
+
+    /// Signal to noise ratio of a single spectrum, mean over standard deviation
+    pub fn snr(&self) -> Vec<f64> {
+        self.mean
+            .iter()
+            .zip(self.std_dev())
+            .map(|(mean, sd)| mean / sd)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accumulate_statistics() {
+        let spectra = [
+            [2.0, 10.0],
+            [4.0, 10.0],
+            [4.0, 10.0],
+            [4.0, 10.0],
+            [5.0, 10.0],
+        ];
+        let stats = Statistics::from_spectra(2, spectra.iter().map(|s| &s[..]));
+        assert_eq!(stats.count(), 5);
+        assert_eq!(stats.mean(), &[3.8, 10.0]);
+        assert_eq!(stats.min(), &[2.0, 10.0]);
+        assert_eq!(stats.max(), &[5.0, 10.0]);
+        let variance = stats.variance();
+        assert!((variance[0] - 1.2).abs() < 1e-12);
+        assert_eq!(variance[1], 0.0);
+        assert!((stats.std_error()[0] - (1.2f64 / 5.0).sqrt()).abs() < 1e-12);
+        assert!(stats.snr()[1].is_infinite());
+    }
+
+    #[test]
+    fn stay_stable_with_large_offset() {
+        let mut stats = Statistics::new(1);
+        for value in [1e9 + 4.0, 1e9 + 7.0, 1e9 + 13.0, 1e9 + 16.0] {
+            stats.push(&[value]);
+        }
+        assert!((stats.variance()[0] - 30.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn need_two_spectra_for_variance() {
+        let stats = Statistics::from_spectra(1, [&[1.0][..]]);
+        assert!(stats.std_dev()[0].is_nan());
+    }
+}
@@ -0,0 +1,363 @@
+use crate::{
+    absorbance::ReferenceSpectrum,
+    calibration::WavelengthCalibration,
+    chemometrics::ChemometricModel,
+    concentration::ConcentrationCurve,
+    dark::DarkFrame,
+    defects::DefectMap,
+    error::{Error, Result},
+    raman::Laser,
+};
+use ccd_lcamv06::ExposureTime;
+use serde::{de::DeserializeOwned, Serialize};
+use std::{
+    fs,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+};
+
+/// Directory with per instrument data, like calibrations. Every CCD gets a subdirectory named
+/// after its serial number, data is kept as JSON files inside of it. Data that depends on
+/// exposure time is kept in a nested directory, with a file per exposure
+#[derive(Clone, Debug)]
+pub struct Store {
+    root: PathBuf,
+}
+
+/// Environment variable with store location, overrides the default one
+pub const DATA_DIR_ENV: &str = "SPECTROMETER_DATA_DIR";
+
+const WAVELENGTH_CALIBRATION: &str = "wavelength_calibration.json";
+const DEFECT_MAP: &str = "defects.json";
+const LASER: &str = "laser.json";
+const LIBRARY: &str = "library";
+const DARK_FRAMES: &str = "dark";
+const REFERENCE_SPECTRA: &str = "reference";
+const CONCENTRATION_CURVES: &str = "curves";
+const MODELS: &str = "models";
+
+fn exposure_file(exposure: ExposureTime) -> String {
+    format!("{}ms.json", exposure.as_millis())
+}
+
+/// Keeps only characters that are safe in a file name on any platform
+pub(crate) fn sanitize(name: &str) -> String {
+    name.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+impl Store {
+    pub fn new(root: impl Into<PathBuf>) -> Store {
+        Store { root: root.into() }
+    }
+
+    /// Location every front end uses unless told otherwise: `SPECTROMETER_DATA_DIR` if set, or
+    /// `spectrometer` in user data directory. None if platform has no data directory
+    pub fn default_root() -> Option<PathBuf> {
+        std::env::var_os(DATA_DIR_ENV)
+            .map(PathBuf::from)
+            .or_else(|| dirs::data_dir().map(|dir| dir.join("spectrometer")))
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Default location of spectral library, shared between instruments
+    pub fn library_dir(&self) -> PathBuf {
+        self.root.join(LIBRARY)
+    }
+
+    /// Directory for a CCD with given serial number, which is sanitized to be a safe file name
+    pub fn instrument_dir(&self, serial: &str) -> PathBuf {
+        self.root.join(sanitize(serial))
+    }
+
+    pub fn wavelength_calibration(&self, serial: &str) -> Result<Option<WavelengthCalibration>> {
+        load(&self.instrument_dir(serial).join(WAVELENGTH_CALIBRATION))
+    }
+
+    pub fn save_wavelength_calibration(
+        &self,
+        serial: &str,
+        calibration: &WavelengthCalibration,
+    ) -> Result<()> {
+        save(
+            &self.instrument_dir(serial).join(WAVELENGTH_CALIBRATION),
+            calibration,
+        )
+    }
+
+    pub fn defect_map(&self, serial: &str) -> Result<Option<DefectMap>> {
+        load(&self.instrument_dir(serial).join(DEFECT_MAP))
+    }
+
+    pub fn save_defect_map(&self, serial: &str, defects: &DefectMap) -> Result<()> {
+        save(&self.instrument_dir(serial).join(DEFECT_MAP), defects)
+    }
+
+    pub fn laser(&self, serial: &str) -> Result<Option<Laser>> {
+        load(&self.instrument_dir(serial).join(LASER))
+    }
+
+    pub fn save_laser(&self, serial: &str, laser: &Laser) -> Result<()> {
+        save(&self.instrument_dir(serial).join(LASER), laser)
+    }
+
+    pub fn dark_frame(&self, serial: &str, exposure: ExposureTime) -> Result<Option<DarkFrame>> {
+        load(
+            &self
+                .instrument_dir(serial)
+                .join(DARK_FRAMES)
+                .join(exposure_file(exposure)),
+        )
+    }
+
+    pub fn save_dark_frame(&self, serial: &str, dark: &DarkFrame) -> Result<()> {
+        save(
+            &self
+                .instrument_dir(serial)
+                .join(DARK_FRAMES)
+                .join(exposure_file(dark.exposure())),
+            dark,
+        )
+    }
+
+    pub fn reference(
+        &self,
+        serial: &str,
+        exposure: ExposureTime,
+    ) -> Result<Option<ReferenceSpectrum>> {
+        load(
+            &self
+                .instrument_dir(serial)
+                .join(REFERENCE_SPECTRA)
+                .join(exposure_file(exposure)),
+        )
+    }
+
+    pub fn save_reference(&self, serial: &str, reference: &ReferenceSpectrum) -> Result<()> {
+        save(
+            &self
+                .instrument_dir(serial)
+                .join(REFERENCE_SPECTRA)
+                .join(exposure_file(reference.exposure())),
+            reference,
+        )
+    }
+
+    /// Concentration curves are shared between instruments, since responses are computed from
+    /// absorbance, which does not depend on sensitivity of a CCD
+    pub fn concentration_curve(&self, name: &str) -> Result<Option<ConcentrationCurve>> {
+        let curve: Option<ConcentrationCurve> = load(&self.concentration_curve_path(name))?;
+        // File may hold another curve with a name that sanitizes the same way
+        Ok(curve.filter(|curve| curve.name == name))
+    }
+
+    /// Replaces curve with the same name, but refuses to replace another curve that is stored
+    /// in the same file
+    pub fn save_concentration_curve(&self, curve: &ConcentrationCurve) -> Result<()> {
+        let path = self.concentration_curve_path(&curve.name);
+        if let Some(existing) = load::<ConcentrationCurve>(&path)? {
+            if existing.name != curve.name {
+                return Err(Error::NameCollision {
+                    name: curve.name.clone(),
+                    existing: existing.name,
+                });
+            }
+        }
+        save(&path, curve)
+    }
+
+    /// All stored curves sorted by name
+    pub fn concentration_curves(&self) -> Result<Vec<ConcentrationCurve>> {
+        let mut curves: Vec<ConcentrationCurve> = load_all(&self.root.join(CONCENTRATION_CURVES))?;
+        curves.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(curves)
+    }
+
+    /// Default location of PCA and PLS models, which are built offline and copied in as is
+    pub fn models_dir(&self) -> PathBuf {
+        self.root.join(MODELS)
+    }
+
+    /// All models from models directory sorted by name, every one of them checked to be valid
+    pub fn models(&self) -> Result<Vec<ChemometricModel>> {
+        let mut models: Vec<ChemometricModel> = load_all(&self.models_dir())?;
+        for model in &models {
+            model.validate()?;
+        }
+        models.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(models)
+    }
+
+    fn concentration_curve_path(&self, name: &str) -> PathBuf {
+        self.root
+            .join(CONCENTRATION_CURVES)
+            .join(format!("{}.json", sanitize(name)))
+    }
+}
+
+/// Missing file is not an error, it just means that nothing was stored yet
+pub(crate) fn load<T: DeserializeOwned>(path: &Path) -> Result<Option<T>> {
+    match fs::read(path) {
+        Ok(data) => Ok(Some(serde_json::from_slice(&data)?)),
+        Err(err) if err.kind() == ErrorKind::NotFound => Ok(None),
+        Err(err) => Err(err.into()),
+    }
+}
+
+/// Every JSON file in a directory in order of file names, missing directory means there are
+/// none yet
+pub(crate) fn load_all<T: DeserializeOwned>(dir: &Path) -> Result<Vec<T>> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(err) if err.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(err) => return Err(err.into()),
+    };
+    let mut paths = entries
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<std::io::Result<Vec<_>>>()?;
+    paths.retain(|path| path.extension().is_some_and(|ext| ext == "json"));
+    paths.sort();
+    let mut values = Vec::new();
+    for path in paths {
+        values.extend(load(&path)?);
+    }
+    Ok(values)
+}
+
+pub(crate) fn save<T: Serialize>(path: &Path, value: &T) -> Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    // Writing into a temporary file first, so that interrupted write does not corrupt old data
+    let tmp = path.with_extension("json.tmp");
+    fs::write(&tmp, serde_json::to_vec_pretty(value)?)?;
+    fs::rename(tmp, path)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{calibration::CalibrationPoint, test_utils::frame};
+    use claims::{assert_matches, assert_none, assert_ok, assert_some_eq};
+
+    fn temp_store(name: &str) -> Store {
+        let root = std::env::temp_dir().join(format!("{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        Store::new(root)
+    }
+
+    #[test]
+    fn sanitize_serial_number() {
+        let store = Store::new("/data");
+        assert_eq!(
+            store.instrument_dir("../2021 11"),
+            Path::new("/data/___2021_11")
+        );
+    }
+
+    #[test]
+    fn store_wavelength_calibration() {
+        let store = temp_store("spectrometer-store-test");
+        assert_none!(assert_ok!(store.wavelength_calibration("202111161548")));
+
+        let points = vec![
+            CalibrationPoint {
+                pixel: 0.0,
+                wavelength: 400.0,
+            },
+            CalibrationPoint {
+                pixel: 1000.0,
+                wavelength: 500.0,
+            },
+        ];
+        let cal = assert_ok!(WavelengthCalibration::fit(points, 1));
+        assert_ok!(store.save_wavelength_calibration("202111161548", &cal));
+        assert_some_eq!(
+            assert_ok!(store.wavelength_calibration("202111161548")),
+            cal
+        );
+        assert_none!(assert_ok!(store.wavelength_calibration("202111161549")));
+
+        fs::remove_dir_all(store.root()).unwrap();
+    }
+
+    #[test]
+    fn store_dark_frames_per_exposure() {
+        let store = temp_store("spectrometer-dark-test");
+        let exposure = |ms| ExposureTime::from_millis(ms).unwrap();
+        let dark = assert_ok!(DarkFrame::average(&[frame(1000, 20)]));
+        assert_ok!(store.save_dark_frame("202111161548", &dark));
+        assert_some_eq!(
+            assert_ok!(store.dark_frame("202111161548", exposure(20))),
+            dark
+        );
+        assert_none!(assert_ok!(store.dark_frame("202111161548", exposure(30))));
+
+        fs::remove_dir_all(store.root()).unwrap();
+    }
+
+    #[test]
+    fn store_concentration_curves() {
+        use crate::concentration::Standard;
+
+        let store = temp_store("spectrometer-curves-test");
+        assert_eq!(assert_ok!(store.concentration_curves()), vec![]);
+        let standards = (0..4)
+            .map(|c| Standard {
+                concentration: f64::from(c),
+                response: 0.25 * f64::from(c) + if c % 2 == 0 { 0.125 } else { 0.0 },
+            })
+            .collect();
+        let curve = assert_ok!(ConcentrationCurve::fit(
+            "nitrate/NO3".into(),
+            "mg/L".into(),
+            assert_ok!("peak:530:540".parse()),
+            standards,
+            1
+        ));
+        assert_ok!(store.save_concentration_curve(&curve));
+        assert_some_eq!(assert_ok!(store.concentration_curve("nitrate/NO3")), curve);
+        assert_eq!(
+            assert_ok!(store.concentration_curves()),
+            vec![curve.clone()]
+        );
+
+        let colliding = ConcentrationCurve {
+            name: "nitrate NO3".into(),
+            ..curve.clone()
+        };
+        assert_matches!(
+            store.save_concentration_curve(&colliding),
+            Err(Error::NameCollision { .. })
+        );
+        assert_none!(assert_ok!(store.concentration_curve("nitrate NO3")));
+        assert_eq!(assert_ok!(store.concentration_curves()), vec![curve]);
+
+        fs::remove_dir_all(store.root()).unwrap();
+    }
+
+    #[test]
+    fn store_defect_map() {
+        let store = temp_store("spectrometer-defects-test");
+        assert_none!(assert_ok!(store.defect_map("202111161548")));
+        let defects = DefectMap {
+            hot: vec![500],
+            dead: vec![1200, 1201],
+        };
+        assert_ok!(store.save_defect_map("202111161548", &defects));
+        assert_some_eq!(assert_ok!(store.defect_map("202111161548")), defects);
+
+        fs::remove_dir_all(store.root()).unwrap();
+    }
+}
@@ -0,0 +1,13 @@
+//! Helpers shared by unit tests of several modules
+
+use ccd_lcamv06::{ExposureTime, Frame, FRAME_PIXEL_COUNT};
+
+/// Frame with given pixels, taken at given exposure
+pub fn frame_of(pixels: [u16; FRAME_PIXEL_COUNT], exposure_ms: u16) -> Frame {
+    Frame::new(pixels).with_exposure(Some(ExposureTime::from_millis(exposure_ms).unwrap()))
+}
+
+/// Flat frame, taken at given exposure
+pub fn frame(value: u16, exposure_ms: u16) -> Frame {
+    frame_of([value; FRAME_PIXEL_COUNT], exposure_ms)
+}
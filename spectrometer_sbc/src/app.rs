@@ -1,7 +1,17 @@
 use std::io::{Read, Write};
 
 use crate::{components::chart::*, error_template::ErrorTemplate};
-use ccd_lcamv06::{IoAdapter, StdIoAdapter};
+use ccd_lcamv06::IoAdapter;
+use crate::reading::*;
+use spectrometer_processing::{
+    bands::BandMetric,
+    chemometrics::ChemometricModel,
+    concentration::ConcentrationCurve,
+    fit::Shape,
+    library::{Hit, Metric, Normalization},
+    raman::Side,
+    Baseline, Filter,
+};
 use leptos::{html::Input, *};
 use leptos_meta::*;
 use leptos_router::*;
@@ -47,15 +57,27 @@ pub fn App(cx: Scope) -> impl IntoView {
     }
 }
 
+#[cfg(feature = "ssr")]
+fn server_error(err: impl std::fmt::Display) -> ServerFnError {
+    ServerFnError::ServerError(err.to_string())
+}
+
+#[cfg(feature = "ssr")]
+fn app_state(cx: Scope) -> Result<crate::state::AppState, ServerFnError> {
+    use_context::<crate::state::AppState>(cx).ok_or_else(|| server_error("Could not get app state"))
+}
+
+/// Opens CCD the same way as other front ends, with its current settings read
+#[cfg(feature = "ssr")]
+fn open_ccd(port: &str) -> Result<ccd_lcamv06::CCD<ccd_lcamv06::Link>, ServerFnError> {
+    use ccd_lcamv06::{BaudRate, Link, LinkAddress};
+
+    Link::connect(&LinkAddress::from(port), BaudRate::default()).map_err(server_error)
+}
+
 #[server(ToggleLaser, "/api")]
 async fn toggle_laser(cx: Scope) -> Result<(), ServerFnError> {
-    use crate::state::AppState;
-
-    let Some(state) = use_context::<AppState>(cx) else {
-        return Err(ServerFnError::ServerError(
-            "Could not get app state".to_string(),
-        ));
-    };
+    let state = app_state(cx)?;
     let mut laser = state.gpio.laser.lock()?;
     laser.toggle();
 
@@ -64,39 +86,670 @@ async fn toggle_laser(cx: Scope) -> Result<(), ServerFnError> {
 
 #[component]
 fn HomePage(cx: Scope) -> impl IntoView {
-    let (chart_data, set_chart_data) = create_signal(cx, vec![]);
+    let (chart_data, set_chart_data) = create_signal(cx, Reading::default());
+    let (options, set_options) = create_signal(cx, ProcessingOptions::default());
+    // Fitted on processed reading, shared by chart overlay and list of fitted parameters
+    let fitted = create_memo(cx, move |_| {
+        options.with(|options| {
+            let shape = options.fit?;
+            let (reading, _) = chart_data.with(|reading| reading.processed(options));
+            Some(reading.fit_peaks(shape, options.raman))
+        })
+    });
 
     let chart_view = move || {
-        let chart_options = move || ChartOptions {
-            title: TitleOptions {
-                text: "Spectrogram".to_string(),
-            },
-            x_axis: Some(AxisOptions {
-                data: (0..chart_data().len()).map(|x| x.to_string()).collect(),
-            })
-            .into(),
-            data_zoom: vec![DataZoom::Slider, DataZoom::Inside],
-            series: vec![Series::Line {
-                name: "Intensity".to_string(),
-                data: chart_data(),
-            }],
-            tooltip: Some(TooltipOptions {
-                trigger: TooltipTrigger::Axis,
-            }),
-            ..Default::default()
+        let chart_options = move || {
+            let (reading, baseline) =
+                options.with(|options| chart_data.with(|reading| reading.processed(options)));
+            let mut series = vec![Series::Line {
+                name: reading.label().to_string(),
+                mark_point: options.with(|options| {
+                    options.show_peaks.then(|| reading.peak_markers(options.raman))
+                }),
+                data: reading.intensities.clone(),
+            }];
+            if let Some(baseline) = baseline {
+                series.push(Series::Line {
+                    name: "Baseline".to_string(),
+                    data: baseline,
+                    mark_point: None,
+                });
+            }
+            if let Some((_, curve)) = fitted() {
+                series.push(Series::Line {
+                    name: "Fit".to_string(),
+                    data: curve,
+                    mark_point: None,
+                });
+            }
+            ChartOptions {
+                title: TitleOptions {
+                    text: "Spectrogram".to_string(),
+                },
+                x_axis: Some(options.with(|options| reading.x_axis(options.raman))).into(),
+                data_zoom: vec![DataZoom::Slider, DataZoom::Inside],
+                series,
+                tooltip: Some(TooltipOptions {
+                    trigger: TooltipTrigger::Axis,
+                }),
+                ..Default::default()
+            }
         };
         view! { cx, <Chart options=chart_options class="aspect-[4/3]"/> }
     };
+    let saturation_warning = move || {
+        chart_data.with(|reading| {
+            (!reading.saturation.is_empty()).then(|| {
+                let warning = format!("Warning: {}", reading.saturation);
+                view! { cx, <p class="m-3 text-amber-600">{warning}</p> }
+            })
+        })
+    };
+    let fitted_peaks = move || {
+        fitted().map(|(fits, _)| {
+            let text = fits
+                .iter()
+                .map(|fit| fit.to_string())
+                .collect::<Vec<_>>()
+                .join("\n\n");
+            view! { cx, <pre class="m-3 text-sm">{text}</pre> }
+        })
+    };
+    let corrected_pixels = move || {
+        chart_data.with(|reading| {
+            (!reading.corrected.is_empty()).then(|| {
+                let pixels: Vec<_> = reading.corrected.iter().map(|p| p.to_string()).collect();
+                let note = format!(
+                    "Corrected {} defective pixels: {}",
+                    pixels.len(),
+                    pixels.join(", ")
+                );
+                view! { cx, <p class="m-3">{note}</p> }
+            })
+        })
+    };
 
     view! { cx,
         <Gpio/>
         <SerialPortReader set_frame=set_chart_data/>
         <FileReader set_frame=set_chart_data/>
+        <ProcessingControls set_options=set_options/>
+        <LibraryPanel reading=chart_data options=options/>
+        <BandTrend reading=chart_data options=options/>
+        <ConcentrationPanel reading=chart_data options=options/>
+        <ModelPanel reading=chart_data options=options/>
+        {saturation_warning}
+        {corrected_pixels}
         <Transition fallback=move || {} >
             <ErrorBoundary fallback=move |cx, errors| view!{cx, <ErrorTemplate errors=errors/>}>
                 {chart_view}
             </ErrorBoundary>
         </Transition>
+        {fitted_peaks}
+    }
+}
+
+#[component]
+fn ProcessingControls(cx: Scope, set_options: WriteSignal<ProcessingOptions>) -> impl IntoView {
+    let (error, set_error) = create_signal(cx, None::<String>);
+
+    view! { cx,
+        <div class="m-3">
+            <label class="m-3">
+                <input
+                    type="checkbox"
+                    class="mr-1"
+                    on:change=move |ev| {
+                        let checked = event_target_checked(&ev);
+                        set_options.update(|options| options.show_peaks = checked);
+                    }
+                />
+                "Mark peaks"
+            </label>
+            <input
+                type="text"
+                placeholder="Baseline, e.g. als:1e5:0.01"
+                class="rounded-lg p-3 m-3 w-64"
+                on:change=move |ev| {
+                    let value = event_target_value(&ev);
+                    let baseline = if value.trim().is_empty() {
+                        Ok(None)
+                    } else {
+                        value.parse::<Baseline>().map(Some)
+                    };
+                    match baseline {
+                        Ok(baseline) => {
+                            set_options.update(|options| options.baseline = baseline);
+                            set_error(None);
+                        }
+                        Err(err) => set_error(Some(err.to_string())),
+                    }
+                }
+            />
+            <label class="m-3">
+                <input
+                    type="checkbox"
+                    class="mr-1"
+                    on:change=move |ev| {
+                        let checked = event_target_checked(&ev);
+                        set_options.update(|options| options.subtract_baseline = checked);
+                    }
+                />
+                "Subtract baseline"
+            </label>
+            <input
+                type="text"
+                placeholder="Filters, e.g. median:5, sg:11:3"
+                class="rounded-lg p-3 m-3 w-64"
+                on:change=move |ev| {
+                    // Filters are applied in order they are listed, separated by commas
+                    let filters = event_target_value(&ev)
+                        .split(',')
+                        .filter(|filter| !filter.trim().is_empty())
+                        .map(str::parse)
+                        .collect::<Result<Vec<Filter>, _>>();
+                    match filters {
+                        Ok(filters) => {
+                            set_options.update(|options| options.filters = filters);
+                            set_error(None);
+                        }
+                        Err(err) => set_error(Some(err.to_string())),
+                    }
+                }
+            />
+            <select
+                class="rounded-lg p-3"
+                on:change=move |ev| {
+                    let raman = match event_target_value(&ev).as_str() {
+                        "stokes" => Some(Side::Stokes),
+                        "anti-stokes" => Some(Side::AntiStokes),
+                        _ => None,
+                    };
+                    set_options.update(|options| options.raman = raman);
+                }
+            >
+                <option value="wavelength">"Wavelength"</option>
+                <option value="stokes">"Raman shift"</option>
+                <option value="anti-stokes">"Anti-Stokes shift"</option>
+            </select>
+            <select
+                class="rounded-lg p-3 m-3"
+                on:change=move |ev| {
+                    let fit = match event_target_value(&ev).as_str() {
+                        "gaussian" => Some(Shape::Gaussian),
+                        "lorentzian" => Some(Shape::Lorentzian),
+                        "voigt" => Some(Shape::PseudoVoigt),
+                        _ => None,
+                    };
+                    set_options.update(|options| options.fit = fit);
+                }
+            >
+                <option value="none">"No peak fitting"</option>
+                <option value="gaussian">"Fit Gaussian peaks"</option>
+                <option value="lorentzian">"Fit Lorentzian peaks"</option>
+                <option value="voigt">"Fit pseudo-Voigt peaks"</option>
+            </select>
+            {move || error().map(|err| view! { cx, <p class="text-red-600">{err}</p> })}
+        </div>
+    }
+}
+
+/// Scores processed spectrum against spectral library, best matches first
+#[server(IdentifyReading, "/api", "Cbor")]
+pub async fn identify_reading(
+    cx: Scope,
+    intensities: Vec<f64>,
+    wavelengths: Option<Vec<f64>>,
+    metric: Metric,
+    normalization: Normalization,
+) -> Result<Vec<Hit>, ServerFnError> {
+    use spectrometer_processing::{library::MatchOptions, Library};
+
+    let state = app_state(cx)?;
+    let library = Library::load(&state.store.library_dir()).map_err(server_error)?;
+    let options = MatchOptions {
+        metric,
+        normalization,
+        baseline: None,
+        ..Default::default()
+    };
+    library
+        .identify(&intensities, wavelengths.as_deref(), &options)
+        .map_err(server_error)
+}
+
+/// Stores processed spectrum in spectral library under given name
+#[server(AddToLibrary, "/api", "Cbor")]
+pub async fn add_to_library(
+    cx: Scope,
+    name: String,
+    intensities: Vec<f64>,
+    wavelengths: Option<Vec<f64>>,
+) -> Result<(), ServerFnError> {
+    use spectrometer_processing::{library::LibraryEntry, Library};
+
+    let state = app_state(cx)?;
+    let entry = LibraryEntry {
+        name,
+        wavelengths,
+        values: intensities,
+    };
+    Library::save_entry(&state.store.library_dir(), &entry).map_err(server_error)?;
+    Ok(())
+}
+
+/// Identifies shown spectrum with spectral library, after processing done in the browser
+#[component]
+fn LibraryPanel(
+    cx: Scope,
+    reading: ReadSignal<Reading>,
+    options: ReadSignal<ProcessingOptions>,
+) -> impl IntoView {
+    let processed = move || options.with(|options| reading.with(|r| r.processed(options).0));
+    let (metric, set_metric) = create_signal(cx, Metric::default());
+    let (normalization, set_normalization) = create_signal(cx, Normalization::default());
+    let (name, set_name) = create_signal(cx, String::new());
+    let serv_identify = create_server_action::<IdentifyReading>(cx);
+    let serv_add = create_server_action::<AddToLibrary>(cx);
+    let hits = move || {
+        serv_identify.value().get().map(|hits| match hits {
+            Ok(hits) if hits.is_empty() => {
+                view! { cx, <p>"Library is empty"</p> }.into_view(cx)
+            }
+            Ok(hits) => view! { cx,
+                <ol class="m-3">
+                    {hits
+                        .into_iter()
+                        .take(5)
+                        .map(|hit| view! { cx, <li>{format!("{}: {:.4}", hit.name, hit.score)}</li> })
+                        .collect_view(cx)}
+                </ol>
+            }
+            .into_view(cx),
+            Err(err) => view! { cx, <p class="text-red-600">{err.to_string()}</p> }.into_view(cx),
+        })
+    };
+    let add_error = move || {
+        serv_add
+            .value()
+            .get()
+            .and_then(|result| result.err())
+            .map(|err| view! { cx, <p class="text-red-600">{err.to_string()}</p> })
+    };
+
+    view! { cx,
+        <div class="m-3">
+            <select
+                class="rounded-lg p-3"
+                on:change=move |ev| {
+                    set_metric(match event_target_value(&ev).as_str() {
+                        "euclidean" => Metric::Euclidean,
+                        "angle" => Metric::SpectralAngle,
+                        _ => Metric::Correlation,
+                    });
+                }
+            >
+                <option value="correlation">"Correlation"</option>
+                <option value="euclidean">"Euclidean distance"</option>
+                <option value="angle">"Spectral angle"</option>
+            </select>
+            <select
+                class="rounded-lg p-3 m-3"
+                on:change=move |ev| {
+                    set_normalization(match event_target_value(&ev).as_str() {
+                        "none" => Normalization::None,
+                        "max" => Normalization::Max,
+                        "area" => Normalization::Area,
+                        "snv" => Normalization::Snv,
+                        _ => Normalization::Vector,
+                    });
+                }
+            >
+                <option value="vector">"Vector normalisation"</option>
+                <option value="max">"Max normalisation"</option>
+                <option value="area">"Area normalisation"</option>
+                <option value="snv">"SNV"</option>
+                <option value="none">"No normalisation"</option>
+            </select>
+            <button
+                class="bg-amber-600 disabled:bg-gray-400 hover:bg-amber-800 p-3 m-3 text-white rounded-lg"
+                disabled=serv_identify.pending()
+                on:click=move |_| {
+                    let processed = processed();
+                    serv_identify.dispatch(IdentifyReading {
+                        intensities: processed.intensities,
+                        wavelengths: processed.wavelengths,
+                        metric: metric(),
+                        normalization: normalization(),
+                    });
+                }
+            >
+                "Identify"
+            </button>
+            <input
+                type="text"
+                placeholder="Material name"
+                class="rounded-lg p-3 m-3 w-64"
+                on:change=move |ev| set_name(event_target_value(&ev))
+            />
+            <button
+                class="bg-slate-600 disabled:bg-gray-400 hover:bg-slate-800 p-3 m-3 text-white rounded-lg"
+                disabled=move || serv_add.pending()() || name.with(String::is_empty)
+                on:click=move |_| {
+                    let reading = processed();
+                    serv_add.dispatch(AddToLibrary {
+                        name: name(),
+                        intensities: reading.intensities,
+                        wavelengths: reading.wavelengths,
+                    });
+                }
+            >
+                "Add to library"
+            </button>
+            {add_error}
+            {hits}
+        </div>
+    }
+}
+
+/// Amount of most recent points kept in band trend chart
+const TREND_LENGTH: usize = 500;
+
+/// Band metrics of every new reading charted over time, after processing done in the browser
+#[component]
+fn BandTrend(
+    cx: Scope,
+    reading: ReadSignal<Reading>,
+    options: ReadSignal<ProcessingOptions>,
+) -> impl IntoView {
+    let (metrics, set_metrics) = create_signal(cx, Vec::<BandMetric>::new());
+    let (error, set_error) = create_signal(cx, None::<String>);
+    // Time of every point along with value of every metric
+    let (trend, set_trend) = create_signal(cx, Vec::<(String, Vec<f64>)>::new());
+    create_effect(cx, move |_| {
+        // Only new readings add points, changing options or metrics does not
+        let (x, y) = reading.with(|reading| {
+            options.with_untracked(|options| {
+                let (processed, _) = reading.processed(options);
+                (processed.x_values(options.raman), processed.intensities)
+            })
+        });
+        let values: Vec<f64> = metrics.with_untracked(|metrics| {
+            metrics.iter().map(|metric| metric.evaluate(&x, &y)).collect()
+        });
+        if y.is_empty() || values.is_empty() {
+            return;
+        }
+        let time = js_sys::Date::new_0()
+            .to_locale_time_string("en-GB")
+            .as_string()
+            .unwrap_or_default();
+        set_trend.update(|trend| {
+            trend.push((time, values));
+            if trend.len() > TREND_LENGTH {
+                trend.remove(0);
+            }
+        });
+    });
+
+    let chart_options = move || {
+        let series = metrics.with(|metrics| {
+            trend.with(|trend| {
+                metrics
+                    .iter()
+                    .enumerate()
+                    .map(|(idx, metric)| Series::Line {
+                        name: metric.to_string(),
+                        data: trend.iter().map(|(_, values)| values[idx]).collect(),
+                        mark_point: None,
+                    })
+                    .collect()
+            })
+        });
+        ChartOptions {
+            title: TitleOptions {
+                text: "Band trend".to_string(),
+            },
+            x_axis: Some(AxisOptions {
+                name: Some("Time".to_string()),
+                data: trend.with(|trend| trend.iter().map(|(time, _)| time.clone()).collect()),
+            })
+            .into(),
+            data_zoom: vec![DataZoom::Slider, DataZoom::Inside],
+            series,
+            tooltip: Some(TooltipOptions {
+                trigger: TooltipTrigger::Axis,
+            }),
+            ..Default::default()
+        }
+    };
+
+    view! { cx,
+        <div class="m-3">
+            <input
+                type="text"
+                placeholder="Band metrics, e.g. sum:520:540, ratio:520:540/600:620"
+                class="rounded-lg p-3 m-3 w-96"
+                on:change=move |ev| {
+                    // Metrics are separated by commas, same as filters
+                    let metrics = event_target_value(&ev)
+                        .split(',')
+                        .filter(|metric| !metric.trim().is_empty())
+                        .map(str::parse)
+                        .collect::<Result<Vec<BandMetric>, _>>();
+                    match metrics {
+                        Ok(metrics) => {
+                            set_metrics(metrics);
+                            set_trend(Vec::new());
+                            set_error(None);
+                        }
+                        Err(err) => set_error(Some(err.to_string())),
+                    }
+                }
+            />
+            <button
+                class="bg-slate-600 hover:bg-slate-800 p-3 m-3 text-white rounded-lg"
+                on:click=move |_| set_trend(Vec::new())
+            >
+                "Clear trend"
+            </button>
+            {move || error().map(|err| view! { cx, <p class="text-red-600">{err}</p> })}
+            {move || {
+                metrics
+                    .with(|metrics| !metrics.is_empty())
+                    .then(|| view! { cx, <Chart options=chart_options class="aspect-[4/1]"/> })
+            }}
+        </div>
+    }
+}
+
+#[server(ListConcentrationCurves, "/api", "Cbor")]
+pub async fn list_concentration_curves(
+    cx: Scope,
+) -> Result<Vec<ConcentrationCurve>, ServerFnError> {
+    let state = app_state(cx)?;
+    state.store.concentration_curves().map_err(server_error)
+}
+
+/// Concentration estimated with a stored curve from absorbance processed in the browser
+#[component]
+fn ConcentrationPanel(
+    cx: Scope,
+    reading: ReadSignal<Reading>,
+    options: ReadSignal<ProcessingOptions>,
+) -> impl IntoView {
+    let curves = create_local_resource(cx, || {}, move |_| list_concentration_curves(cx));
+    let (selected, set_selected) = create_signal(cx, String::new());
+    let curve = move || {
+        let curves = curves.read(cx)?.ok()?;
+        selected.with(|name| curves.into_iter().find(|curve| &curve.name == name))
+    };
+    let estimate = move || {
+        let curve = curve()?;
+        let (processed, x) = reading.with(|reading| {
+            options.with(|options| {
+                let (processed, _) = reading.processed(options);
+                let x = processed.x_values(options.raman);
+                (processed, x)
+            })
+        });
+        if processed.intensities.is_empty() {
+            return None;
+        }
+        if !matches!(processed.measure, Measure::Absorbance) {
+            return Some(view! { cx,
+                <p class="text-red-600">"Concentration is estimated from absorbance"</p>
+            }
+            .into_view(cx));
+        }
+        Some(match curve.estimate(&x, &processed.intensities) {
+            Ok(estimate) => {
+                let text = format!(
+                    "{}: {:.4} ± {:.4} {}",
+                    curve.name, estimate.concentration, estimate.uncertainty, curve.unit
+                );
+                let (lowest, highest) = curve.range();
+                let warnings = [
+                    estimate.below_lod.then(|| {
+                        format!("Below limit of detection of {:.4} {}", curve.lod, curve.unit)
+                    }),
+                    estimate.extrapolated.then(|| {
+                        format!("Outside of standards from {lowest} to {highest} {}", curve.unit)
+                    }),
+                ];
+                view! { cx,
+                    <p class="text-lg">{text}</p>
+                    {warnings
+                        .into_iter()
+                        .flatten()
+                        .map(|warning| view! { cx, <p class="text-amber-600">{warning}</p> })
+                        .collect_view(cx)}
+                }
+                .into_view(cx)
+            }
+            Err(err) => view! { cx, <p class="text-red-600">{err.to_string()}</p> }.into_view(cx),
+        })
+    };
+    let curves_view = move || {
+        curves.read(cx).map(|curves| match curves {
+            Ok(curves) if curves.is_empty() => {
+                view! { cx, <p>"No concentration curves stored"</p> }.into_view(cx)
+            }
+            Ok(curves) => view! { cx,
+                <select
+                    class="rounded-lg p-3"
+                    on:change=move |ev| set_selected(event_target_value(&ev))
+                >
+                    <option value="">"No concentration curve"</option>
+                    {curves
+                        .into_iter()
+                        .map(|curve| {
+                            let label = curve.to_string();
+                            view! { cx, <option value=curve.name>{label}</option> }
+                        })
+                        .collect_view(cx)}
+                </select>
+            }
+            .into_view(cx),
+            Err(err) => view! { cx, <p class="text-red-600">{err.to_string()}</p> }.into_view(cx),
+        })
+    };
+
+    view! { cx,
+        <div class="m-3">
+            <Transition fallback=move || {}>{curves_view}</Transition>
+            {estimate}
+        </div>
+    }
+}
+
+#[server(ListModels, "/api", "Cbor")]
+pub async fn list_models(cx: Scope) -> Result<Vec<ChemometricModel>, ServerFnError> {
+    let state = app_state(cx)?;
+    state.store.models().map_err(server_error)
+}
+
+/// Scores and predictions of a PCA or PLS model for every new reading, applied in the browser
+#[component]
+fn ModelPanel(
+    cx: Scope,
+    reading: ReadSignal<Reading>,
+    options: ReadSignal<ProcessingOptions>,
+) -> impl IntoView {
+    let models = create_local_resource(cx, || {}, move |_| list_models(cx));
+    let (selected, set_selected) = create_signal(cx, String::new());
+    let model = move || {
+        let models = models.read(cx)?.ok()?;
+        selected.with(|name| models.into_iter().find(|model| &model.name == name))
+    };
+    let output = move || {
+        let model = model()?;
+        let (x, y) = reading.with(|reading| {
+            options.with(|options| {
+                let (processed, _) = reading.processed(options);
+                (processed.x_values(options.raman), processed.intensities)
+            })
+        });
+        if y.is_empty() {
+            return None;
+        }
+        Some(match model.apply(&x, &y) {
+            Ok(output) => {
+                let scores = output
+                    .scores
+                    .iter()
+                    .enumerate()
+                    .map(|(idx, t)| format!("T{}: {t:.4}", idx + 1));
+                let predictions = model
+                    .regression
+                    .iter()
+                    .zip(&output.predictions)
+                    .map(|(regression, v)| format!("{}: {v:.4}", regression.name));
+                let text = scores.chain(predictions).collect::<Vec<_>>().join("\n");
+                let limit = |limit: Option<f64>| limit.map_or(String::new(), |l| format!(" (limit {l})"));
+                let t_squared = format!("T²: {:.4}{}", output.t_squared, limit(model.t_squared_limit));
+                let q = format!("Q: {:.4}{}", output.q_residual, limit(model.q_limit));
+                let flag = |outlier: bool| if outlier { "text-red-600" } else { "" };
+                view! { cx,
+                    <pre class="text-sm">{text}</pre>
+                    <p class=flag(output.t_squared_outlier(&model))>{t_squared}</p>
+                    <p class=flag(output.q_outlier(&model))>{q}</p>
+                }
+                .into_view(cx)
+            }
+            Err(err) => view! { cx, <p class="text-red-600">{err.to_string()}</p> }.into_view(cx),
+        })
+    };
+    let models_view = move || {
+        models.read(cx).map(|models| match models {
+            Ok(models) if models.is_empty() => {
+                view! { cx, <p>"No PCA or PLS models stored"</p> }.into_view(cx)
+            }
+            Ok(models) => view! { cx,
+                <select
+                    class="rounded-lg p-3"
+                    on:change=move |ev| set_selected(event_target_value(&ev))
+                >
+                    <option value="">"No model"</option>
+                    {models
+                        .into_iter()
+                        .map(|model| {
+                            let label = format!("{}, {} components", model.name, model.components());
+                            view! { cx, <option value=model.name>{label}</option> }
+                        })
+                        .collect_view(cx)}
+                </select>
+            }
+            .into_view(cx),
+            Err(err) => view! { cx, <p class="text-red-600">{err.to_string()}</p> }.into_view(cx),
+        })
+    };
+
+    view! { cx,
+        <div class="m-3">
+            <Transition fallback=move || {}>{models_view}</Transition>
+            {output}
+        </div>
     }
 }
 
@@ -149,26 +802,228 @@ fn Gpio(cx: Scope) -> impl IntoView {
 
 #[server(ListSerialPorts, "/api")]
 pub async fn list_serial_ports() -> Result<Vec<String>, ServerFnError> {
-    let ports =
-        serialport::available_ports().map_err(|err| ServerFnError::ServerError(err.to_string()))?;
+    let ports = serialport::available_ports().map_err(server_error)?;
     let port_names = ports.into_iter().map(|port| port.port_name).collect();
     Ok(port_names)
 }
 
 #[server(GetSingleReading, "/api")]
-pub async fn get_single_reading(port: String) -> Result<Vec<f64>, ServerFnError> {
-    let serial = serialport::new(port, Default::default())
-        .open()
-        .map_err(|err| ServerFnError::ServerError(err.to_string()))?;
-    let mut ccd = StdIoAdapter::new(serial).open_ccd();
-    let frame = ccd
-        .get_frame()
-        .map_err(|err| ServerFnError::ServerError(err.to_string()))?;
-    Ok(frame.into_iter().map(f64::from).collect())
+pub async fn get_single_reading(
+    cx: Scope,
+    port: String,
+    subtract_dark: bool,
+    measure: Measure,
+    raw: bool,
+) -> Result<Reading, ServerFnError> {
+    use spectrometer_processing::{
+        absorbance::AbsorbanceOptions, intensity::intensity, AbsorbanceMeasurement, Saturation,
+    };
+
+    let state = app_state(cx)?;
+    let mut ccd = open_ccd(&port)?;
+    let version = ccd.get_version().map_err(server_error)?;
+    let needs_reference = measure != Measure::Intensity;
+    let dark = if subtract_dark || needs_reference {
+        // Reading exposure time also makes CCD attach it to captured frames
+        let exposure = ccd.get_exp_time().map_err(server_error)?;
+        let dark = state
+            .store
+            .dark_frame(version.serial_number(), exposure)
+            .map_err(server_error)?
+            .ok_or_else(|| server_error(format!("No dark frame stored for {exposure}")))?;
+        Some((exposure, dark))
+    } else {
+        None
+    };
+    let frame = ccd.get_frame().map_err(server_error)?;
+    let wavelengths = state
+        .store
+        .wavelength_calibration(version.serial_number())
+        .map_err(server_error)?
+        .map(|calibration| calibration.wavelengths(frame.len()));
+    let laser = state
+        .store
+        .laser(version.serial_number())
+        .map_err(server_error)?;
+    let mut intensities = match dark {
+        Some((exposure, dark)) if needs_reference => {
+            let reference = state
+                .store
+                .reference(version.serial_number(), exposure)
+                .map_err(server_error)?
+                .ok_or_else(|| {
+                    server_error(format!("No reference spectrum stored for {exposure}"))
+                })?;
+            let measurement =
+                AbsorbanceMeasurement::new(dark, reference, AbsorbanceOptions::default())
+                    .map_err(server_error)?;
+            match measure {
+                Measure::Absorbance => measurement.absorbance(&frame),
+                _ => measurement.transmittance(&frame),
+            }
+            .map_err(server_error)?
+        }
+        Some((_, dark)) if raw => dark.subtract(&frame),
+        Some((_, dark)) => dark.intensity(&frame),
+        None if raw => frame.iter().map(|&pixel| f64::from(pixel)).collect(),
+        None => intensity(&frame),
+    };
+    let corrected = state
+        .store
+        .defect_map(version.serial_number())
+        .map_err(server_error)?
+        .map(|defects| defects.correct(&mut intensities))
+        .unwrap_or_default();
+    Ok(Reading {
+        intensities,
+        measure,
+        raw,
+        wavelengths,
+        laser,
+        saturation: Saturation::analyze(&frame),
+        corrected,
+    })
+}
+
+/// Takes frames for averaging at current exposure time, returns them with CCD serial number
+#[cfg(feature = "ssr")]
+fn capture_frames(
+    port: String,
+    count: usize,
+) -> Result<(String, Vec<ccd_lcamv06::Frame>), ServerFnError> {
+    let mut ccd = open_ccd(&port)?;
+    let version = ccd.get_version().map_err(server_error)?;
+    ccd.get_exp_time().map_err(server_error)?;
+    // First frame may still be integrating with previous settings, so it is dropped
+    let mut frames = Vec::with_capacity(count + 1);
+    ccd.extend_with_frames(&mut frames, count + 1)
+        .map_err(server_error)?;
+    frames.remove(0);
+    Ok((version.serial_number().to_string(), frames))
+}
+
+/// Warning about pixels saturated or clipped while averaging, which are left out of measurements
+#[cfg(feature = "ssr")]
+fn clipped_note(clipped: &[usize]) -> String {
+    if clipped.is_empty() {
+        return String::new();
+    }
+    let list: Vec<_> = clipped.iter().map(|p| p.to_string()).collect();
+    format!(
+        "Warning: {} pixels were saturated or clipped and are left out: {}",
+        clipped.len(),
+        list.join(", ")
+    )
+}
+
+/// Averages `count` frames taken with CCD covered and stores them for current exposure time
+#[server(CaptureDark, "/api")]
+pub async fn capture_dark(cx: Scope, port: String, count: usize) -> Result<String, ServerFnError> {
+    use spectrometer_processing::DarkFrame;
+
+    let state = app_state(cx)?;
+    let (serial, frames) = capture_frames(port, count)?;
+    let dark = DarkFrame::average(&frames).map_err(server_error)?;
+    state
+        .store
+        .save_dark_frame(&serial, &dark)
+        .map_err(server_error)?;
+    Ok(clipped_note(dark.clipped()))
+}
+
+/// Averages `count` frames taken through a blank and stores them for current exposure time
+#[server(CaptureReference, "/api")]
+pub async fn capture_reference(
+    cx: Scope,
+    port: String,
+    count: usize,
+) -> Result<String, ServerFnError> {
+    use spectrometer_processing::ReferenceSpectrum;
+
+    let state = app_state(cx)?;
+    let (serial, frames) = capture_frames(port, count)?;
+    let reference = ReferenceSpectrum::average(&frames).map_err(server_error)?;
+    state
+        .store
+        .save_reference(&serial, &reference)
+        .map_err(server_error)?;
+    Ok(clipped_note(reference.clipped()))
+}
+
+/// Stores wavelength of excitation laser in nm for CCD, used for Raman shift axis
+#[server(SetLaserWavelength, "/api")]
+pub async fn set_laser_wavelength(
+    cx: Scope,
+    port: String,
+    wavelength: f64,
+) -> Result<(), ServerFnError> {
+    use spectrometer_processing::Laser;
+
+    let state = app_state(cx)?;
+    let laser = Laser::new(wavelength).map_err(server_error)?;
+    let mut ccd = open_ccd(&port)?;
+    let version = ccd.get_version().map_err(server_error)?;
+    state
+        .store
+        .save_laser(version.serial_number(), &laser)
+        .map_err(server_error)?;
+    Ok(())
+}
+
+/// Finds defective pixels from dark frame and reference spectrum stored for current exposure
+/// time, and stores them to be interpolated over in later readings
+#[server(FindDefects, "/api")]
+pub async fn find_defects(cx: Scope, port: String) -> Result<String, ServerFnError> {
+    use spectrometer_processing::{defects::DefectOptions, DefectMap};
+
+    let state = app_state(cx)?;
+    let mut ccd = open_ccd(&port)?;
+    let version = ccd.get_version().map_err(server_error)?;
+    let exposure = ccd.get_exp_time().map_err(server_error)?;
+    let serial = version.serial_number();
+    let dark = state
+        .store
+        .dark_frame(serial, exposure)
+        .map_err(server_error)?
+        .ok_or_else(|| server_error(format!("No dark frame stored for {exposure}")))?;
+    let flat = state
+        .store
+        .reference(serial, exposure)
+        .map_err(server_error)?
+        .ok_or_else(|| server_error(format!("No reference spectrum stored for {exposure}")))?;
+    let defects = DefectMap::find(&dark, &flat, &DefectOptions::default());
+    state
+        .store
+        .save_defect_map(serial, &defects)
+        .map_err(server_error)?;
+    Ok(defects.to_string())
+}
+
+/// Searches for exposure time that puts peak signal near full scale and leaves it set. Stored
+/// defective pixels don't count towards peak signal
+#[server(AutoExposure, "/api")]
+pub async fn auto_exposure(cx: Scope, port: String) -> Result<String, ServerFnError> {
+    use ccd_lcamv06::AutoExposureOptions;
+
+    let state = app_state(cx)?;
+    let mut ccd = open_ccd(&port)?;
+    let version = ccd.get_version().map_err(server_error)?;
+    let defects = state
+        .store
+        .defect_map(version.serial_number())
+        .map_err(server_error)?
+        .map(|defects| defects.pixels())
+        .unwrap_or_default();
+    let options = AutoExposureOptions {
+        exclude: &defects,
+        ..Default::default()
+    };
+    let result = ccd.auto_exposure(&options).map_err(server_error)?;
+    Ok(result.to_string())
 }
 
 #[component]
-fn SerialPortReader(cx: Scope, set_frame: WriteSignal<Vec<f64>>) -> impl IntoView {
+fn SerialPortReader(cx: Scope, set_frame: WriteSignal<Reading>) -> impl IntoView {
     let ports = create_local_resource(cx, || {}, |_| async move { list_serial_ports().await });
     let (selected_port, set_selected_port) = create_signal(cx, None);
     let ports_view = move || {
@@ -199,12 +1054,59 @@ fn SerialPortReader(cx: Scope, set_frame: WriteSignal<Vec<f64>>) -> impl IntoVie
             }))
     };
 
+    let (subtract_dark, set_subtract_dark) = create_signal(cx, false);
+    let (raw, set_raw) = create_signal(cx, false);
     let serv_get_single_reading = create_server_action::<GetSingleReading>(cx);
-    create_effect(cx, move |_| {
-        serv_get_single_reading
-            .value()
-            .get()
-            .map(|frame| frame.map(set_frame))
+    let serv_capture_dark = create_server_action::<CaptureDark>(cx);
+    let (measure, set_measure) = create_signal(cx, Measure::Intensity);
+    let serv_capture_reference = create_server_action::<CaptureReference>(cx);
+    let serv_auto_exposure = create_server_action::<AutoExposure>(cx);
+    let capture_result = move || {
+        [serv_capture_dark.value().get(), serv_capture_reference.value().get()]
+            .into_iter()
+            .flatten()
+            .map(|result| match result {
+                Ok(note) => view! { cx, <p class="text-amber-600">{note}</p> },
+                Err(err) => view! { cx, <p class="text-red-600">{err.to_string()}</p> },
+            })
+            .collect_view(cx)
+    };
+    let auto_exposure_result = move || {
+        serv_auto_exposure.value().get().map(|result| match result {
+            Ok(result) => view! { cx, <p>{result}</p> },
+            Err(err) => view! { cx, <p class="text-red-600">{err.to_string()}</p> },
+        })
+    };
+    let (laser_wavelength, set_laser_wavelength) = create_signal(cx, None::<f64>);
+    let serv_set_laser_wavelength = create_server_action::<SetLaserWavelength>(cx);
+    let serv_find_defects = create_server_action::<FindDefects>(cx);
+    let find_defects_result = move || {
+        serv_find_defects.value().get().map(|result| match result {
+            Ok(result) => view! { cx, <p class="whitespace-pre-line">{result}</p> },
+            Err(err) => view! { cx, <p class="text-red-600">{err.to_string()}</p> },
+        })
+    };
+    // In live mode next reading is requested as soon as previous one arrives
+    let (live, set_live) = create_signal(cx, false);
+    let get_reading = move || {
+        if let Some(port) = selected_port.get_untracked() {
+            serv_get_single_reading.dispatch(GetSingleReading {
+                port,
+                subtract_dark: subtract_dark.get_untracked(),
+                measure: measure.get_untracked(),
+                raw: raw.get_untracked(),
+            });
+        }
+    };
+    create_effect(cx, move |_| match serv_get_single_reading.value().get() {
+        Some(Ok(frame)) => {
+            set_frame(frame);
+            if live.get_untracked() {
+                get_reading();
+            }
+        }
+        Some(Err(_)) => set_live(false),
+        None => {}
     });
 
     view! { cx,
@@ -212,21 +1114,126 @@ fn SerialPortReader(cx: Scope, set_frame: WriteSignal<Vec<f64>>) -> impl IntoVie
             {ports_view}
             <button
                 class="bg-amber-600 disabled:bg-gray-400 hover:bg-amber-800 p-3 m-3 text-white rounded-lg"
-                disabled=serv_get_single_reading.pending()
+                disabled=move || serv_get_single_reading.pending()() || live()
+                on:click=move |_| get_reading()
+            >
+                "Get a single frame"
+            </button>
+            <label class="m-3">
+                <input
+                    type="checkbox"
+                    class="mr-1"
+                    prop:checked=move || live()
+                    on:change=move |ev| {
+                        let checked = event_target_checked(&ev);
+                        set_live(checked);
+                        if checked && !serv_get_single_reading.pending().get_untracked() {
+                            get_reading();
+                        }
+                    }
+                />
+                "Live"
+            </label>
+            <label class="m-3">
+                <input
+                    type="checkbox"
+                    class="mr-1"
+                    on:change=move |ev| set_subtract_dark(event_target_checked(&ev))
+                />
+                "Subtract dark frame"
+            </label>
+            <label class="m-3">
+                <input
+                    type="checkbox"
+                    class="mr-1"
+                    on:change=move |ev| set_raw(event_target_checked(&ev))
+                />
+                "Raw values"
+            </label>
+            <button
+                class="bg-slate-600 disabled:bg-gray-400 hover:bg-slate-800 p-3 m-3 text-white rounded-lg"
+                disabled=serv_capture_dark.pending()
                 on:click=move |_| {
                     if let Some(port) = selected_port() {
-                        serv_get_single_reading.dispatch(GetSingleReading{port});
+                        serv_capture_dark.dispatch(CaptureDark{port, count: 20});
                     }
                 }
             >
-                "Get a single frame"
+                "Capture dark frame"
+            </button>
+            <select
+                class="rounded-lg p-3"
+                on:change=move |ev| {
+                    set_measure(match event_target_value(&ev).as_str() {
+                        "transmittance" => Measure::Transmittance,
+                        "absorbance" => Measure::Absorbance,
+                        _ => Measure::Intensity,
+                    });
+                }
+            >
+                <option value="intensity">"Intensity"</option>
+                <option value="transmittance">"Transmittance"</option>
+                <option value="absorbance">"Absorbance"</option>
+            </select>
+            <button
+                class="bg-slate-600 disabled:bg-gray-400 hover:bg-slate-800 p-3 m-3 text-white rounded-lg"
+                disabled=serv_capture_reference.pending()
+                on:click=move |_| {
+                    if let Some(port) = selected_port() {
+                        serv_capture_reference.dispatch(CaptureReference{port, count: 20});
+                    }
+                }
+            >
+                "Capture reference"
+            </button>
+            {capture_result}
+            <button
+                class="bg-slate-600 disabled:bg-gray-400 hover:bg-slate-800 p-3 m-3 text-white rounded-lg"
+                disabled=serv_auto_exposure.pending()
+                on:click=move |_| {
+                    if let Some(port) = selected_port() {
+                        serv_auto_exposure.dispatch(AutoExposure{port});
+                    }
+                }
+            >
+                "Auto exposure"
+            </button>
+            {auto_exposure_result}
+            <button
+                class="bg-slate-600 disabled:bg-gray-400 hover:bg-slate-800 p-3 m-3 text-white rounded-lg"
+                disabled=serv_find_defects.pending()
+                on:click=move |_| {
+                    if let Some(port) = selected_port() {
+                        serv_find_defects.dispatch(FindDefects{port});
+                    }
+                }
+            >
+                "Map defective pixels"
+            </button>
+            {find_defects_result}
+            <input
+                type="number"
+                placeholder="Laser wavelength, nm"
+                class="rounded-lg p-3 m-3 w-64"
+                on:change=move |ev| set_laser_wavelength(event_target_value(&ev).parse().ok())
+            />
+            <button
+                class="bg-slate-600 disabled:bg-gray-400 hover:bg-slate-800 p-3 m-3 text-white rounded-lg"
+                disabled=serv_set_laser_wavelength.pending()
+                on:click=move |_| {
+                    if let (Some(port), Some(wavelength)) = (selected_port(), laser_wavelength()) {
+                        serv_set_laser_wavelength.dispatch(SetLaserWavelength{port, wavelength});
+                    }
+                }
+            >
+                "Set laser wavelength"
             </button>
         </div>
     }
 }
 
 #[component]
-fn FileReader(cx: Scope, set_frame: WriteSignal<Vec<f64>>) -> impl IntoView {
+fn FileReader(cx: Scope, set_frame: WriteSignal<Reading>) -> impl IntoView {
     let file_ref = create_node_ref::<Input>(cx);
     let file_parse = move |_| {
         if let Some(files) = file_ref.get().and_then(|f| f.files()) {
@@ -246,8 +1253,16 @@ fn FileReader(cx: Scope, set_frame: WriteSignal<Vec<f64>>) -> impl IntoView {
                 let hex_cursor = IOIgnoreWrite(parsed_hex.as_slice());
                 let mut ccd = ccd_lcamv06::StdIoAdapter::new(hex_cursor).open_ccd();
                 let frame = ccd.get_frame().unwrap();
-                let frame_vec = frame.into_iter().map(|x| x.into()).collect();
-                set_frame(frame_vec);
+                // Recorded files carry no serial number, so there is no calibration to apply
+                set_frame(Reading {
+                    intensities: spectrometer_processing::intensity::intensity(&frame),
+                    measure: Measure::Intensity,
+                    raw: false,
+                    wavelengths: None,
+                    laser: None,
+                    saturation: spectrometer_processing::Saturation::analyze(&frame),
+                    corrected: Vec::new(),
+                });
             });
         }
     };
@@ -103,18 +103,38 @@ pub enum DataZoom {
     Inside,
 }
 
-#[derive(Clone, Serialize, Deserialize)]
+#[derive(Clone, Serialize, Deserialize, Default)]
 pub struct AxisOptions {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
     pub data: Vec<String>,
 }
 
 #[derive(Clone, Serialize, Deserialize)]
 #[serde(tag = "type", rename_all = "camelCase")]
 pub enum Series {
-    Line { name: String, data: Vec<f64> },
+    Line {
+        name: String,
+        data: Vec<f64>,
+        #[serde(rename = "markPoint", skip_serializing_if = "Option::is_none")]
+        mark_point: Option<MarkPoint>,
+    },
     Bar { name: String, data: Vec<f64> },
 }
 
+#[derive(Clone, Serialize, Deserialize, Default)]
+pub struct MarkPoint {
+    pub data: Vec<MarkPointData>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct MarkPointData {
+    /// Index on category axis and value on value axis
+    pub coord: (usize, f64),
+    /// Label shown inside of a marker
+    pub value: String,
+}
+
 /// Acts as std::option::Option, but None is serialized into empty object instead of undefined
 #[derive(Clone, Debug, Deserialize, Serialize)]
 #[serde(deny_unknown_fields)]
@@ -3,6 +3,7 @@ pub mod app;
 pub mod components;
 pub mod error_template;
 pub mod fallback;
+pub mod reading;
 
 cfg_if! {
 if #[cfg(feature = "ssr")] {
@@ -23,6 +23,7 @@ if #[cfg(feature = "ssr")] {
     use std::convert::Infallible;
     use rppal::gpio::Level;
     use spectrometer_sbc::gpio::GpioState;
+    use spectrometer_processing::Store;
     use tokio::select;
 
     async fn server_fn_handler(State(app_state): State<AppState>, path: Path<String>, headers: HeaderMap, raw_query: RawQuery, request: Request<AxumBody>) -> impl IntoResponse {
@@ -79,9 +80,15 @@ if #[cfg(feature = "ssr")] {
         let addr = leptos_options.site_addr;
         let routes = generate_route_list(|cx| view! { cx, <App/> }).await;
 
+        // Per CCD data, like wavelength calibrations, shared with spectrometer_cli
+        let store = Store::new(Store::default_root().ok_or_else(|| {
+            anyhow::anyhow!("Could not find data directory, set it with SPECTROMETER_DATA_DIR")
+        })?);
+
         let state = AppState {
             gpio,
-            leptos_options
+            leptos_options,
+            store,
         };
 
         // build our application with a route
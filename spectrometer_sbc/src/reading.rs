@@ -0,0 +1,203 @@
+//! Readings as they are sent to the browser, and processing applied to them before charting
+
+use crate::components::chart::{AxisOptions, MarkPoint, MarkPointData};
+use serde::{Deserialize, Serialize};
+use spectrometer_processing::{
+    filter::apply_chain,
+    fit::{fit_detected, FitOptions, Shape},
+    peaks::{detect_peaks, Interpolation, Peak, PeakOptions},
+    raman::Side,
+    Baseline, Filter, Fit, Laser, Saturation,
+};
+
+/// Quantity computed for every pixel of a reading
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Measure {
+    #[default]
+    Intensity,
+    /// Requires dark frame and reference spectrum stored at current exposure time
+    Transmittance,
+    Absorbance,
+}
+
+impl Measure {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Measure::Intensity => "Intensity",
+            Measure::Transmittance => "Transmittance",
+            Measure::Absorbance => "Absorbance, AU",
+        }
+    }
+}
+
+/// JSON has no NaN, so pixels that could not be measured are sent as null
+mod nan_as_null {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(values: &[f64], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(values.iter().map(|v| (!v.is_nan()).then_some(*v)))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<f64>, D::Error> {
+        let values = Vec::<Option<f64>>::deserialize(deserializer)?;
+        Ok(values.into_iter().map(|v| v.unwrap_or(f64::NAN)).collect())
+    }
+}
+
+/// Frame prepared for charting
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Reading {
+    /// Value of every pixel, NaN where it could not be measured
+    #[serde(with = "nan_as_null")]
+    pub intensities: Vec<f64>,
+    pub measure: Measure,
+    /// Intensity was left as inverted CCD output
+    pub raw: bool,
+    /// Wavelength of every pixel in nm, present if CCD has a stored calibration
+    pub wavelengths: Option<Vec<f64>>,
+    /// Excitation laser stored for CCD, needed for Raman shift axis
+    pub laser: Option<Laser>,
+    /// Pixels at either end of ADC range in the frame reading was computed from
+    pub saturation: Saturation,
+    /// Defective pixels that were interpolated over
+    pub corrected: Vec<usize>,
+}
+
+impl Reading {
+    /// Horizontal axis name and value of every pixel. Raman shift is used if requested and
+    /// CCD has both wavelength calibration and laser, wavelength otherwise
+    fn axis(&self, raman: Option<Side>) -> Option<(&'static str, Vec<f64>)> {
+        let wavelengths = self.wavelengths.as_ref()?;
+        match (raman, self.laser) {
+            (Some(side), Some(laser)) => {
+                Some(("Raman shift, cm⁻¹", laser.raman_shifts(wavelengths, side)))
+            }
+            _ => Some(("Wavelength, nm", wavelengths.clone())),
+        }
+    }
+
+    /// Horizontal axis value of every pixel, pixel numbers without wavelength calibration
+    pub fn x_values(&self, raman: Option<Side>) -> Vec<f64> {
+        match self.axis(raman) {
+            Some((_, values)) => values,
+            None => (0..self.intensities.len()).map(|x| x as f64).collect(),
+        }
+    }
+
+    /// Peaks standing out by at least 5% of reading range
+    fn prominent_peaks(&self) -> Vec<Peak> {
+        let (min, max) = self
+            .intensities
+            .iter()
+            .fold((f64::INFINITY, f64::NEG_INFINITY), |(lo, hi), &y| {
+                (lo.min(y), hi.max(y))
+            });
+        let options = PeakOptions {
+            min_prominence: (max - min) * 0.05,
+            interpolation: Interpolation::Gaussian,
+            ..Default::default()
+        };
+        detect_peaks(&self.intensities, &options)
+    }
+
+    /// Markers for prominent peaks, labelled with sub-pixel position in axis units
+    pub fn peak_markers(&self, raman: Option<Side>) -> MarkPoint {
+        let axis = self.axis(raman);
+        let data = self
+            .prominent_peaks()
+            .into_iter()
+            .map(|peak| MarkPointData {
+                coord: (peak.index, peak.height),
+                value: match &axis {
+                    Some((_, values)) => {
+                        let idx = peak.position.floor() as usize;
+                        let next = values.get(idx + 1).unwrap_or(&values[idx]);
+                        let value = values[idx] + (next - values[idx]) * (peak.position - idx as f64);
+                        format!("{value:.2}")
+                    }
+                    None => format!("{:.1}", peak.position),
+                },
+            })
+            .collect();
+        MarkPoint { data }
+    }
+
+    /// Shape fitted to prominent peaks, in axis units. Value of every pixel within fitted
+    /// windows is also returned for charting, NaN elsewhere
+    pub fn fit_peaks(&self, shape: Shape, raman: Option<Side>) -> (Vec<Fit>, Vec<f64>) {
+        let x = self.x_values(raman);
+        let options = FitOptions {
+            shape,
+            ..Default::default()
+        };
+        let fits = fit_detected(&x, &self.intensities, &self.prominent_peaks(), &options);
+        let curve = x
+            .iter()
+            .map(|&x| {
+                fits.iter()
+                    .find(|fit| fit.x_range.start <= x && x <= fit.x_range.end)
+                    .map_or(f64::NAN, |fit| fit.eval(x))
+            })
+            .collect();
+        (fits, curve)
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self.measure {
+            Measure::Intensity if self.raw => "Raw counts",
+            measure => measure.label(),
+        }
+    }
+
+    /// Copy with processing applied, along with estimated baseline if it is shown as an overlay
+    /// instead of being subtracted. Filters go last, like in CLI
+    pub fn processed(&self, options: &ProcessingOptions) -> (Reading, Option<Vec<f64>>) {
+        let mut reading = self.clone();
+        let mut overlay = None;
+        if let Some(baseline) = &options.baseline {
+            match baseline.estimate(&reading.intensities) {
+                Ok(estimate) if options.subtract_baseline => reading
+                    .intensities
+                    .iter_mut()
+                    .zip(estimate)
+                    .for_each(|(y, b)| *y -= b),
+                Ok(estimate) => overlay = Some(estimate),
+                Err(err) => log::warn!("Could not estimate baseline: {err}"),
+            }
+        }
+        if !options.filters.is_empty() {
+            let mut scratch = vec![0.0; reading.intensities.len()];
+            apply_chain(&options.filters, &mut reading.intensities, &mut scratch);
+        }
+        (reading, overlay)
+    }
+
+    pub fn x_axis(&self, raman: Option<Side>) -> AxisOptions {
+        match self.axis(raman) {
+            Some((name, values)) => AxisOptions {
+                name: Some(name.to_string()),
+                data: values.iter().map(|v| format!("{v:.2}")).collect(),
+            },
+            None => AxisOptions {
+                name: Some("Pixel #".to_string()),
+                data: (0..self.intensities.len()).map(|x| x.to_string()).collect(),
+            },
+        }
+    }
+}
+
+
+/// Processing done in the browser, so it can be tuned without taking new readings
+#[derive(Clone, Debug, Default)]
+pub struct ProcessingOptions {
+    pub show_peaks: bool,
+    pub baseline: Option<Baseline>,
+    /// Subtract baseline instead of showing it over the reading
+    pub subtract_baseline: bool,
+    /// Applied one after another
+    pub filters: Vec<Filter>,
+    /// Show Raman shift on given side of laser line instead of wavelength
+    pub raman: Option<Side>,
+    /// Fit this shape to prominent peaks and show fitted curves over the reading
+    pub fit: Option<Shape>,
+}
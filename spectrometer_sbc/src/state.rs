@@ -1,9 +1,11 @@
 use crate::gpio::GpioState;
 use axum::extract::FromRef;
 use leptos::LeptosOptions;
+use spectrometer_processing::Store;
 
 #[derive(FromRef, Debug, Clone)]
 pub struct AppState {
     pub leptos_options: LeptosOptions,
     pub gpio: GpioState,
+    pub store: Store,
 }